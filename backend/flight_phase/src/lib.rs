@@ -0,0 +1,107 @@
+//! Flight phase classification shared by the simulator and the KV server,
+//! so both sides agree on what a packet's phase is.
+
+const GROUND_ALTITUDE: f32 = 2.0;      // Consider on ground if below 2m
+const CRUISE_ALTITUDE: f32 = 140.0;    // Cruise altitude threshold
+const LANDING_ALTITUDE: f32 = 20.0;    // Descending below this is a landing
+const TAKEOFF_SPEED: f32 = 3.0;        // Speed threshold for takeoff roll
+const HOVER_SPEED: f32 = 1.0;          // Airborne and level below this is a hover
+const CLIMB_RATE: f32 = 0.8;           // Minimum climb rate for ascent
+const DESCENT_RATE: f32 = -0.8;        // Descent rate threshold
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlightPhase {
+    OnGround,
+    TakingOff,
+    Ascent,
+    Cruise,
+    Hover,
+    Descent,
+    Landing,
+}
+
+impl FlightPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlightPhase::OnGround => "On Ground",
+            FlightPhase::TakingOff => "Taking Off",
+            FlightPhase::Ascent => "Ascent",
+            FlightPhase::Cruise => "Cruise",
+            FlightPhase::Hover => "Hover",
+            FlightPhase::Descent => "Descent",
+            FlightPhase::Landing => "Landing",
+        }
+    }
+}
+
+/// Classify from barometric altitude (m), ground speed (m/s) and vertical speed (m/s)
+pub fn classify(altitude: f32, ground_speed: f32, vertical_speed: f32) -> FlightPhase {
+    let is_on_ground = altitude < GROUND_ALTITUDE;
+    let is_moving = ground_speed >= TAKEOFF_SPEED;
+    let is_climbing = vertical_speed > CLIMB_RATE;
+    let is_descending = vertical_speed < DESCENT_RATE;
+    
+    // On Ground: stationary or slow movement
+    if is_on_ground && !is_moving {
+        return FlightPhase::OnGround;
+    }
+    
+    // Taking Off: on ground, accelerating
+    if is_on_ground && is_moving {
+        return FlightPhase::TakingOff;
+    }
+    
+    // Landing: low altitude, descending
+    if altitude < LANDING_ALTITUDE && is_descending {
+        return FlightPhase::Landing;
+    }
+    
+    // Hover: airborne, level and holding position
+    if !is_climbing && !is_descending && ground_speed < HOVER_SPEED {
+        return FlightPhase::Hover;
+    }
+    
+    // Ascent: airborne and climbing
+    if is_climbing && altitude < CRUISE_ALTITUDE {
+        return FlightPhase::Ascent;
+    }
+    
+    // Cruise: at or near cruise altitude, level flight
+    if altitude >= CRUISE_ALTITUDE && !is_climbing && !is_descending {
+        return FlightPhase::Cruise;
+    }
+    
+    // Descent: descending from altitude
+    if is_descending && altitude > LANDING_ALTITUDE {
+        return FlightPhase::Descent;
+    }
+    
+    // Still climbing to cruise
+    if is_climbing {
+        return FlightPhase::Ascent;
+    }
+    
+    // Default for airborne but not clearly in a phase
+    FlightPhase::Cruise
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_ground_phases() {
+        assert_eq!(classify(0.0, 0.0, 0.0), FlightPhase::OnGround);
+        assert_eq!(classify(0.5, 8.0, 0.0), FlightPhase::TakingOff);
+    }
+    
+    #[test]
+    fn test_airborne_phases() {
+        assert_eq!(classify(60.0, 20.0, 5.0), FlightPhase::Ascent);
+        assert_eq!(classify(150.0, 25.0, 0.0), FlightPhase::Cruise);
+        assert_eq!(classify(150.0, 0.3, 0.0), FlightPhase::Hover);
+        assert_eq!(classify(60.0, 0.3, 0.0), FlightPhase::Hover);
+        assert_eq!(classify(80.0, 20.0, -3.0), FlightPhase::Descent);
+        assert_eq!(classify(10.0, 12.0, -3.0), FlightPhase::Landing);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::StoreError;
+use crate::store::Store;
+use crate::types::{borrowed_to_owned, CompactionReport, Key, OwnedEntry, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// `Store` for tokio code. Saves, loads and compactions run on the blocking
+/// pool so they don't stall other tasks; reads and writes lock briefly inline.
+/// A save only holds the lock while serializing, not while writing files.
+#[derive(Clone)]
+pub struct AsyncStore {
+    inner: Arc<Mutex<Store>>,
+    // One save writes the files at a time
+    saving: Arc<Mutex<()>>,
+}
+
+impl AsyncStore {
+    pub fn new(store: Store) -> AsyncStore {
+        AsyncStore { inner: Arc::new(Mutex::new(store)), saving: Arc::new(Mutex::new(())) }
+    }
+
+    /// Open or create the store at `path`, like `Store::with_path`
+    pub async fn with_path(path: impl Into<PathBuf>) -> Result<AsyncStore, StoreError> {
+        let path = path.into();
+        let store = unblock(move || Store::with_path(path)).await?;
+        Ok(AsyncStore::new(store))
+    }
+
+    pub async fn load(path: impl Into<PathBuf>) -> Result<AsyncStore, StoreError> {
+        let path = path.into();
+        let store = unblock(move || Store::load(path)).await?;
+        Ok(AsyncStore::new(store))
+    }
+
+    pub async fn put(&self, key: Key, value: Value) {
+        self.inner.lock().await.put(key, value);
+    }
+
+    pub async fn get(&self, key: &Key) -> Result<OwnedEntry, StoreError> {
+        self.inner.lock().await.get(key).map(|entry| borrowed_to_owned(&entry))
+    }
+
+    pub async fn delete(&self, key: &Key) -> Result<(), StoreError> {
+        self.inner.lock().await.delete(key)
+    }
+
+    pub async fn save(&self) -> Result<(), StoreError> {
+        let _saving = self.saving.lock().await;
+        let inner = self.inner.clone();
+        unblock(move || {
+            let prepared = inner.blocking_lock().begin_save();
+            let result = prepared.and_then(|(base_path, snapshot)| {
+                Store::commit_save(&snapshot, base_path).map(|()| snapshot)
+            });
+            inner.blocking_lock().finish_save(result)
+        }).await
+    }
+
+    pub async fn compact(&self) -> Result<CompactionReport, StoreError> {
+        let inner = self.inner.clone();
+        unblock(move || inner.blocking_lock().compact()).await
+    }
+}
+
+async fn unblock<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::remove_store_files;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_save_does_not_block_runtime() -> Result<(), StoreError> {
+        let path = "/tmp/test_store_async";
+        remove_store_files(path);
+
+        let store = AsyncStore::with_path(path).await?;
+        for i in 0..50_000 {
+            store.put(Key::Int(i), Value::String(format!("value number {}", i))).await;
+        }
+
+        // On a single-threaded runtime this only advances while save() is
+        // parked, writing to the store in the meantime
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            let store = store.clone();
+            async move {
+                loop {
+                    let tick = ticks.fetch_add(1, Ordering::Relaxed) as i64;
+                    store.put(Key::Int(100_000 + tick), Value::Int(tick)).await;
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+
+        store.save().await?;
+        ticker.abort();
+        let ticks = ticks.load(Ordering::Relaxed) as i64;
+        assert!(ticks > 0);
+        // Whatever came in during the save goes out with the next one
+        store.save().await?;
+
+        assert_eq!(store.get(&Key::Int(42)).await?, OwnedEntry::Text("value number 42".into()));
+        drop(store);
+        let reloaded = AsyncStore::load(path).await?;
+        assert_eq!(reloaded.get(&Key::Int(49_999)).await?, OwnedEntry::Text("value number 49999".into()));
+        assert_eq!(reloaded.get(&Key::Int(100_000 + ticks - 1)).await?, OwnedEntry::Int(ticks - 1));
+        drop(reloaded);
+
+        remove_store_files(path);
+        Ok(())
+    }
+}
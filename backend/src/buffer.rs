@@ -0,0 +1,49 @@
+use memmap2::Mmap;
+use std::ops::Deref;
+
+/// Value bytes of a store: owned, or a read-only map of the `.data` file
+/// that is copied into memory on the first write.
+pub(crate) enum DataBuffer {
+    Owned(Vec<u8>),
+    /// The values start `start` bytes into the file, after its header
+    Mapped { map: Mmap, start: usize },
+}
+
+impl DataBuffer {
+    pub(crate) fn to_mut(&mut self) -> &mut Vec<u8> {
+        if let DataBuffer::Mapped { map, start } = self {
+            *self = DataBuffer::Owned(map[*start..].to_vec());
+        }
+        match self {
+            DataBuffer::Owned(data) => data,
+            DataBuffer::Mapped { .. } => unreachable!(),
+        }
+    }
+
+    pub(crate) fn is_mapped(&self) -> bool {
+        matches!(self, DataBuffer::Mapped { .. })
+    }
+}
+
+impl Default for DataBuffer {
+    fn default() -> Self {
+        DataBuffer::Owned(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for DataBuffer {
+    fn from(data: Vec<u8>) -> Self {
+        DataBuffer::Owned(data)
+    }
+}
+
+impl Deref for DataBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            DataBuffer::Owned(data) => data,
+            DataBuffer::Mapped { map, start } => &map[*start..],
+        }
+    }
+}
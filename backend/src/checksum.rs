@@ -0,0 +1,38 @@
+use crate::serialization::calculate_crc32;
+
+/// Algorithm used for the file checksums recorded in `.meta`.
+/// Value headers always carry their own CRC32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checksum {
+    #[default]
+    Crc32,
+    Crc32c,
+    Xxhash64,
+}
+
+impl Checksum {
+    pub fn compute(self, data: &[u8]) -> u64 {
+        match self {
+            Checksum::Crc32 => calculate_crc32(data) as u64,
+            Checksum::Crc32c => crc32c::crc32c(data) as u64,
+            Checksum::Xxhash64 => xxhash_rust::xxh64::xxh64(data, 0),
+        }
+    }
+
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Checksum::Crc32 => 0,
+            Checksum::Crc32c => 1,
+            Checksum::Xxhash64 => 2,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Option<Checksum> {
+        match id {
+            0 => Some(Checksum::Crc32),
+            1 => Some(Checksum::Crc32c),
+            2 => Some(Checksum::Xxhash64),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::checksum::Checksum;
+
+/// Tuning for a `Store`. Build one with `StoreConfig::builder()`, or start
+/// from `StoreConfig::default()` and adjust with the `with_*` methods.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoreConfig {
+    /// Fragmentation ratio above which `save` compacts first
+    pub compaction_threshold: f64,
+    /// Checksum written to `.meta`. None keeps the one a loaded store was
+    /// saved with, and uses the default for a new store.
+    pub checksum: Option<Checksum>,
+    /// What reads do with a value that fails to deserialize
+    pub corruption_policy: CorruptionPolicy,
+    /// Largest `.data` file loading may read into memory, unlimited if None
+    pub memory_budget: Option<u64>,
+}
+
+/// How reads treat a value whose bytes are damaged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptionPolicy {
+    /// Return `DataCorruption` or `InvalidData`
+    #[default]
+    FailFast,
+    /// Act as if the key were missing: `get` returns `KeyNotFound` and the
+    /// iterators leave the entry out. `errors` still reports it.
+    SkipCorrupt,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            compaction_threshold: 0.35,
+            checksum: None,
+            corruption_policy: CorruptionPolicy::default(),
+            memory_budget: None,
+        }
+    }
+}
+
+impl StoreConfig {
+    pub fn builder() -> StoreConfigBuilder {
+        StoreConfigBuilder::default()
+    }
+
+    pub fn with_compaction_threshold(mut self, threshold: f64) -> StoreConfig {
+        self.compaction_threshold = threshold;
+        self
+    }
+
+    pub fn with_checksum(mut self, checksum: Checksum) -> StoreConfig {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    pub fn with_corruption_policy(mut self, policy: CorruptionPolicy) -> StoreConfig {
+        self.corruption_policy = policy;
+        self
+    }
+
+    pub fn with_memory_budget(mut self, bytes: u64) -> StoreConfig {
+        self.memory_budget = Some(bytes);
+        self
+    }
+}
+
+/// Builder for a `StoreConfig`, starting from the defaults:
+/// `StoreConfig::builder().compaction_threshold(0.5).build()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StoreConfigBuilder {
+    config: StoreConfig,
+}
+
+impl StoreConfigBuilder {
+    pub fn compaction_threshold(self, threshold: f64) -> StoreConfigBuilder {
+        StoreConfigBuilder { config: self.config.with_compaction_threshold(threshold) }
+    }
+
+    pub fn checksum(self, checksum: Checksum) -> StoreConfigBuilder {
+        StoreConfigBuilder { config: self.config.with_checksum(checksum) }
+    }
+
+    pub fn corruption_policy(self, policy: CorruptionPolicy) -> StoreConfigBuilder {
+        StoreConfigBuilder { config: self.config.with_corruption_policy(policy) }
+    }
+
+    pub fn memory_budget(self, bytes: u64) -> StoreConfigBuilder {
+        StoreConfigBuilder { config: self.config.with_memory_budget(bytes) }
+    }
+
+    pub fn build(self) -> StoreConfig {
+        self.config
+    }
+}
@@ -12,6 +12,15 @@ pub enum DeserializationError {
     #[error("Unknown tag value: 0x{0:02x}")]
     UnknownTag(u8),
 
+    #[error("Declared length does not fit in memory")]
+    LengthOverflow,
+
+    #[error("Invalid bool byte: 0x{0:02x}")]
+    InvalidBool(u8),
+
+    #[error("Payload too long: expected {expected} bytes, got {actual}")]
+    TrailingBytes { expected: usize, actual: usize },
+
     #[error("Checksum mismatch: expected 0x{expected:08x}, got 0x{actual:08x}")]
     ChecksumMismatch { expected: u32, actual: u32 },
 
@@ -42,6 +51,32 @@ pub enum StoreError {
     #[error("File corrupted: checksum mismatch")]
     FileCorrupted,
 
+    #[error("Offset {offset} for {key:?} is not the start of a value")]
+    BadOffset {
+        key: Key,
+        offset: usize,
+        #[source]
+        cause: DeserializationError,
+    },
+
     #[error("Unsupported file version: {0}")]
     UnsupportedVersion(u32),
+
+    #[error("Unsupported checksum algorithm: {0}")]
+    UnsupportedChecksum(u8),
+
+    #[error("Not a kiwi-store file: {}", .0.display())]
+    BadMagic(std::path::PathBuf),
+
+    #[error("Data file is {size} bytes, over the memory budget of {budget}; use load_mmap instead")]
+    TooLargeForMemory { size: u64, budget: u64 },
+
+    #[error("Invalid store path: {0}")]
+    InvalidPath(std::path::PathBuf),
+
+    #[error("Type mismatch: expected {expected}, found {found}")]
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
 }
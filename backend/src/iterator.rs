@@ -1,6 +1,6 @@
 use crate::types::{Key, BorrowedEntry};
 use crate::error::{StoreError, DeserializationError};
-use crate::serialization::deserialize_value;
+use crate::serialization::{deserialize_value, check_value_header};
 use crate::Store;
 
 pub struct StoreIterator<'a> {
@@ -9,6 +9,14 @@ pub struct StoreIterator<'a> {
 }
 
 pub struct StoreIter<'a> {
+    pub(crate) scan: DataScan<'a>,
+    pub(crate) skip_corrupt: bool,
+}
+
+/// Every value in `.data` order with its offset, live or not. A value that
+/// can't be read is reported and stepped over; the scan stops after one
+/// whose header is damaged too, since the next one can't be located.
+pub struct DataScan<'a> {
     pub(crate) buf: &'a [u8],
     pub(crate) pos: usize,
 }
@@ -17,34 +25,56 @@ impl<'a> Iterator for StoreIterator<'a> {
     type Item = (&'a Key, Result<BorrowedEntry<'a>, StoreError>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let key = self.keys_iter.next()?;
-        let value = self.store.get(&key);
-        Some((key, value))
+        loop {
+            let key = self.keys_iter.next()?;
+            match self.store.get(key) {
+                // Only a skipped corrupt value goes missing for a key in the index
+                Err(StoreError::KeyNotFound(_)) => continue,
+                value => return Some((key, value)),
+            }
+        }
     }
 }
 
 impl<'a> Iterator for StoreIter<'a> {
     type Item = Result<BorrowedEntry<'a>, StoreError>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.scan.next()? {
+                (_, Err(_)) if self.skip_corrupt => continue,
+                (_, entry) => return Some(entry),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for DataScan<'a> {
+    type Item = (usize, Result<BorrowedEntry<'a>, StoreError>);
+
     fn next(&mut self) -> Option<Self::Item> {
         if self.pos >= self.buf.len() {
             return None;
         }
 
-        match deserialize_value(&self.buf[self.pos..]) {
+        let offset = self.pos;
+        match deserialize_value(&self.buf[offset..]) {
             Ok((entry, bytes_read)) => {
                 self.pos += bytes_read;
-                Some(Ok(entry))
+                Some((offset, Ok(entry)))
             }
             Err(e) => {
-                self.pos = self.buf.len();
+                self.pos = match check_value_header(&self.buf[offset..]) {
+                    Ok(total) => offset + total,
+                    Err(_) => self.buf.len(),
+                };
                 let store_error = match e {
                     DeserializationError::ChecksumMismatch { .. } => {
                         StoreError::DataCorruption { cause: e }
                     }
                     _ => StoreError::InvalidData { cause: e }
                 };
-                Some(Err(store_error))
+                Some((offset, Err(store_error)))
             }
         }
     }
@@ -7,15 +7,13 @@ pub(crate) struct RawHeader {
     pub(crate) tag: u8,
 }
 
-pub(crate) unsafe fn serialize_header_unsafe(header: &RawHeader, buffer: &mut Vec<u8>) {
+/// Copy `header` over the start of `dest`, which must hold at least a header
+pub(crate) unsafe fn write_header_unsafe(header: &RawHeader, dest: &mut [u8]) {
     let header_size = size_of::<RawHeader>();
-    let offset = buffer.len();
-    buffer.reserve(header_size);
+    assert!(dest.len() >= header_size);
     unsafe {
-        buffer.set_len(offset + header_size);
-        let dest_ptr = buffer.as_mut_ptr().add(offset);
         let header_ptr = header as *const RawHeader;
-        ptr::copy_nonoverlapping(header_ptr as *const u8, dest_ptr, header_size);
+        ptr::copy_nonoverlapping(header_ptr as *const u8, dest.as_mut_ptr(), header_size);
     }
 }
 
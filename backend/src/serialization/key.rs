@@ -18,7 +18,39 @@ pub(crate) fn serialize_key(key: &Key) -> Vec<u8> {
             out.extend_from_slice(&i.to_le_bytes());
             out
         }
+        Key::Bytes(b) => {
+            let mut out = Vec::new();
+            out.push(0x03u8);
+            out.extend_from_slice(&(b.len() as u64).to_le_bytes());
+            out.extend_from_slice(b);
+            out
+        }
+    }
+}
+
+/// The length-prefixed payload after a String or Bytes tag, and where it ends
+fn length_prefixed(bytes: &[u8]) -> Result<(&[u8], usize), DeserializationError> {
+    if bytes.len() < 9 {
+        return Err(DeserializationError::BufferTooShort {
+            expected: 9,
+            actual: bytes.len(),
+        });
     }
+    let len = u64::from_le_bytes(
+        bytes[1..9].try_into()
+            .map_err(|_| DeserializationError::ByteConversionError)?
+    );
+    let end = usize::try_from(len).ok()
+        .and_then(|len| len.checked_add(9))
+        .ok_or(DeserializationError::LengthOverflow)?;
+
+    if bytes.len() < end {
+        return Err(DeserializationError::BufferTooShort {
+            expected: end,
+            actual: bytes.len(),
+        });
+    }
+    Ok((&bytes[9..end], end))
 }
 
 pub(crate) fn deserialize_key(bytes: &[u8]) -> Result<(Key, usize), DeserializationError> {
@@ -32,26 +64,9 @@ pub(crate) fn deserialize_key(bytes: &[u8]) -> Result<(Key, usize), Deserializat
     let tag = bytes[0];
     match tag {
         0x01 => {
-            if bytes.len() < 9 {
-                return Err(DeserializationError::BufferTooShort {
-                    expected: 9,
-                    actual: bytes.len(),
-                });
-            }
-            let len = u64::from_le_bytes(
-                bytes[1..9].try_into()
-                    .map_err(|_| DeserializationError::ByteConversionError)?
-            ) as usize;
-
-            if bytes.len() < 9 + len {
-                return Err(DeserializationError::BufferTooShort {
-                    expected: 9 + len,
-                    actual: bytes.len(),
-                });
-            }
-
-            let s = std::str::from_utf8(&bytes[9..9 + len])?;
-            Ok((Key::String(s.to_string()), 9 + len))
+            let (payload, end) = length_prefixed(bytes)?;
+            let s = std::str::from_utf8(payload)?;
+            Ok((Key::String(s.to_string()), end))
         }
         0x02 => {
             if bytes.len() < 9 {
@@ -66,6 +81,10 @@ pub(crate) fn deserialize_key(bytes: &[u8]) -> Result<(Key, usize), Deserializat
             );
             Ok((Key::Int(i), 9))
         }
+        0x03 => {
+            let (payload, end) = length_prefixed(bytes)?;
+            Ok((Key::Bytes(payload.to_vec()), end))
+        }
         _ => Err(DeserializationError::UnknownTag(tag)),
     }
 }
@@ -4,8 +4,10 @@ mod value;
 
 pub(crate) use header::calculate_crc32;
 pub(crate) use key::{serialize_key, deserialize_key};
-pub(crate) use value::{serialize_value, deserialize_value};
+pub(crate) use value::{serialize_value_into, deserialize_value, value_payload, unchecked_payload, check_value_header};
 
-// Re-export RawHeader only for tests
+// Re-export RawHeader and the allocating serializer only for tests
 #[cfg(test)]
 pub(crate) use header::RawHeader;
+#[cfg(test)]
+pub(crate) use value::serialize_value;
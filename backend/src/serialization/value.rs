@@ -1,43 +1,95 @@
 use std::convert::TryInto;
 use crate::types::{Value, BorrowedEntry};
 use crate::error::DeserializationError;
-use super::header::{RawHeader, serialize_header_unsafe, deserialize_header_unsafe, calculate_crc32};
+use super::header::{RawHeader, write_header_unsafe, deserialize_header_unsafe, calculate_crc32};
 
+#[cfg(test)]
 pub(crate) fn serialize_value(value: &Value) -> Vec<u8> {
-    let (tag, value_data) = match value {
+    let mut out = Vec::new();
+    serialize_value_into(value, &mut out);
+    out
+}
+
+/// Append the serialized `value` to `out`
+pub(crate) fn serialize_value_into(value: &Value, out: &mut Vec<u8>) {
+    let header_size = size_of::<RawHeader>();
+    let payload_len = match value {
+        Value::String(s) => 8 + s.len(),
+        Value::Int(_) => 8,
+        Value::Bool(_) => 1,
+        Value::Bytes(b) => 8 + b.len(),
+    };
+
+    // Reserve the header, append the payload, then backfill the header
+    let start = out.len();
+    out.reserve(header_size + payload_len);
+    out.resize(start + header_size, 0);
+    let tag = match value {
         Value::String(s) => {
-            let mut v = Vec::new();
             let b = s.as_bytes();
-            v.extend_from_slice(&(b.len() as u64).to_le_bytes());
-            v.extend_from_slice(b);
-            (0x01u8, v)
+            out.extend_from_slice(&(b.len() as u64).to_le_bytes());
+            out.extend_from_slice(b);
+            0x01u8
+        }
+        Value::Int(i) => {
+            out.extend_from_slice(&i.to_le_bytes());
+            0x02u8
+        }
+        Value::Bool(b) => {
+            out.push(*b as u8);
+            0x05u8
+        }
+        Value::Bytes(b) => {
+            out.extend_from_slice(&(b.len() as u64).to_le_bytes());
+            out.extend_from_slice(b);
+            0x03u8
         }
-        Value::Int(i) => (0x02u8, i.to_le_bytes().to_vec()),
     };
 
-    let checksum = calculate_crc32(&value_data);
-
     let header = RawHeader {
-        length: value_data.len() as u64,
-        checksum,
+        length: payload_len as u64,
+        checksum: calculate_crc32(&out[start + header_size..]),
         tag,
     };
-
-    let mut out = Vec::new();
-    unsafe { serialize_header_unsafe(&header, &mut out) };
-    out.extend_from_slice(&value_data);
-    out
+    unsafe { write_header_unsafe(&header, &mut out[start..start + header_size]) };
 }
 
-pub(crate) fn deserialize_value(bytes: &[u8]) -> Result<(BorrowedEntry, usize), DeserializationError> {
-    let header_size = size_of::<RawHeader>();
-    if bytes.len() < header_size {
-        return Err(DeserializationError::BufferTooShort {
-            expected: header_size,
-            actual: bytes.len(),
+/// Tag and checksum-verified payload of the value at the start of `bytes`,
+/// plus the bytes it occupies including the header
+pub(crate) fn value_payload(bytes: &[u8]) -> Result<(u8, &[u8], usize), DeserializationError> {
+    let (header, total) = value_header(bytes)?;
+    let value_data = &bytes[size_of::<RawHeader>()..total];
+
+    let actual = calculate_crc32(value_data);
+    if actual != header.checksum {
+        return Err(DeserializationError::ChecksumMismatch {
+            expected: header.checksum,
+            actual,
         });
     }
 
+    Ok((header.tag, value_data, total))
+}
+
+/// The payload of the value `bytes` starts with, whether or not it still
+/// matches its checksum
+pub(crate) fn unchecked_payload(bytes: &[u8]) -> Result<&[u8], DeserializationError> {
+    let (_, total) = value_header(bytes)?;
+    Ok(&bytes[size_of::<RawHeader>()..total])
+}
+
+/// Checks that `bytes` starts with a plausible value without reading the
+/// payload: a known tag and a length that fits. Returns the bytes it occupies.
+pub(crate) fn check_value_header(bytes: &[u8]) -> Result<usize, DeserializationError> {
+    let (header, total) = value_header(bytes)?;
+    match header.tag {
+        0x01 | 0x02 | 0x03 | 0x05 => Ok(total),
+        tag => Err(DeserializationError::UnknownTag(tag)),
+    }
+}
+
+fn value_header(bytes: &[u8]) -> Result<(RawHeader, usize), DeserializationError> {
+    let header_size = size_of::<RawHeader>();
     let header = unsafe {
         deserialize_header_unsafe(bytes)
             .ok_or(DeserializationError::BufferTooShort {
@@ -46,47 +98,53 @@ pub(crate) fn deserialize_value(bytes: &[u8]) -> Result<(BorrowedEntry, usize),
             })?
     };
 
-    let length = header.length as usize;
-    if bytes.len() < header_size + length {
+    // Lengths come from disk or the radio link, so guard the arithmetic
+    let length = usize::try_from(header.length)
+        .map_err(|_| DeserializationError::LengthOverflow)?;
+    let total = header_size.checked_add(length)
+        .ok_or(DeserializationError::LengthOverflow)?;
+    if bytes.len() < total {
         return Err(DeserializationError::BufferTooShort {
-            expected: header_size + length,
+            expected: total,
             actual: bytes.len(),
         });
     }
 
-    let value_data = &bytes[header_size..header_size + length];
+    Ok((header, total))
+}
 
-    let actual = calculate_crc32(value_data);
-    if actual != header.checksum {
-        return Err(DeserializationError::ChecksumMismatch {
-            expected: header.checksum,
-            actual,
+/// The bytes behind the u64 length that starts a String or Bytes payload
+fn length_prefixed(value_data: &[u8]) -> Result<&[u8], DeserializationError> {
+    if value_data.len() < 8 {
+        return Err(DeserializationError::BufferTooShort {
+            expected: 8,
+            actual: value_data.len(),
         });
     }
+    let len = u64::from_le_bytes(
+        value_data[0..8].try_into()
+            .map_err(|_| DeserializationError::ByteConversionError)?
+    );
+    let end = usize::try_from(len).ok()
+        .and_then(|len| len.checked_add(8))
+        .ok_or(DeserializationError::LengthOverflow)?;
 
-    match header.tag {
-        0x01 => {
-            if value_data.len() < 8 {
-                return Err(DeserializationError::BufferTooShort {
-                    expected: 8,
-                    actual: value_data.len(),
-                });
-            }
-            let len = u64::from_le_bytes(
-                value_data[0..8].try_into()
-                    .map_err(|_| DeserializationError::ByteConversionError)?
-            ) as usize;
-
-            if value_data.len() < 8 + len {
-                return Err(DeserializationError::BufferTooShort {
-                    expected: 8 + len,
-                    actual: value_data.len(),
-                });
-            }
+    if value_data.len() < end {
+        return Err(DeserializationError::BufferTooShort {
+            expected: end,
+            actual: value_data.len(),
+        });
+    }
+    Ok(&value_data[8..end])
+}
 
-            let s = std::str::from_utf8(&value_data[8..8 + len])?;
+pub(crate) fn deserialize_value(bytes: &[u8]) -> Result<(BorrowedEntry, usize), DeserializationError> {
+    let (tag, value_data, total) = value_payload(bytes)?;
 
-            Ok((BorrowedEntry::Text(s), header_size + length))
+    match tag {
+        0x01 => {
+            let s = std::str::from_utf8(length_prefixed(value_data)?)?;
+            Ok((BorrowedEntry::Text(s), total))
         }
         0x02 => {
             if value_data.len() < 8 {
@@ -99,8 +157,24 @@ pub(crate) fn deserialize_value(bytes: &[u8]) -> Result<(BorrowedEntry, usize),
                 value_data[0..8].try_into()
                     .map_err(|_| DeserializationError::ByteConversionError)?
             );
-            Ok((BorrowedEntry::Int(v), header_size + length))
+            Ok((BorrowedEntry::Int(v), total))
+        }
+        0x03 => Ok((BorrowedEntry::Bytes(length_prefixed(value_data)?), total)),
+        0x05 => {
+            match value_data {
+                [0] => Ok((BorrowedEntry::Bool(false), total)),
+                [1] => Ok((BorrowedEntry::Bool(true), total)),
+                &[byte] => Err(DeserializationError::InvalidBool(byte)),
+                [] => Err(DeserializationError::BufferTooShort {
+                    expected: 1,
+                    actual: 0,
+                }),
+                _ => Err(DeserializationError::TrailingBytes {
+                    expected: 1,
+                    actual: value_data.len(),
+                }),
+            }
         }
-        _ => Err(DeserializationError::UnknownTag(header.tag)),
+        _ => Err(DeserializationError::UnknownTag(tag)),
     }
 }
@@ -1,36 +1,160 @@
-use crate::types::{Key, Value, BorrowedEntry};
+use crate::types::{Key, Value, BorrowedEntry, OwnedEntry, CompactionReport, CompactionProgress, StoreDiff, TypeHistogram, borrowed_to_owned};
 use crate::error::{StoreError, DeserializationError};
-use crate::serialization::{serialize_value, deserialize_value, serialize_key, deserialize_key, calculate_crc32};
-use crate::iterator::{StoreIterator, StoreIter};
+use crate::serialization::{serialize_value_into, deserialize_value, value_payload, unchecked_payload, check_value_header, serialize_key, deserialize_key, calculate_crc32};
+use crate::checksum::Checksum;
+use crate::config::{StoreConfig, CorruptionPolicy};
+use crate::buffer::DataBuffer;
+use crate::iterator::{StoreIterator, StoreIter, DataScan};
+use crate::transaction::{Transaction, Op};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::fs;
-
-const FILE_VERSION: u32 = 1;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+
+// The meta version holds the major in its low 16 bits and the minor in the
+// high 16. A minor may only append meta fields, never change existing ones,
+// so minors of the current major up to MAX_SUPPORTED_MINOR load: trailing
+// meta bytes this build doesn't know are ignored. A higher minor is refused,
+// and anything else needs a new major.
+const FILE_VERSION: u32 = 4;
+const FILE_MINOR_VERSION: u32 = 0;
+const MAX_SUPPORTED_MINOR: u32 = 0xFF;
+// Version 3: no file headers, otherwise the current layout
+const HEADERLESS_FILE_VERSION: u32 = 3;
+// Version 2: same meta, but key records have no CRC of their own
+const UNCHECKED_KEYS_FILE_VERSION: u32 = 2;
+// Version 1 meta: CRC32 only, 32-bit checksums
+const LEGACY_FILE_VERSION: u32 = 1;
+// Key length (4) + smallest serialized key, an Int (9) + offset (8)
+const MIN_KEY_RECORD_LEN: usize = 4 + 9 + 8;
+// CRC32 closing each key record since version 3
+const KEY_RECORD_CRC_LEN: usize = 4;
+// Since version 4 every file starts with MAGIC and a byte naming the file
+const MAGIC: &[u8; 4] = b"KIWI";
+const FILE_HEADER_LEN: usize = 5;
+const META_FILE: u8 = b'M';
+const KEYS_FILE: u8 = b'K';
+const DATA_FILE: u8 = b'D';
 
 pub struct Store {
     index: HashMap<Key, usize>,
-    data: Vec<u8>,
+    data: DataBuffer,
     path: Option<PathBuf>,
+    last_save_error: Option<String>,
+    compaction: Option<IncrementalCompaction>,
+    // Prefix of `data` already in the .data file, None forces a full rewrite
+    persisted_len: Option<usize>,
+    // Bumped whenever `data` is replaced, so older snapshots aren't marked saved
+    rewrites: u64,
+    config: StoreConfig,
+}
+
+/// Serialized contents of a store, produced by `Store::prepare_save` and
+/// written by `Store::commit_save`
+pub struct SaveSnapshot {
+    meta: Vec<u8>,
+    keys: Vec<u8>,
+    // Bytes of the .data file from `data_start` on
+    data: Vec<u8>,
+    data_start: usize,
+    rewrites: u64,
+}
+
+/// Partially built buffer of an in-progress `compact_incremental`
+struct IncrementalCompaction {
+    pending: Vec<Key>,
+    // Length of `data` when `pending` was filled; later writes wait for the next fill
+    pending_until: usize,
+    new_data: Vec<u8>,
+    new_index: HashMap<Key, usize>,
+    relocated: HashMap<usize, usize>,
 }
 
 impl Store {
     pub fn new() -> Store {
         Store {
             index: HashMap::new(),
-            data: Vec::new(),
+            data: DataBuffer::default(),
             path: None,
+            last_save_error: None,
+            compaction: None,
+            persisted_len: None,
+            rewrites: 0,
+            config: StoreConfig::default(),
         }
     }
 
+    /// Serializes straight into the data buffer, so a put allocates nothing
+    /// beyond the buffer's own growth
     pub fn put(&mut self, key: Key, value: Value) {
         let pos = self.data.len();
-        let serialized = serialize_value(&value);
-        self.data.extend_from_slice(&serialized);
+        serialize_value_into(&value, self.data.to_mut());
+        self.index_put(key, pos);
+    }
+
+    fn index_put(&mut self, key: Key, pos: usize) {
+        if let Some(compaction) = &mut self.compaction {
+            compaction.new_index.remove(&key);
+        }
         self.index.insert(key, pos);
     }
 
     pub fn get<'a>(&'a self, key: &Key) -> Result<BorrowedEntry<'a>, StoreError> {
+        self.apply_corruption_policy(key, self.read(key))
+    }
+
+    // `get` regardless of the corruption policy
+    fn read<'a>(&'a self, key: &Key) -> Result<BorrowedEntry<'a>, StoreError> {
+        let (entry, _) = deserialize_value(self.value_bytes(key)?)
+            .map_err(Self::read_error)?;
+
+        Ok(entry)
+    }
+
+    /// The stored payload of a value, checksum-verified but not interpreted.
+    /// Strings keep their 8-byte length prefix.
+    pub fn get_raw(&self, key: &Key) -> Result<&[u8], StoreError> {
+        let payload = value_payload(self.value_bytes(key)?)
+            .map(|(_, payload, _)| payload)
+            .map_err(Self::read_error);
+
+        self.apply_corruption_policy(key, payload)
+    }
+
+    /// How reads treat damaged values from now on
+    pub fn set_corruption_policy(&mut self, policy: CorruptionPolicy) {
+        self.config.corruption_policy = policy;
+    }
+
+    fn apply_corruption_policy<T>(&self, key: &Key, result: Result<T, StoreError>) -> Result<T, StoreError> {
+        match result {
+            Err(StoreError::DataCorruption { .. } | StoreError::InvalidData { .. })
+                if self.config.corruption_policy == CorruptionPolicy::SkipCorrupt =>
+            {
+                Err(StoreError::KeyNotFound(key.clone()))
+            }
+            result => result,
+        }
+    }
+
+    /// Keys whose raw payload contains `needle`, for tracking down records by
+    /// content. Checksums are not verified, so damaged records are found too;
+    /// only values whose header is unreadable are skipped. Scans every live value.
+    pub fn find_values_containing(&self, needle: &[u8]) -> Vec<&Key> {
+        self.index.keys()
+            .filter(|key| {
+                let payload = self.value_bytes(key)
+                    .and_then(|bytes| unchecked_payload(bytes).map_err(Self::read_error));
+                match payload {
+                    Ok(payload) => needle.is_empty() || payload.windows(needle.len()).any(|w| w == needle),
+                    Err(_) => false,
+                }
+            })
+            .collect()
+    }
+
+    // Data from the value of `key` to the end of the buffer
+    fn value_bytes(&self, key: &Key) -> Result<&[u8], StoreError> {
         let pos = *self.index.get(key)
             .ok_or_else(|| StoreError::KeyNotFound(key.clone()))?;
 
@@ -43,65 +167,329 @@ impl Store {
             });
         }
 
-        let (entry, _) = deserialize_value(&self.data[pos..])
-            .map_err(|cause| {
-                match cause {
-                    DeserializationError::ChecksumMismatch { .. } => {
-                        StoreError::DataCorruption { cause }
-                    }
-                    _ => StoreError::InvalidData { cause }
-                }
-            })?;
+        Ok(&self.data[pos..])
+    }
 
-        Ok(entry)
+    fn read_error(cause: DeserializationError) -> StoreError {
+        match cause {
+            DeserializationError::ChecksumMismatch { .. } => {
+                StoreError::DataCorruption { cause }
+            }
+            _ => StoreError::InvalidData { cause }
+        }
+    }
+
+    pub fn get_int(&self, key: &Key) -> Result<i64, StoreError> {
+        match self.get(key)? {
+            BorrowedEntry::Int(i) => Ok(i),
+            other => Err(StoreError::TypeMismatch { expected: "Int", found: other.type_name() }),
+        }
+    }
+
+    pub fn get_bool(&self, key: &Key) -> Result<bool, StoreError> {
+        match self.get(key)? {
+            BorrowedEntry::Bool(b) => Ok(b),
+            other => Err(StoreError::TypeMismatch { expected: "Bool", found: other.type_name() }),
+        }
+    }
+
+    pub fn get_bytes<'a>(&'a self, key: &Key) -> Result<&'a [u8], StoreError> {
+        match self.get(key)? {
+            BorrowedEntry::Bytes(b) => Ok(b),
+            other => Err(StoreError::TypeMismatch { expected: "Bytes", found: other.type_name() }),
+        }
+    }
+
+    pub fn get_str<'a>(&'a self, key: &Key) -> Result<&'a str, StoreError> {
+        match self.get(key)? {
+            BorrowedEntry::Text(s) => Ok(s),
+            other => Err(StoreError::TypeMismatch { expected: "Text", found: other.type_name() }),
+        }
     }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Best-effort read: missing and unreadable keys both give None
+    pub fn peek(&self, key: &Key) -> Option<OwnedEntry> {
+        self.get(key).ok().map(|entry| borrowed_to_owned(&entry))
+    }
+
+    /// Look up several keys at once, results line up with `keys`
+    pub fn get_batch<'a>(&'a self, keys: &[Key]) -> Vec<Result<BorrowedEntry<'a>, StoreError>> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
     pub fn delete(&mut self, key: &Key) -> Result<(), StoreError> {
         self.index.remove(key)
             .ok_or_else(|| StoreError::KeyNotFound(key.clone()))?;
+        if let Some(compaction) = &mut self.compaction {
+            compaction.new_index.remove(key);
+        }
         Ok(())
     }
-    pub fn compact(&mut self) -> Result<usize, StoreError> {
+
+    /// Point `new` at the value of `old` without copying it, replacing any
+    /// value `new` had
+    pub fn rename_key(&mut self, old: &Key, new: &Key) -> Result<(), StoreError> {
+        let offset = self.index.remove(old)
+            .ok_or_else(|| StoreError::KeyNotFound(old.clone()))?;
+        self.index.insert(new.clone(), offset);
+        if let Some(compaction) = &mut self.compaction {
+            match compaction.new_index.remove(old) {
+                Some(new_offset) => compaction.new_index.insert(new.clone(), new_offset),
+                None => compaction.new_index.remove(new),
+            };
+        }
+        Ok(())
+    }
+
+    /// Stage writes in `f` and apply all of them if it returns Ok. On Err
+    /// the store is left untouched.
+    ///
+    /// ```ignore
+    /// store.transaction(|tx| {
+    ///     tx.rename_key(&Key::from("draft"), &Key::from("final"))?;
+    ///     tx.delete(&Key::from("lock"))
+    /// })?;
+    /// ```
+    pub fn transaction<T, F>(&mut self, f: F) -> Result<T, StoreError>
+    where
+        F: FnOnce(&mut Transaction) -> Result<T, StoreError>,
+    {
+        let mut tx = Transaction::new(self);
+        let result = f(&mut tx)?;
+        for op in tx.ops {
+            // Deletes and renames were checked when staged
+            match op {
+                Op::Put(key, value) => self.put(key, value),
+                Op::Delete(key) => { let _ = self.delete(&key); }
+                Op::Rename(old, new) => { let _ = self.rename_key(&old, &new); }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn compact(&mut self) -> Result<CompactionReport, StoreError> {
+        self.compaction = None;
         let old_size = self.data.len();
         let mut new_data = Vec::new();
         let mut new_index = HashMap::new();
+        // Keys sharing an old offset must keep sharing one copy
+        let mut relocated: HashMap<usize, usize> = HashMap::new();
+
+        // Copy in offset order so `keys_in_order` survives compaction
+        let mut entries: Vec<(&Key, &usize)> = self.index.iter().collect();
+        entries.sort_by_key(|&(_, offset)| *offset);
+
+        for (key, old_offset) in entries {
+            if let Some(&new_offset) = relocated.get(old_offset) {
+                new_index.insert(key.clone(), new_offset);
+                continue;
+            }
 
-        for (key, old_offset) in &self.index {
             let new_offset = new_data.len();
             let (_, bytes_to_copy) = deserialize_value(&self.data[*old_offset..])
                 .map_err(|cause| StoreError::InvalidData { cause })?;
 
             new_data.extend_from_slice(&self.data[*old_offset..*old_offset + bytes_to_copy]);
 
+            debug_assert_eq!(
+                deserialize_value(&new_data[new_offset..]).ok(),
+                deserialize_value(&self.data[*old_offset..]).ok(),
+                "compaction changed the value of {:?}", key
+            );
+
+            relocated.insert(*old_offset, new_offset);
             new_index.insert(key.clone(), new_offset);
         }
 
-        let bytes_reclaimed = old_size - new_data.len();
-        self.data = new_data;
+        let report = CompactionReport {
+            bytes_reclaimed: old_size - new_data.len(),
+            entries_retained: new_index.len(),
+        };
+        self.data = new_data.into();
+        self.persisted_len = None;
+        self.rewrites += 1;
         self.index = new_index;
 
-        Ok(bytes_reclaimed)
+        Ok(report)
+    }
+
+    /// Relocate roughly `max_bytes` of live data into a fresh buffer.
+    /// Writes between calls are picked up before the buffers are swapped; an
+    /// entry rewritten after it was moved leaves its old copy until the next pass.
+    pub fn compact_incremental(&mut self, max_bytes: usize) -> Result<CompactionProgress, StoreError> {
+        let mut compaction = self.compaction.take().unwrap_or_else(|| IncrementalCompaction {
+            pending: self.keys_by_offset_desc(|_| true),
+            pending_until: self.data.len(),
+            new_data: Vec::new(),
+            new_index: HashMap::new(),
+            relocated: HashMap::new(),
+        });
+        let mut bytes_moved = 0;
+
+        loop {
+            if compaction.pending.is_empty() {
+                // Keys written or overwritten since the last step
+                compaction.pending = self.keys_by_offset_desc(|k| !compaction.new_index.contains_key(k));
+                compaction.pending_until = self.data.len();
+                if compaction.pending.is_empty() {
+                    break;
+                }
+            }
+            if bytes_moved >= max_bytes {
+                let keys_remaining = compaction.pending.len();
+                self.compaction = Some(compaction);
+                return Ok(CompactionProgress::InProgress { bytes_moved, keys_remaining });
+            }
+
+            let key = compaction.pending.pop().unwrap();
+            if compaction.new_index.contains_key(&key) {
+                continue;
+            }
+            let Some(&old_offset) = self.index.get(&key) else {
+                continue;
+            };
+            if old_offset >= compaction.pending_until {
+                continue;
+            }
+
+            if let Some(&new_offset) = compaction.relocated.get(&old_offset) {
+                compaction.new_index.insert(key, new_offset);
+                continue;
+            }
+
+            let new_offset = compaction.new_data.len();
+            let (_, bytes_to_copy) = deserialize_value(&self.data[old_offset..])
+                .map_err(|cause| StoreError::InvalidData { cause })?;
+            compaction.new_data.extend_from_slice(&self.data[old_offset..old_offset + bytes_to_copy]);
+            bytes_moved += bytes_to_copy;
+
+            compaction.relocated.insert(old_offset, new_offset);
+            compaction.new_index.insert(key, new_offset);
+        }
+
+        let report = CompactionReport {
+            bytes_reclaimed: self.data.len() - compaction.new_data.len(),
+            entries_retained: compaction.new_index.len(),
+        };
+        self.data = compaction.new_data.into();
+        self.persisted_len = None;
+        self.rewrites += 1;
+        self.index = compaction.new_index;
+
+        Ok(CompactionProgress::Done(report))
+    }
+
+    /// Keys matching `filter`, last-written first so `pop` yields them in write order
+    fn keys_by_offset_desc(&self, filter: impl Fn(&Key) -> bool) -> Vec<Key> {
+        let mut keys: Vec<(&Key, usize)> = self.index.iter()
+            .filter(|(k, _)| filter(k))
+            .map(|(k, &offset)| (k, offset))
+            .collect();
+        keys.sort_by_key(|&(_, offset)| std::cmp::Reverse(offset));
+        keys.into_iter().map(|(k, _)| k.clone()).collect()
+    }
+
+    pub fn compaction_in_progress(&self) -> bool {
+        self.compaction.is_some()
+    }
+
+    /// Delete every String key starting with `prefix` and return how many
+    /// there were. As with `delete`, the space comes back on the next compaction.
+    pub fn clear_prefix(&mut self, prefix: &str) -> usize {
+        let matches = |key: &Key| matches!(key, Key::String(s) if s.starts_with(prefix));
+        let before = self.index.len();
+        self.index.retain(|key, _| !matches(key));
+        if let Some(compaction) = &mut self.compaction {
+            compaction.new_index.retain(|key, _| !matches(key));
+        }
+        before - self.index.len()
     }
 
     pub fn clear(&mut self) {
+        self.compaction = None;
+        self.persisted_len = None;
+        self.rewrites += 1;
         self.index.clear();
-        self.data.clear();
+        self.data = DataBuffer::default();
     }
 
 
-    pub fn fragmentation_ratio(&self) -> f64 {
-        if self.data.is_empty() {
-            return 0.0;
-        }
+    /// Bytes held in memory for values, live or not
+    pub fn data_size(&self) -> usize {
+        self.data.len()
+    }
 
+    /// Bytes held by values that are still reachable from a key. A value
+    /// with a corrupt payload still counts, its header says how big it is.
+    pub fn live_size(&self) -> usize {
         let mut active_size = 0;
         for offset in self.index.values() {
-            if let Ok((_, bytes_read)) = deserialize_value(&self.data[*offset..]) {
+            if let Ok(bytes_read) = check_value_header(&self.data[*offset..]) {
                 active_size += bytes_read;
             }
         }
+        active_size
+    }
+
+    /// Count and size of the live values of each type, in one pass
+    pub fn type_histogram(&self) -> TypeHistogram {
+        let mut histogram = TypeHistogram::default();
+        for offset in self.index.values() {
+            let (tally, size) = match deserialize_value(&self.data[*offset..]) {
+                Ok((BorrowedEntry::Int(_), size)) => (&mut histogram.ints, size),
+                Ok((BorrowedEntry::Text(_), size)) => (&mut histogram.strings, size),
+                Ok((BorrowedEntry::Bool(_), size)) => (&mut histogram.bools, size),
+                Ok((BorrowedEntry::Bytes(_), size)) => (&mut histogram.bytes, size),
+                Err(_) => {
+                    histogram.unreadable += 1;
+                    continue;
+                }
+            };
+            tally.count += 1;
+            tally.bytes += size;
+        }
+        histogram
+    }
+
+    /// Keys added, removed and changed going from this store to `other`,
+    /// comparing values rather than where they are stored. A value that
+    /// can't be read on either side counts as changed.
+    pub fn diff(&self, other: &Store) -> StoreDiff {
+        let mut diff = StoreDiff::default();
+        for key in self.index.keys() {
+            if !other.contains_key(key) {
+                diff.removed.push(key.clone());
+                continue;
+            }
+            let same = match (self.read(key), other.read(key)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            };
+            if !same {
+                diff.changed.push(key.clone());
+            }
+        }
+        diff.added = other.index.keys()
+            .filter(|key| !self.contains_key(key))
+            .cloned()
+            .collect();
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    pub fn fragmentation_ratio(&self) -> f64 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
 
         let total_size = self.data.len();
-        let wasted_size = total_size.saturating_sub(active_size);
+        let wasted_size = total_size.saturating_sub(self.live_size());
 
         wasted_size as f64 / total_size as f64
     }
@@ -133,8 +521,22 @@ impl Store {
         }
     }
 
+    /// Keys whose value fails to deserialize, for diagnosing a corrupt store
+    pub fn errors(&self) -> impl Iterator<Item = (&Key, StoreError)> {
+        self.index.keys().filter_map(|key| self.read(key).err().map(|e| (key, e)))
+    }
+
     pub fn buffer_iter(&self) -> StoreIter {
         StoreIter {
+            scan: self.scan_data(),
+            skip_corrupt: self.config.corruption_policy == CorruptionPolicy::SkipCorrupt,
+        }
+    }
+
+    /// Every value in the data with its offset, including overwritten and
+    /// deleted ones, for rebuilding or checking the index
+    pub fn scan_data(&self) -> DataScan<'_> {
+        DataScan {
             buf: &self.data,
             pos: 0,
         }
@@ -144,102 +546,377 @@ impl Store {
         self.index.keys()
     }
 
+    /// All live entries, Int keys ascending then String and Bytes keys
+    /// lexicographically, so two snapshots of a store can be diffed line by line
+    pub fn scan_all_sorted(&self) -> impl Iterator<Item = (&'_ Key, Result<BorrowedEntry<'_>, StoreError>)> {
+        let mut keys: Vec<&Key> = self.index.keys().collect();
+        keys.sort();
+        keys.into_iter().map(|key| (key, self.get(key)))
+    }
+
+    /// Keys in the order they were last written, matching `buffer_iter`.
+    /// Overwriting a key moves it to the end.
+    pub fn keys_in_order(&self) -> impl Iterator<Item = &Key> {
+        let mut keys: Vec<(&Key, usize)> = self.index.iter()
+            .map(|(k, &offset)| (k, offset))
+            .collect();
+        keys.sort_by_key(|&(_, offset)| offset);
+        keys.into_iter().map(|(k, _)| k)
+    }
+
     pub fn values(&self) -> impl Iterator<Item = Result<BorrowedEntry, StoreError>> {
         self.iter().map(|(_, value)| value)
     }
 
+    /// Like `iter`, but yields copies that outlive the store
+    pub fn iter_owned(&self) -> impl Iterator<Item = (Key, Result<OwnedEntry, StoreError>)> + '_ {
+        self.iter().map(|(key, value)| (key.clone(), value.map(|entry| borrowed_to_owned(&entry))))
+    }
+
+    /// Like `values`, but yields copies that outlive the store
+    pub fn values_owned(&self) -> impl Iterator<Item = Result<OwnedEntry, StoreError>> + '_ {
+        self.values().map(|value| value.map(|entry| borrowed_to_owned(&entry)))
+    }
+
     pub fn with_path<P: AsRef<Path>>(path: P) -> Result<Store, StoreError> {
         let path_buf = path.as_ref().to_path_buf();
+        Self::check_base_path(&path_buf)?;
+        Self::migrate_legacy_paths(&path_buf)?;
 
         if Self::files_exist(&path_buf) {
             Self::load(&path_buf)
         } else {
             Ok(Store {
                 index: HashMap::new(),
-                data: Vec::new(),
+                data: DataBuffer::default(),
                 path: Some(path_buf),
+                last_save_error: None,
+            compaction: None,
+            persisted_len: None,
+            rewrites: 0,
+            config: StoreConfig::default(),
             })
         }
     }
 
+    /// Checksum used for `.meta` from the next save on
+    pub fn with_checksum(mut self, checksum: Checksum) -> Store {
+        self.config.checksum = Some(checksum);
+        self
+    }
+
+    /// Open or create the store at `path` with the given settings. A checksum
+    /// takes effect from the next save, as with `with_checksum`; without one
+    /// a loaded store keeps its own. Fails with `TooLargeForMemory` if the
+    /// `.data` file is over the memory budget.
+    pub fn with_config<P: AsRef<Path>>(path: P, mut config: StoreConfig) -> Result<Store, StoreError> {
+        let path = path.as_ref();
+        Self::check_base_path(path)?;
+        Self::migrate_legacy_paths(path)?;
+        let mut store = if Self::files_exist(path) {
+            Self::load_from(path, false, false, config.memory_budget)?.0
+        } else {
+            Self::with_path(path)?
+        };
+        config.checksum = config.checksum.or(store.config.checksum);
+        store.config = config;
+        Ok(store)
+    }
+
+    pub fn config(&self) -> &StoreConfig {
+        &self.config
+    }
+
+    pub fn checksum(&self) -> Checksum {
+        self.config.checksum.unwrap_or_default()
+    }
+
     pub fn save(&mut self) -> Result<(), StoreError> {
+        let result = self.begin_save().and_then(|(base_path, snapshot)| {
+            Self::commit_save(&snapshot, base_path).map(|()| snapshot)
+        });
+        self.finish_save(result)
+    }
+
+    /// Message of the most recent failed save, cleared by the next successful one
+    pub fn last_save_error(&self) -> Option<&str> {
+        self.last_save_error.as_deref()
+    }
+
+    /// Save and detach from disk, so the error is returned instead of lost in Drop
+    pub fn close(mut self) -> Result<(), StoreError> {
+        let result = self.save();
+        self.path = None;
+        result
+    }
+
+    /// First half of `save`: the snapshot to write and where. The caller
+    /// commits it, possibly without holding on to the store, and hands the
+    /// outcome to `finish_save`.
+    pub(crate) fn begin_save(&mut self) -> Result<(PathBuf, SaveSnapshot), StoreError> {
         let frag_ratio = self.fragmentation_ratio();
-        if frag_ratio > 0.35 {
-            self.compact()?;
+        if frag_ratio > self.config.compaction_threshold {
+            // Compacting stops at a corrupt value without changing anything,
+            // and saving as-is beats not saving at all
+            let _ = self.compact();
         }
-        let base_path = self.path.as_ref()
+        let base_path = self.path.clone()
             .ok_or_else(|| std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "No path set for store"
             ))?;
 
-        let keys_path = Self::keys_path(base_path);
-        let data_path = Self::data_path(base_path);
-        let meta_path = Self::meta_path(base_path);
+        if !Self::data_path(&base_path).exists() {
+            self.persisted_len = None;
+        }
+        let snapshot = self.prepare_save();
+        // Truncating a file we have mapped would pull it out from under us
+        if snapshot.data_start == 0 && self.data.is_mapped() {
+            self.data.to_mut();
+        }
+        Ok((base_path, snapshot))
+    }
+
+    pub(crate) fn finish_save(&mut self, result: Result<SaveSnapshot, StoreError>) -> Result<(), StoreError> {
+        self.last_save_error = result.as_ref().err().map(|e| e.to_string());
+        self.mark_saved(&result?);
+        Ok(())
+    }
 
-        let mut keys_buf = Vec::new();
+    /// Serialize everything `save` would write, without touching disk. Only
+    /// the data not yet in the `.data` file is copied.
+    pub fn prepare_save(&self) -> SaveSnapshot {
+        let mut keys_buf = Self::file_header(KEYS_FILE).to_vec();
         for (key, offset) in &self.index {
+            let start = keys_buf.len();
             let key_bytes = serialize_key(key);
             keys_buf.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
             keys_buf.extend_from_slice(&key_bytes);
             keys_buf.extend_from_slice(&(*offset as u64).to_le_bytes());
+            let record_crc = calculate_crc32(&keys_buf[start..]);
+            keys_buf.extend_from_slice(&record_crc.to_le_bytes());
         }
 
-        let keys_checksum = calculate_crc32(&keys_buf);
-        let data_checksum = calculate_crc32(&self.data);
+        let checksum = self.checksum();
+        let keys_checksum = checksum.compute(&keys_buf[FILE_HEADER_LEN..]);
+        let data_checksum = checksum.compute(&self.data);
 
-        let mut meta_buf = Vec::new();
-        meta_buf.extend_from_slice(&FILE_VERSION.to_le_bytes());
+        let mut meta_buf = Self::file_header(META_FILE).to_vec();
+        meta_buf.extend_from_slice(&(FILE_VERSION | FILE_MINOR_VERSION << 16).to_le_bytes());
+        meta_buf.push(checksum.id());
         meta_buf.extend_from_slice(&keys_checksum.to_le_bytes());
         meta_buf.extend_from_slice(&data_checksum.to_le_bytes());
         meta_buf.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
 
-        fs::write(&meta_path, &meta_buf)?;
-        fs::write(&keys_path, &keys_buf)?;
-        fs::write(&data_path, &self.data)?;
+        // Data is append-only between compactions, only the tail is new
+        let data_start = match self.persisted_len {
+            Some(persisted) if persisted <= self.data.len() => persisted,
+            _ => 0,
+        };
+
+        SaveSnapshot {
+            meta: meta_buf,
+            keys: keys_buf,
+            data: self.data[data_start..].to_vec(),
+            data_start,
+            rewrites: self.rewrites,
+        }
+    }
+
+    /// Record that `snapshot` was committed, so the next `prepare_save` only
+    /// copies what was written after it. Does nothing if the store was
+    /// compacted or cleared since the snapshot was taken.
+    pub fn mark_saved(&mut self, snapshot: &SaveSnapshot) {
+        if snapshot.rewrites == self.rewrites {
+            self.persisted_len = Some(snapshot.data_start + snapshot.data.len());
+        }
+    }
+
+    /// Write a snapshot from `prepare_save` to the files at `base_path`. Needs
+    /// no access to the store, so it can run after its lock is released.
+    ///
+    /// Files are replaced, never rewritten in place: `.keys`, `.meta` and a
+    /// full `.data` rewrite go to a synced temp file that is renamed over the
+    /// old one, `.meta` last. Only an incremental save appends to `.data`
+    /// directly, leaving the bytes the old `.keys` point at untouched.
+    pub fn commit_save<P: AsRef<Path>>(snapshot: &SaveSnapshot, base_path: P) -> Result<(), StoreError> {
+        let base_path = base_path.as_ref();
+        Self::check_base_path(base_path)?;
+        let data_path = Self::data_path(base_path);
+
+        // data_start counts from the end of the file header
+        let file_start = FILE_HEADER_LEN + snapshot.data_start;
+        let existing_len = fs::metadata(&data_path).map(|m| m.len() as usize).ok();
+        match existing_len {
+            // Drop anything past the snapshot's start, an earlier commit may
+            // already have appended part of this tail
+            Some(len) if snapshot.data_start > 0 && len >= file_start => {
+                let file = OpenOptions::new().write(true).open(&data_path)?;
+                file.set_len(file_start as u64)?;
+                drop(file);
+                let mut file = OpenOptions::new().append(true).open(&data_path)?;
+                file.write_all(&snapshot.data)?;
+                file.sync_all()?;
+            }
+            _ if snapshot.data_start > 0 => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "data file is shorter than the snapshot expects"
+                ).into());
+            }
+            _ => Self::replace_file(&data_path, &[&Self::file_header(DATA_FILE), &snapshot.data])?,
+        }
+        Self::replace_file(&Self::keys_path(base_path), &[&snapshot.keys])?;
+        Self::replace_file(&Self::meta_path(base_path), &[&snapshot.meta])?;
+
+        Ok(())
+    }
 
+    /// Write `parts` to a temp file next to `path`, sync it and rename it over
+    /// `path`, so a crash leaves either the old file or the new one
+    fn replace_file(path: &Path, parts: &[&[u8]]) -> Result<(), StoreError> {
+        let temp_path = Self::with_suffix(path, ".tmp");
+        let mut file = File::create(&temp_path)?;
+        for part in parts {
+            file.write_all(part)?;
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, path)?;
         Ok(())
     }
 
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Store, StoreError> {
-        let base_path = path.as_ref();
+        Self::load_from(path.as_ref(), false, false, None).map(|(store, _)| store)
+    }
+
+    /// Load what can be salvaged from damaged files: key records failing
+    /// their CRC or pointing at something other than a value are dropped
+    /// instead of failing the load, and the whole-file
+    /// checksums and entry count aren't enforced. Values are still checked
+    /// when read. Returns the store and how many key records were dropped.
+    pub fn load_lenient<P: AsRef<Path>>(path: P) -> Result<(Store, usize), StoreError> {
+        Self::load_from(path.as_ref(), false, true, None)
+    }
+
+    /// Like `load`, but maps the `.data` file instead of reading it into memory.
+    /// The first write copies the data into memory. The file must not be
+    /// modified by anything else while the store is open. Key offsets aren't
+    /// checked against the data up front; a bad one fails on `get` instead.
+    pub fn load_mmap<P: AsRef<Path>>(path: P) -> Result<Store, StoreError> {
+        Self::load_from(path.as_ref(), true, false, None).map(|(store, _)| store)
+    }
+
+    /// `budget` caps the `.data` size read into memory, mapping ignores it
+    fn load_from(base_path: &Path, mmap: bool, lenient: bool, budget: Option<u64>) -> Result<(Store, usize), StoreError> {
+        Self::check_base_path(base_path)?;
+        Self::migrate_legacy_paths(base_path)?;
         let keys_path = Self::keys_path(base_path);
         let data_path = Self::data_path(base_path);
         let meta_path = Self::meta_path(base_path);
 
-        let meta_buf = fs::read(&meta_path)?;
-        if meta_buf.len() < 20 {
+        let meta_file = fs::read(&meta_path)?;
+        // Older versions have no headers, anything else must start with one
+        let has_header = meta_file.starts_with(MAGIC);
+        if has_header {
+            Self::check_file_header(&meta_file, META_FILE, &meta_path)?;
+        }
+        let meta_buf = if has_header { &meta_file[FILE_HEADER_LEN..] } else { &meta_file[..] };
+        if meta_buf.len() < 4 {
             return Err(StoreError::InvalidData {
                 cause: DeserializationError::BufferTooShort {
-                    expected: 20,
+                    expected: 4,
                     actual: meta_buf.len(),
                 },
             });
         }
 
-        let version = u32::from_le_bytes(meta_buf[0..4].try_into().unwrap());
-        if version != FILE_VERSION {
-            return Err(StoreError::UnsupportedVersion(version));
+        let full_version = u32::from_le_bytes(meta_buf[0..4].try_into().unwrap());
+        let (version, minor) = (full_version & 0xFFFF, full_version >> 16);
+        let expected_len = match (version, has_header) {
+            (FILE_VERSION, true) if minor <= MAX_SUPPORTED_MINOR => 29,
+            (HEADERLESS_FILE_VERSION | UNCHECKED_KEYS_FILE_VERSION, false) => 29,
+            (LEGACY_FILE_VERSION, false) => 20,
+            (_, false) => return Err(StoreError::BadMagic(meta_path)),
+            (_, true) => return Err(StoreError::UnsupportedVersion(full_version)),
+        };
+        if meta_buf.len() < expected_len {
+            return Err(StoreError::InvalidData {
+                cause: DeserializationError::BufferTooShort {
+                    expected: expected_len,
+                    actual: meta_buf.len(),
+                },
+            });
         }
 
-        let stored_keys_checksum = u32::from_le_bytes(meta_buf[4..8].try_into().unwrap());
-        let stored_data_checksum = u32::from_le_bytes(meta_buf[8..12].try_into().unwrap());
-        let entry_count = u64::from_le_bytes(meta_buf[12..20].try_into().unwrap());
-
-        let keys_buf = fs::read(&keys_path)?;
-        let data_buf = fs::read(&data_path)?;
-
-        let actual_keys_checksum = calculate_crc32(&keys_buf);
-        if actual_keys_checksum != stored_keys_checksum {
+        let (checksum, stored_keys_checksum, stored_data_checksum, entry_count) = if version != LEGACY_FILE_VERSION {
+            let checksum = Checksum::from_id(meta_buf[4])
+                .ok_or(StoreError::UnsupportedChecksum(meta_buf[4]))?;
+            (
+                checksum,
+                u64::from_le_bytes(meta_buf[5..13].try_into().unwrap()),
+                u64::from_le_bytes(meta_buf[13..21].try_into().unwrap()),
+                u64::from_le_bytes(meta_buf[21..29].try_into().unwrap()),
+            )
+        } else {
+            (
+                Checksum::Crc32,
+                u32::from_le_bytes(meta_buf[4..8].try_into().unwrap()) as u64,
+                u32::from_le_bytes(meta_buf[8..12].try_into().unwrap()) as u64,
+                u64::from_le_bytes(meta_buf[12..20].try_into().unwrap()),
+            )
+        };
+
+        let record_crc_len = if version >= HEADERLESS_FILE_VERSION { KEY_RECORD_CRC_LEN } else { 0 };
+        let header_len = if has_header { FILE_HEADER_LEN } else { 0 };
+        let keys_file = fs::read(&keys_path)?;
+        if has_header {
+            Self::check_file_header(&keys_file, KEYS_FILE, &keys_path)?;
+        }
+        let keys_buf = &keys_file[header_len..];
+        // Garbage meta can claim more keys than the keys file could hold
+        if !lenient && entry_count > (keys_buf.len() / (MIN_KEY_RECORD_LEN + record_crc_len)) as u64 {
             return Err(StoreError::FileCorrupted);
         }
 
-        let actual_data_checksum = calculate_crc32(&data_buf);
-        if actual_data_checksum != stored_data_checksum {
-            return Err(StoreError::FileCorrupted);
+        let data_buf = if mmap {
+            let file = File::open(&data_path)?;
+            // SAFETY: read-only map, and the store never writes to the file
+            // while mapped (see `load_mmap`)
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            if has_header {
+                Self::check_file_header(&map, DATA_FILE, &data_path)?;
+            }
+            DataBuffer::Mapped { map, start: header_len }
+        } else {
+            if let Some(budget) = budget {
+                let size = fs::metadata(&data_path)?.len();
+                if size > budget {
+                    return Err(StoreError::TooLargeForMemory { size, budget });
+                }
+            }
+            let mut data = fs::read(&data_path)?;
+            if has_header {
+                Self::check_file_header(&data, DATA_FILE, &data_path)?;
+                data.drain(..FILE_HEADER_LEN);
+            }
+            DataBuffer::Owned(data)
+        };
+
+        if !lenient {
+            let actual_keys_checksum = checksum.compute(keys_buf);
+            if actual_keys_checksum != stored_keys_checksum {
+                return Err(StoreError::FileCorrupted);
+            }
+
+            let actual_data_checksum = checksum.compute(&data_buf);
+            if actual_data_checksum != stored_data_checksum {
+                return Err(StoreError::FileCorrupted);
+            }
         }
 
         let mut index = HashMap::new();
+        let mut skipped = 0;
         let mut pos = 0;
 
         while pos < keys_buf.len() {
@@ -247,37 +924,98 @@ impl Store {
                 break;
             }
 
+            let start = pos;
             let key_len = u32::from_le_bytes(keys_buf[pos..pos+4].try_into().unwrap()) as usize;
             pos += 4;
 
-            if pos + key_len + 8 > keys_buf.len() {
+            let end = pos.saturating_add(key_len).saturating_add(8 + record_crc_len);
+            if end > keys_buf.len() {
+                // A bad length leaves nothing to resync on
+                if lenient {
+                    skipped += 1;
+                    break;
+                }
                 return Err(StoreError::InvalidData {
                     cause: DeserializationError::BufferTooShort {
-                        expected: pos + key_len + 8,
+                        expected: end,
                         actual: keys_buf.len(),
                     },
                 });
             }
 
-            let (key, _) = deserialize_key(&keys_buf[pos..pos+key_len])
-                .map_err(|cause| StoreError::InvalidData { cause })?;
+            if record_crc_len > 0 {
+                let crc_start = end - record_crc_len;
+                let stored = u32::from_le_bytes(keys_buf[crc_start..end].try_into().unwrap());
+                if calculate_crc32(&keys_buf[start..crc_start]) != stored {
+                    if lenient {
+                        skipped += 1;
+                        pos = end;
+                        continue;
+                    }
+                    return Err(StoreError::FileCorrupted);
+                }
+            }
+
+            let key = match deserialize_key(&keys_buf[pos..pos+key_len]) {
+                Ok((key, _)) => key,
+                Err(_) if lenient => {
+                    skipped += 1;
+                    pos = end;
+                    continue;
+                }
+                Err(cause) => return Err(StoreError::InvalidData { cause }),
+            };
             pos += key_len;
 
             let offset = u64::from_le_bytes(keys_buf[pos..pos+8].try_into().unwrap()) as usize;
-            pos += 8;
+            pos = end;
+
+            // Catch an index that disagrees with the data now rather than on
+            // `get`. Mapped loads skip it so opening doesn't touch every value.
+            if !mmap {
+                if let Err(cause) = check_value_header(data_buf.get(offset..).unwrap_or_default()) {
+                    if lenient {
+                        skipped += 1;
+                        continue;
+                    }
+                    return Err(StoreError::BadOffset { key, offset, cause });
+                }
+            }
 
             index.insert(key, offset);
         }
 
-        if index.len() != entry_count as usize {
+        if !lenient && index.len() != entry_count as usize {
             return Err(StoreError::FileCorrupted);
         }
 
-        Ok(Store {
+        let store = Store {
             index,
+            // Files without headers get rewritten in full on the next save
+            persisted_len: has_header.then_some(data_buf.len()),
             data: data_buf,
             path: Some(base_path.to_path_buf()),
-        })
+            last_save_error: None,
+            compaction: None,
+            rewrites: 0,
+            config: StoreConfig { checksum: Some(checksum), ..StoreConfig::default() },
+        };
+        Ok((store, skipped))
+    }
+
+    /// Stores used to name their files with `set_extension`, so a base path
+    /// with an extension (`telemetry.db`) wrote `telemetry.keys`. Move such
+    /// files to the current names, unless files already exist there.
+    fn migrate_legacy_paths(base_path: &Path) -> Result<(), StoreError> {
+        let current = Self::file_paths(base_path);
+        let legacy = ["keys", "data", "meta"].map(|ext| base_path.with_extension(ext));
+        if legacy == current || current.iter().any(|p| p.exists()) || !legacy.iter().all(|p| p.exists()) {
+            return Ok(());
+        }
+        for (old, new) in legacy.iter().zip(&current) {
+            fs::rename(old, new)?;
+        }
+        Ok(())
     }
 
     fn files_exist(base_path: &Path) -> bool {
@@ -287,37 +1025,67 @@ impl Store {
         keys_path.exists() && data_path.exists() && meta_path.exists()
     }
 
+    fn file_header(kind: u8) -> [u8; FILE_HEADER_LEN] {
+        [MAGIC[0], MAGIC[1], MAGIC[2], MAGIC[3], kind]
+    }
+
+    fn check_file_header(file: &[u8], kind: u8, path: &Path) -> Result<(), StoreError> {
+        if file.get(..FILE_HEADER_LEN) == Some(&Self::file_header(kind)[..]) {
+            Ok(())
+        } else {
+            Err(StoreError::BadMagic(path.to_path_buf()))
+        }
+    }
+
+    /// The `.keys`, `.data` and `.meta` files of the store at `base_path`. The
+    /// suffixes are appended, so `flight.log` uses `flight.log.keys`.
+    pub fn file_paths<P: AsRef<Path>>(base_path: P) -> [PathBuf; 3] {
+        let base_path = base_path.as_ref();
+        [Self::keys_path(base_path), Self::data_path(base_path), Self::meta_path(base_path)]
+    }
+
+    // A base path has to name a file, or the suffixes would land on its parent
+    fn check_base_path(base_path: &Path) -> Result<(), StoreError> {
+        match base_path.file_name() {
+            Some(_) => Ok(()),
+            None => Err(StoreError::InvalidPath(base_path.to_path_buf())),
+        }
+    }
+
     fn keys_path(base_path: &Path) -> PathBuf {
-        let mut p = base_path.to_path_buf();
-        p.set_extension("keys");
-        p
+        Self::with_suffix(base_path, ".keys")
     }
 
     fn data_path(base_path: &Path) -> PathBuf {
-        let mut p = base_path.to_path_buf();
-        p.set_extension("data");
-        p
+        Self::with_suffix(base_path, ".data")
     }
 
     fn meta_path(base_path: &Path) -> PathBuf {
-        let mut p = base_path.to_path_buf();
-        p.set_extension("meta");
-        p
+        Self::with_suffix(base_path, ".meta")
+    }
+
+    fn with_suffix(base_path: &Path, suffix: &str) -> PathBuf {
+        let mut p = base_path.as_os_str().to_owned();
+        p.push(suffix);
+        PathBuf::from(p)
     }
 }
 
 impl Drop for Store {
     fn drop(&mut self) {
-        if self.path.is_some() {
-            let _ = self.save();
+        if let Some(path) = self.path.clone() {
+            if let Err(e) = self.save() {
+                eprintln!("kiwi-store: failed to save {} on drop: {}", path.display(), e);
+            }
         }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use std::fs;
+    use crate::serialization::serialize_value;
     use crate::types::{OwnedEntry, borrowed_to_owned};
 
     #[test]
@@ -338,6 +1106,54 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn test_typed_getters() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::String("count".into()), Value::Int(7));
+        store.put(Key::String("name".into()), Value::String("kiwi".into()));
+
+        assert_eq!(store.get_int(&Key::String("count".into()))?, 7);
+        assert_eq!(store.get_str(&Key::String("name".into()))?, "kiwi");
+
+        let result = store.get_int(&Key::String("name".into()));
+        assert!(matches!(result, Err(StoreError::TypeMismatch { expected: "Int", found: "Text" })));
+        let result = store.get_str(&Key::String("count".into()));
+        assert!(matches!(result, Err(StoreError::TypeMismatch { expected: "Text", found: "Int" })));
+
+        store.put(Key::String("blob".into()), Value::Bytes(vec![0, 1, 2]));
+        assert_eq!(store.get_bytes(&Key::String("blob".into()))?, &[0, 1, 2]);
+        let result = store.get_str(&Key::String("blob".into()));
+        assert!(matches!(result, Err(StoreError::TypeMismatch { expected: "Text", found: "Bytes" })));
+
+        assert!(matches!(store.get_int(&Key::Int(1)), Err(StoreError::KeyNotFound(_))));
+        assert!(matches!(store.get_str(&Key::Int(1)), Err(StoreError::KeyNotFound(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_batch() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::String("a".into()), Value::Int(1));
+        store.put(Key::Int(2), Value::String("two".into()));
+
+        let keys = vec![
+            Key::Int(2),
+            Key::String("missing".into()),
+            Key::String("a".into()),
+        ];
+        let results = store.get_batch(&keys);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(*results[0].as_ref().unwrap(), BorrowedEntry::Text("two"));
+        assert!(matches!(&results[1], Err(StoreError::KeyNotFound(Key::String(k))) if k == "missing"));
+        assert_eq!(*results[2].as_ref().unwrap(), BorrowedEntry::Int(1));
+
+        assert!(store.get_batch(&[]).is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_delete() -> Result<(), StoreError> {
         let mut store = Store::new();
@@ -376,8 +1192,8 @@ mod tests {
         let size_before_compact = store.data.len();
         assert!(size_before_compact > initial_size);
 
-        let bytes_reclaimed = store.compact()?;
-        assert!(bytes_reclaimed > 0);
+        let report = store.compact()?;
+        assert!(report.bytes_reclaimed > 0);
 
         let size_after_compact = store.data.len();
         assert!(size_after_compact < size_before_compact);
@@ -392,28 +1208,226 @@ mod tests {
     }
 
     #[test]
-    fn test_fragmentation_ratio() -> Result<(), StoreError> {
+    fn test_compaction_report() -> Result<(), StoreError> {
         let mut store = Store::new();
+        for i in 0..10 {
+            store.put(Key::Int(i), Value::String(format!("v{}", i)));
+        }
+        for i in 0..5 {
+            store.put(Key::Int(i), Value::String(format!("updated{}", i)));
+        }
+        store.delete(&Key::Int(8))?;
+        store.delete(&Key::Int(9))?;
 
-        assert_eq!(store.fragmentation_ratio(), 0.0);
+        let size_before = store.data.len();
+        let report = store.compact()?;
 
-        store.put(Key::String("k1".into()), Value::Int(1));
-        store.put(Key::String("k2".into()), Value::Int(2));
+        assert_eq!(report.entries_retained, store.keys().count());
+        assert_eq!(report.entries_retained, 8);
+        assert_eq!(report.bytes_reclaimed, size_before - store.data.len());
+        assert_eq!(store.get(&Key::Int(2))?, BorrowedEntry::Text("updated2"));
+        assert_eq!(store.get(&Key::Int(7))?, BorrowedEntry::Text("v7"));
 
-        let frag1 = store.fragmentation_ratio();
-        assert!(frag1 < 0.01);
+        Ok(())
+    }
 
-        store.put(Key::String("k1".into()), Value::Int(999));
+    #[test]
+    fn test_compact_keeps_shared_offsets() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("shared".into()));
+        let offset = store.index[&Key::Int(1)];
+        store.index.insert(Key::Int(2), offset);
 
-        let frag2 = store.fragmentation_ratio();
-        assert!(frag2 > frag1);
+        let report = store.compact()?;
 
-        store.compact()?;
-        let frag3 = store.fragmentation_ratio();
+        assert_eq!(report.entries_retained, 2);
+        assert_eq!(store.index[&Key::Int(1)], store.index[&Key::Int(2)]);
+        assert_eq!(store.get(&Key::Int(2))?, BorrowedEntry::Text("shared"));
+
+        Ok(())
+    }
+
+    fn fragmented_store() -> Store {
+        let mut store = Store::new();
+        for i in 0..50 {
+            store.put(Key::Int(i), Value::String(format!("value {}", i)));
+        }
+        for i in 0..50 {
+            if i % 3 == 0 {
+                store.put(Key::Int(i), Value::Int(i * 10));
+            }
+        }
+        for i in 40..50 {
+            store.delete(&Key::Int(i)).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_compact_incremental_matches_full() -> Result<(), StoreError> {
+        let mut full = fragmented_store();
+        let mut incremental = fragmented_store();
+
+        let full_report = full.compact()?;
+
+        let mut steps = 0;
+        let report = loop {
+            steps += 1;
+            match incremental.compact_incremental(64)? {
+                CompactionProgress::Done(report) => break report,
+                CompactionProgress::InProgress { bytes_moved, .. } => assert!(bytes_moved >= 64),
+            }
+        };
+
+        assert!(steps > 1);
+        assert!(!incremental.compaction_in_progress());
+        assert_eq!(report, full_report);
+        assert_eq!(incremental.data.len(), full.data.len());
+        for key in full.keys() {
+            assert_eq!(incremental.get(key)?, full.get(key)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_incremental_with_writes() -> Result<(), StoreError> {
+        let mut store = fragmented_store();
+        let size_before = store.data.len();
+
+        assert!(!store.compact_incremental(64)?.is_done());
+        store.put(Key::Int(100), Value::Int(100));
+        store.put(Key::Int(1), Value::String("rewritten".into()));
+        store.delete(&Key::Int(2))?;
+
+        while !store.compact_incremental(64)?.is_done() {}
+
+        assert_eq!(store.keys().count(), 40);
+        assert_eq!(store.get(&Key::Int(100))?, BorrowedEntry::Int(100));
+        assert_eq!(store.get(&Key::Int(1))?, BorrowedEntry::Text("rewritten"));
+        assert!(store.get(&Key::Int(2)).is_err());
+        assert!(store.data.len() < size_before);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fragmentation_ratio() -> Result<(), StoreError> {
+        let mut store = Store::new();
+
+        assert_eq!(store.fragmentation_ratio(), 0.0);
+
+        store.put(Key::String("k1".into()), Value::Int(1));
+        store.put(Key::String("k2".into()), Value::Int(2));
+
+        let frag1 = store.fragmentation_ratio();
+        assert!(frag1 < 0.01);
+
+        store.put(Key::String("k1".into()), Value::Int(999));
+
+        let frag2 = store.fragmentation_ratio();
+        assert!(frag2 > frag1);
+
+        store.compact()?;
+        let frag3 = store.fragmentation_ratio();
         assert!(frag3 < frag2);
 
         Ok(())
     }
+    #[test]
+    fn test_corrupt_value_does_not_fake_fragmentation() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_corrupt_frag";
+        remove_store_files(temp_path);
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::Int(2), Value::String("two".into()));
+        store.put(Key::Int(3), Value::String("three".into()));
+
+        // Flip the last payload byte of key 2, its header stays intact
+        let end = store.index[&Key::Int(3)];
+        store.data.to_mut()[end - 1] ^= 0xff;
+        assert!(store.get(&Key::Int(2)).is_err());
+        assert_eq!(store.fragmentation_ratio(), 0.0);
+
+        // Real fragmentation on top, so save tries to compact and can't
+        store.put(Key::Int(1), Value::String("uno".into()));
+        store.put(Key::Int(3), Value::String("tres".into()));
+        assert!(store.fragmentation_ratio() > store.config.compaction_threshold);
+        store.close()?;
+
+        let reloaded = Store::load(temp_path)?;
+        assert_eq!(reloaded.get_str(&Key::Int(1))?, "uno");
+        assert!(reloaded.get(&Key::Int(2)).is_err());
+        drop(reloaded);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_diff";
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::Int(2), Value::Int(2));
+        store.put(Key::Int(2), Value::Int(22));
+        store.put(Key::String("flag".into()), Value::Bool(true));
+        store.save()?;
+
+        // Same contents at other offsets after compaction
+        let mut copy = Store::load(temp_path)?;
+        copy.compact()?;
+        assert!(store.diff(&copy).is_empty());
+
+        copy.put(Key::Int(1), Value::String("uno".into()));
+        copy.put(Key::Int(3), Value::Int(3));
+        copy.delete(&Key::String("flag".into()))?;
+        // Rewritten with the same value
+        copy.put(Key::Int(2), Value::Int(22));
+        assert_eq!(store.diff(&copy), StoreDiff {
+            added: vec![Key::Int(3)],
+            removed: vec![Key::String("flag".into())],
+            changed: vec![Key::Int(1)],
+        });
+        drop(copy);
+        drop(store);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_histogram() -> Result<(), StoreError> {
+        use crate::types::TypeCount;
+
+        let mut store = Store::new();
+        store.put(Key::String("telem:1".into()), Value::String("{}".into()));
+        store.put(Key::String("telem:2".into()), Value::String("{\"a\":1}".into()));
+        store.put(Key::String("counter".into()), Value::Int(1));
+        store.put(Key::String("counter".into()), Value::Int(2));
+        store.put(Key::String("flag".into()), Value::Bool(true));
+        store.put(Key::String("gone".into()), Value::Int(3));
+        store.delete(&Key::String("gone".into()))?;
+        assert_eq!(store.type_histogram(), TypeHistogram {
+            ints: TypeCount { count: 1, bytes: serialize_value(&Value::Int(2)).len() },
+            strings: TypeCount {
+                count: 2,
+                bytes: serialize_value(&Value::String("{}".into())).len()
+                    + serialize_value(&Value::String("{\"a\":1}".into())).len(),
+            },
+            bools: TypeCount { count: 1, bytes: serialize_value(&Value::Bool(true)).len() },
+            bytes: TypeCount::default(),
+            unreadable: 0,
+        });
+
+        let offset = store.index[&Key::String("flag".into())];
+        store.data.to_mut()[offset + size_of::<crate::serialization::RawHeader>()] ^= 0xff;
+        let histogram = store.type_histogram();
+        assert_eq!(histogram.bools.count, 0);
+        assert_eq!(histogram.unreadable, 1);
+        Ok(())
+    }
+
     #[test]
     fn test_overwrite_behavior() -> Result<(), StoreError> {
         let mut store = Store::new();
@@ -426,6 +1440,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_put_appends_serialized_values() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        let mut expected = Vec::new();
+        for i in 0..100 {
+            let value = if i % 3 == 0 {
+                Value::Int(i)
+            } else {
+                Value::String("x".repeat(i as usize))
+            };
+            // Revisit keys so overwrites are covered too
+            expected.extend_from_slice(&serialize_value(&value));
+            store.put(Key::Int(i % 40), value);
+        }
+
+        assert_eq!(&store.data[..], &expected[..]);
+        assert_eq!(store.get(&Key::Int(39))?, BorrowedEntry::Text(&"x".repeat(79)));
+        Ok(())
+    }
+
     #[test]
     fn test_borrowed_lifetime() -> Result<(), StoreError> {
         let mut store = Store::new();
@@ -476,6 +1510,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_bytes_key_roundtrip() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_bytes_key";
+        remove_store_files(temp_path);
+        let mac: &[u8] = &[0x00, 0x1a, 0x00, 0x00, 0xff, 0x00];
+
+        {
+            let mut store = Store::with_path(temp_path)?;
+            store.put(Key::from(mac), Value::String("drone".into()));
+            store.put(Key::Bytes(mac[..2].to_vec()), Value::Int(2));
+            store.put(Key::String("\u{0}\u{1a}".into()), Value::Int(3));
+            store.save()?;
+        }
+
+        let mut store = Store::load(temp_path)?;
+        assert_eq!(store.get(&Key::from(mac))?, BorrowedEntry::Text("drone"));
+        assert_eq!(store.get(&Key::Bytes(vec![0x00, 0x1a]))?, BorrowedEntry::Int(2));
+        assert_eq!(store.get(&Key::String("\u{0}\u{1a}".into()))?, BorrowedEntry::Int(3));
+        let sorted: Vec<&Key> = store.scan_all_sorted().map(|(k, _)| k).collect();
+        assert_eq!(sorted, vec![&Key::String("\u{0}\u{1a}".into()), &Key::Bytes(vec![0x00, 0x1a]), &Key::from(mac)]);
+
+        store.delete(&Key::from(mac))?;
+        assert!(matches!(store.get(&Key::from(mac)), Err(StoreError::KeyNotFound(_))));
+        store.close()?;
+        let store = Store::load(temp_path)?;
+        assert!(store.get(&Key::from(mac)).is_err());
+        assert_eq!(store.get(&Key::Bytes(vec![0x00, 0x1a]))?, BorrowedEntry::Int(2));
+        drop(store);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
     #[test]
     fn test_auto_load_on_with_path() -> Result<(), StoreError> {
         let temp_path = "/tmp/test_store_autoload";
@@ -515,6 +1582,875 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_close_reports_save_error() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_missing_dir/store";
+
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::String("lost".into()), Value::Int(1));
+
+        assert!(store.save().is_err());
+        assert!(store.last_save_error().is_some());
+
+        let result = store.close();
+        assert!(matches!(result, Err(StoreError::IoError(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_close_saves() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_close";
+
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::String("closed".into()), Value::Int(5));
+        store.close()?;
+
+        let reloaded = Store::load(temp_path)?;
+        assert_eq!(reloaded.get(&Key::String("closed".into()))?, BorrowedEntry::Int(5));
+        drop(reloaded);
+
+        remove_store_files(temp_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_save_matches_full_rewrite() -> Result<(), StoreError> {
+        let appended_path = "/tmp/test_store_append";
+        let rewritten_path = "/tmp/test_store_rewrite";
+
+        let mut appended = Store::with_path(appended_path)?;
+        appended.put(Key::String("a".into()), Value::Int(1));
+        appended.save()?;
+        appended.put(Key::String("b".into()), Value::String("two".into()));
+        appended.save()?;
+        appended.put(Key::String("c".into()), Value::Int(3));
+        appended.save()?;
+
+        let mut rewritten = Store::with_path(rewritten_path)?;
+        rewritten.put(Key::String("a".into()), Value::Int(1));
+        rewritten.put(Key::String("b".into()), Value::String("two".into()));
+        rewritten.put(Key::String("c".into()), Value::Int(3));
+        rewritten.save()?;
+
+        let appended_bytes = fs::read(format!("{}.data", appended_path))?;
+        let rewritten_bytes = fs::read(format!("{}.data", rewritten_path))?;
+        assert_eq!(Checksum::Crc32.compute(&appended_bytes), Checksum::Crc32.compute(&rewritten_bytes));
+        assert_eq!(&appended_bytes[FILE_HEADER_LEN..], &appended.data[..]);
+
+        // A failed append can leave a partial tail behind, the next save drops it
+        OpenOptions::new().append(true).open(format!("{}.data", appended_path))?.write_all(b"partial")?;
+        appended.put(Key::String("d".into()), Value::Int(4));
+        appended.save()?;
+        rewritten.put(Key::String("d".into()), Value::Int(4));
+        rewritten.save()?;
+        assert_eq!(
+            fs::read(format!("{}.data", appended_path))?,
+            fs::read(format!("{}.data", rewritten_path))?,
+        );
+
+        drop(appended);
+        drop(rewritten);
+        let reloaded = Store::load(appended_path)?;
+        assert_eq!(reloaded.get(&Key::String("b".into()))?, BorrowedEntry::Text("two"));
+        drop(reloaded);
+
+        remove_store_files(appended_path);
+        remove_store_files(rewritten_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_rewrite_replaces_files() -> Result<(), StoreError> {
+        use std::io::Read;
+
+        let temp_path = "/tmp/test_store_replace_files";
+        remove_store_files(temp_path);
+        let mut store = Store::with_path(temp_path)?;
+        for i in 0..50 {
+            store.put(Key::Int(i), Value::String(format!("value {}", i)));
+        }
+        store.save()?;
+        let before = fs::read(format!("{}.data", temp_path))?;
+        let mut old_data = File::open(format!("{}.data", temp_path))?;
+
+        // Compaction rewrites .data in full
+        for i in 0..40 {
+            store.delete(&Key::Int(i))?;
+        }
+        store.compact()?;
+        store.save()?;
+
+        // The old file was swapped out, not truncated and rewritten under
+        // whoever still had it open
+        let mut still_open = Vec::new();
+        old_data.read_to_end(&mut still_open)?;
+        assert_eq!(still_open, before);
+        assert!(fs::read(format!("{}.data", temp_path))?.len() < before.len());
+        for ext in ["keys", "data", "meta"] {
+            assert!(!Path::new(&format!("{}.{}.tmp", temp_path, ext)).exists());
+        }
+        drop(store);
+        assert_eq!(Store::load(temp_path)?.keys().count(), 10);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepare_and_commit_match_save() -> Result<(), StoreError> {
+        let saved_path = "/tmp/test_store_saved";
+        let committed_path = "/tmp/test_store_committed";
+
+        let fill = |store: &mut Store, from: i64| for i in from..from + 100 {
+            store.put(Key::Int(i), Value::String(format!("value {}", i)));
+        };
+        let mut saved = Store::with_path(saved_path)?;
+        fill(&mut saved, 0);
+        saved.save()?;
+        fill(&mut saved, 100);
+        saved.save()?;
+
+        // Commit the first snapshot, then the tail on top of it
+        let mut source = Store::new();
+        fill(&mut source, 0);
+        let first = source.prepare_save();
+        Store::commit_save(&first, committed_path)?;
+        source.mark_saved(&first);
+        fill(&mut source, 100);
+        let snapshot = source.prepare_save();
+        Store::commit_save(&snapshot, committed_path)?;
+        source.mark_saved(&snapshot);
+        // Committing the same tail twice must not duplicate it
+        Store::commit_save(&snapshot, committed_path)?;
+
+        drop(saved);
+        assert_eq!(fs::read(format!("{}.data", saved_path))?, fs::read(format!("{}.data", committed_path))?);
+        // .keys follows HashMap order, so compare what loads instead
+        let saved = Store::load(saved_path)?;
+        let committed = Store::load(committed_path)?;
+        assert_eq!(committed.keys().count(), 200);
+        for key in saved.keys() {
+            assert_eq!(committed.get(key)?, saved.get(key)?);
+        }
+        drop(saved);
+        drop(committed);
+
+        remove_store_files(saved_path);
+        remove_store_files(committed_path);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_from_before_compaction_not_marked_saved() -> Result<(), StoreError> {
+        let path = "/tmp/test_store_stale_snapshot";
+        remove_store_files(path);
+
+        let mut store = Store::new();
+        for i in 0..10 {
+            store.put(Key::Int(i), Value::Int(i));
+        }
+        let snapshot = store.prepare_save();
+        Store::commit_save(&snapshot, path)?;
+        // Rewritten and compacted while the snapshot was being written
+        for i in 0..30 {
+            store.put(Key::Int(i), Value::Int(i + 100));
+        }
+        store.compact()?;
+        store.mark_saved(&snapshot);
+        Store::commit_save(&store.prepare_save(), path)?;
+
+        let loaded = Store::load(path)?;
+        assert_eq!(loaded.keys().count(), 30);
+        for i in 0..30 {
+            assert_eq!(loaded.get_int(&Key::Int(i))?, i + 100);
+        }
+        drop(loaded);
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_paths_append_suffix() -> Result<(), StoreError> {
+        let [keys, data, meta] = Store::file_paths("/tmp/telemetry");
+        assert_eq!(keys, PathBuf::from("/tmp/telemetry.keys"));
+        assert_eq!(data, PathBuf::from("/tmp/telemetry.data"));
+        assert_eq!(meta, PathBuf::from("/tmp/telemetry.meta"));
+
+        // Bases differing only in extension no longer share files
+        let log_path = "/tmp/test_store_paths.log";
+        let txt_path = "/tmp/test_store_paths.txt";
+        assert_eq!(Store::file_paths(log_path)[0], PathBuf::from("/tmp/test_store_paths.log.keys"));
+
+        let mut log = Store::with_path(log_path)?;
+        log.put(Key::Int(1), Value::String("log".into()));
+        log.save()?;
+        let mut txt = Store::with_path(txt_path)?;
+        txt.put(Key::Int(1), Value::String("txt".into()));
+        txt.save()?;
+        drop(log);
+        drop(txt);
+
+        assert_eq!(Store::load(log_path)?.get_str(&Key::Int(1))?, "log");
+        assert_eq!(Store::load(txt_path)?.get_str(&Key::Int(1))?, "txt");
+        remove_store_files(log_path);
+        remove_store_files(txt_path);
+
+        assert!(matches!(Store::with_path(""), Err(StoreError::InvalidPath(_))));
+        assert!(matches!(Store::with_path("/tmp/.."), Err(StoreError::InvalidPath(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_store_with_legacy_file_names() -> Result<(), StoreError> {
+        // What `/tmp/test_store_legacy.db` was saved as before suffixes were appended
+        let legacy_files = "/tmp/test_store_legacy";
+        let base_path = "/tmp/test_store_legacy.db";
+        remove_store_files(legacy_files);
+        remove_store_files(base_path);
+        let mut store = Store::with_path(legacy_files)?;
+        store.put(Key::Int(1), Value::String("kept".into()));
+        store.close()?;
+
+        let store = Store::with_path(base_path)?;
+        assert_eq!(store.get_str(&Key::Int(1))?, "kept");
+        store.close()?;
+        assert!(Path::new("/tmp/test_store_legacy.db.data").exists());
+        assert!(!Path::new("/tmp/test_store_legacy.data").exists());
+
+        remove_store_files(base_path);
+        Ok(())
+    }
+
+    const ALL_CHECKSUMS: [Checksum; 3] = [Checksum::Crc32, Checksum::Crc32c, Checksum::Xxhash64];
+
+    pub(crate) fn remove_store_files(path: &str) {
+        fs::remove_file(format!("{}.keys", path)).ok();
+        fs::remove_file(format!("{}.data", path)).ok();
+        fs::remove_file(format!("{}.meta", path)).ok();
+    }
+
+    #[test]
+    fn test_checksum_roundtrip() -> Result<(), StoreError> {
+        for checksum in ALL_CHECKSUMS {
+            let temp_path = format!("/tmp/test_store_checksum_{:?}", checksum);
+
+            let mut store = Store::with_path(&temp_path)?.with_checksum(checksum);
+            store.put(Key::String("alg".into()), Value::String(format!("{:?}", checksum)));
+            store.close()?;
+
+            let reloaded = Store::load(&temp_path)?;
+            assert_eq!(reloaded.checksum(), checksum);
+            assert_eq!(reloaded.get(&Key::String("alg".into()))?, BorrowedEntry::Text(&format!("{:?}", checksum)));
+            drop(reloaded);
+
+            remove_store_files(&temp_path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksum_detects_flipped_byte() -> Result<(), StoreError> {
+        for checksum in ALL_CHECKSUMS {
+            let temp_path = format!("/tmp/test_store_flip_{:?}", checksum);
+
+            let mut store = Store::with_path(&temp_path)?.with_checksum(checksum);
+            store.put(Key::Int(1), Value::String("payload".into()));
+            store.close()?;
+
+            let data_path = format!("{}.data", temp_path);
+            let mut bytes = fs::read(&data_path)?;
+            let last = bytes.len() - 1;
+            bytes[last] ^= 0x01;
+            fs::write(&data_path, &bytes)?;
+
+            assert!(matches!(Store::load(&temp_path), Err(StoreError::FileCorrupted)));
+
+            remove_store_files(&temp_path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_legacy_meta() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_legacy_meta";
+
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(7), Value::Int(70));
+        store.close()?;
+
+        // Key records without their own CRC, as version 1 wrote them
+        let key_bytes = serialize_key(&Key::Int(7));
+        let mut keys = Vec::new();
+        keys.extend_from_slice(&(key_bytes.len() as u32).to_le_bytes());
+        keys.extend_from_slice(&key_bytes);
+        keys.extend_from_slice(&0u64.to_le_bytes());
+        fs::write(format!("{}.keys", temp_path), &keys)?;
+        let data = fs::read(format!("{}.data", temp_path))?[FILE_HEADER_LEN..].to_vec();
+        fs::write(format!("{}.data", temp_path), &data)?;
+        let mut meta = Vec::new();
+        meta.extend_from_slice(&LEGACY_FILE_VERSION.to_le_bytes());
+        meta.extend_from_slice(&(Checksum::Crc32.compute(&keys) as u32).to_le_bytes());
+        meta.extend_from_slice(&(Checksum::Crc32.compute(&data) as u32).to_le_bytes());
+        meta.extend_from_slice(&1u64.to_le_bytes());
+        fs::write(format!("{}.meta", temp_path), &meta)?;
+
+        let reloaded = Store::load(temp_path)?;
+        assert_eq!(reloaded.checksum(), Checksum::Crc32);
+        assert_eq!(reloaded.get(&Key::Int(7))?, BorrowedEntry::Int(70));
+        drop(reloaded);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rejects_impossible_entry_count() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_entry_count";
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::Int(10));
+        store.close()?;
+
+        let meta_path = format!("{}.meta", temp_path);
+        let mut meta = fs::read(&meta_path)?;
+        meta[FILE_HEADER_LEN + 21..FILE_HEADER_LEN + 29].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&meta_path, &meta)?;
+
+        assert!(matches!(Store::load(temp_path), Err(StoreError::FileCorrupted)));
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rejects_bad_magic() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_magic";
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::Int(10));
+        store.close()?;
+        let data_path = format!("{}.data", temp_path);
+        let original = fs::read(&data_path)?;
+
+        // Another store's keys file in place of the data file
+        fs::copy(format!("{}.keys", temp_path), &data_path)?;
+        assert!(matches!(Store::load(temp_path), Err(StoreError::BadMagic(path)) if path.ends_with("test_store_magic.data")));
+
+        // A flipped value byte behind a good header is a checksum failure instead
+        let mut flipped = original.clone();
+        *flipped.last_mut().unwrap() ^= 0xff;
+        fs::write(&data_path, &flipped)?;
+        assert!(matches!(Store::load(temp_path), Err(StoreError::FileCorrupted)));
+
+        fs::write(format!("{}.meta", temp_path), b"definitely not a store meta file")?;
+        assert!(matches!(Store::load(temp_path), Err(StoreError::BadMagic(_))));
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_headerless_store_and_upgrade() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_headerless";
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.close()?;
+
+        // Strip the headers and write version 3 into the meta
+        for ext in ["keys", "data", "meta"] {
+            let path = format!("{}.{}", temp_path, ext);
+            let file = fs::read(&path)?;
+            fs::write(&path, &file[FILE_HEADER_LEN..])?;
+        }
+        let meta_path = format!("{}.meta", temp_path);
+        let mut meta = fs::read(&meta_path)?;
+        meta[0..4].copy_from_slice(&HEADERLESS_FILE_VERSION.to_le_bytes());
+        fs::write(&meta_path, &meta)?;
+
+        let mut store = Store::load(temp_path)?;
+        assert_eq!(store.get_str(&Key::Int(1))?, "one");
+        store.put(Key::Int(2), Value::String("two".into()));
+        store.close()?;
+
+        assert!(fs::read(format!("{}.data", temp_path))?.starts_with(b"KIWID"));
+        let reloaded = Store::load(temp_path)?;
+        assert_eq!(reloaded.get_str(&Key::Int(1))?, "one");
+        assert_eq!(reloaded.get_str(&Key::Int(2))?, "two");
+        drop(reloaded);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::Int(2), Value::String("two".into()));
+        let data_size = store.data_size();
+
+        let result = store.transaction(|tx| {
+            tx.put(Key::Int(3), Value::String("three".into()));
+            tx.rename_key(&Key::Int(1), &Key::Int(10))?;
+            tx.delete(&Key::Int(2))?;
+            // Already renamed away
+            tx.delete(&Key::Int(1))
+        });
+        assert!(matches!(result, Err(StoreError::KeyNotFound(Key::Int(1)))));
+        assert_eq!(store.data_size(), data_size);
+        assert_eq!(store.keys().count(), 2);
+        assert_eq!(store.get_str(&Key::Int(1))?, "one");
+        assert_eq!(store.get_str(&Key::Int(2))?, "two");
+
+        let staged = store.transaction(|tx| {
+            tx.put(Key::Int(3), Value::String("three".into()));
+            tx.rename_key(&Key::Int(3), &Key::Int(30))?;
+            tx.delete(&Key::Int(2))?;
+            Ok(tx.contains_key(&Key::Int(30)))
+        })?;
+        assert!(staged);
+        assert_eq!(store.get_str(&Key::Int(30))?, "three");
+        assert!(!store.contains_key(&Key::Int(2)));
+        assert!(!store.contains_key(&Key::Int(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_newer_minor_version() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_newer_minor";
+        remove_store_files(temp_path);
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.close()?;
+
+        // A later minor with a field this version doesn't know about
+        let meta_path = format!("{}.meta", temp_path);
+        let mut meta = fs::read(&meta_path)?;
+        let version = FILE_VERSION | (FILE_MINOR_VERSION + 1) << 16;
+        meta[FILE_HEADER_LEN..FILE_HEADER_LEN + 4].copy_from_slice(&version.to_le_bytes());
+        meta.extend_from_slice(&[0xAB; 12]);
+        fs::write(&meta_path, &meta)?;
+
+        let store = Store::load(temp_path)?;
+        assert_eq!(store.get_str(&Key::Int(1))?, "one");
+        drop(store);
+
+        // Up to the highest supported minor, and no further
+        let version = FILE_VERSION | MAX_SUPPORTED_MINOR << 16;
+        meta[FILE_HEADER_LEN..FILE_HEADER_LEN + 4].copy_from_slice(&version.to_le_bytes());
+        fs::write(&meta_path, &meta)?;
+        assert_eq!(Store::load(temp_path)?.get_str(&Key::Int(1))?, "one");
+        let version = FILE_VERSION | (MAX_SUPPORTED_MINOR + 1) << 16;
+        meta[FILE_HEADER_LEN..FILE_HEADER_LEN + 4].copy_from_slice(&version.to_le_bytes());
+        fs::write(&meta_path, &meta)?;
+        assert!(matches!(Store::load(temp_path), Err(StoreError::UnsupportedVersion(v)) if v == version));
+
+        // A different major is still refused
+        let version = (FILE_VERSION + 1) | FILE_MINOR_VERSION << 16;
+        meta[FILE_HEADER_LEN..FILE_HEADER_LEN + 4].copy_from_slice(&version.to_le_bytes());
+        fs::write(&meta_path, &meta)?;
+        assert!(matches!(Store::load(temp_path), Err(StoreError::UnsupportedVersion(v)) if v == version));
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_rejects_offset_inside_value() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_bad_offset";
+        remove_store_files(temp_path);
+        let mut store = Store::with_path(temp_path)?;
+        store.config.compaction_threshold = 1.0;
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::Int(2), Value::String("two".into()));
+        // Checksums are computed over this index, so only the offset check can object
+        store.index.insert(Key::Int(2), 3);
+        store.close()?;
+
+        assert!(matches!(
+            Store::load(temp_path),
+            Err(StoreError::BadOffset { key: Key::Int(2), offset: 3, .. })
+        ));
+        let (salvaged, skipped) = Store::load_lenient(temp_path)?;
+        assert_eq!(skipped, 1);
+        assert_eq!(salvaged.get_str(&Key::Int(1))?, "one");
+        assert!(!salvaged.contains_key(&Key::Int(2)));
+        drop(salvaged);
+
+        let mapped = Store::load_mmap(temp_path)?;
+        assert_eq!(mapped.get_str(&Key::Int(1))?, "one");
+        assert!(mapped.get(&Key::Int(2)).is_err());
+        drop(mapped);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_lenient_skips_corrupt_key_record() -> Result<(), StoreError> {
+        let temp_path = "/tmp/test_store_lenient";
+        let mut store = Store::with_path(temp_path)?;
+        store.put(Key::String("flight:flight_001".into()), Value::Int(1));
+        store.put(Key::String("flight:flight_002".into()), Value::Int(2));
+        store.put(Key::String("flight:flight_003".into()), Value::Int(3));
+        store.close()?;
+
+        let keys_path = format!("{}.keys", temp_path);
+        let mut keys = fs::read(&keys_path)?;
+        let at = keys.windows(17).position(|w| w == b"flight:flight_002").unwrap();
+        keys[at + 15] ^= 0xff;
+        fs::write(&keys_path, &keys)?;
+
+        assert!(matches!(Store::load(temp_path), Err(StoreError::FileCorrupted)));
+        let (salvaged, skipped) = Store::load_lenient(temp_path)?;
+        assert_eq!(skipped, 1);
+        assert_eq!(salvaged.keys().count(), 2);
+        assert_eq!(salvaged.get_int(&Key::String("flight:flight_001".into()))?, 1);
+        assert_eq!(salvaged.get_int(&Key::String("flight:flight_003".into()))?, 3);
+        drop(salvaged);
+
+        remove_store_files(temp_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_yields_only_corrupt_keys() {
+        let mut store = Store::new();
+        store.put(Key::String("good".into()), Value::String("intact".into()));
+        store.put(Key::String("bad".into()), Value::String("flipped".into()));
+
+        let offset = store.index[&Key::String("bad".into())];
+        let last = store.data.len() - 1;
+        assert!(last > offset);
+        store.data.to_mut()[last] ^= 0xFF;
+
+        let errors: Vec<_> = store.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, &Key::String("bad".into()));
+        assert!(matches!(errors[0].1, StoreError::DataCorruption { .. }));
+    }
+
+    #[test]
+    fn test_skip_corrupt_policy_hides_corrupt_values() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::Int(2), Value::String("two".into()));
+        store.put(Key::Int(3), Value::String("three".into()));
+        let end = store.index[&Key::Int(3)];
+        store.data.to_mut()[end - 1] ^= 0xff;
+
+        assert!(matches!(store.get(&Key::Int(2)), Err(StoreError::DataCorruption { .. })));
+        assert_eq!(store.iter().count(), 3);
+
+        store.set_corruption_policy(CorruptionPolicy::SkipCorrupt);
+        assert!(matches!(store.get(&Key::Int(2)), Err(StoreError::KeyNotFound(_))));
+        assert!(matches!(store.get_raw(&Key::Int(2)), Err(StoreError::KeyNotFound(_))));
+        assert_eq!(store.get_str(&Key::Int(1))?, "one");
+        assert_eq!(store.get_str(&Key::Int(3))?, "three");
+
+        let mut keys: Vec<&Key> = store.iter().map(|(key, value)| value.map(|_| key)).collect::<Result<_, _>>()?;
+        keys.sort();
+        assert_eq!(keys, vec![&Key::Int(1), &Key::Int(3)]);
+        assert_eq!(store.buffer_iter().collect::<Result<Vec<_>, _>>()?,
+                   vec![BorrowedEntry::Text("one"), BorrowedEntry::Text("three")]);
+        // Still visible to diagnostics
+        assert_eq!(store.errors().count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_raw() -> Result<(), StoreError> {
+        use crate::serialization::calculate_crc32;
+
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("payload".into()));
+        store.put(Key::Int(2), Value::Int(-7));
+
+        let raw = store.get_raw(&Key::Int(1))?;
+        assert_eq!(&raw[..8], &7u64.to_le_bytes());
+        assert_eq!(&raw[8..], b"payload");
+        // Header: length (8 bytes), then the checksum
+        let pos = store.index[&Key::Int(1)];
+        let checksum = u32::from_le_bytes(store.data[pos + 8..pos + 12].try_into().unwrap());
+        assert_eq!(calculate_crc32(raw), checksum);
+        assert_eq!(store.get_raw(&Key::Int(2))?, &(-7i64).to_le_bytes());
+
+        let last = store.data.len() - 1;
+        store.data.to_mut()[last] ^= 0xff;
+        assert!(matches!(store.get_raw(&Key::Int(2)), Err(StoreError::DataCorruption { .. })));
+        assert!(matches!(store.get_raw(&Key::Int(3)), Err(StoreError::KeyNotFound(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_values_containing() {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String(r#"{"lat":49.8728,"lon":8.6512}"#.into()));
+        store.put(Key::Int(2), Value::String(r#"{"lat":51.2000,"lon":8.6512}"#.into()));
+        store.put(Key::Int(3), Value::String("49.8728 in plain text".into()));
+        store.put(Key::Int(4), Value::Int(498728));
+
+        let mut found = store.find_values_containing(b"49.8728");
+        found.sort();
+        assert_eq!(found, vec![&Key::Int(1), &Key::Int(3)]);
+        assert_eq!(store.find_values_containing(b"8.6512").len(), 2);
+        assert_eq!(store.find_values_containing(&498728i64.to_le_bytes()), vec![&Key::Int(4)]);
+        assert!(store.find_values_containing(b"52.0").is_empty());
+
+        // A checksum mismatch elsewhere in the payload doesn't hide a match
+        let offset = store.index[&Key::Int(3)];
+        let header_size = size_of::<crate::serialization::RawHeader>();
+        store.data.to_mut()[offset + header_size + 8 + "49.8728 in plain ".len()] ^= 0xff;
+        assert!(store.get_raw(&Key::Int(3)).is_err());
+        let mut found = store.find_values_containing(b"49.8728");
+        found.sort();
+        assert_eq!(found, vec![&Key::Int(1), &Key::Int(3)]);
+    }
+
+    #[test]
+    fn test_owned_iterators_outlive_store() {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("one".into()));
+        store.put(Key::String("two".into()), Value::Int(2));
+
+        let mut values: Vec<OwnedEntry> = store.values_owned().map(Result::unwrap).collect();
+        let mut entries: Vec<(Key, OwnedEntry)> = store.iter_owned()
+            .map(|(key, value)| (key, value.unwrap()))
+            .collect();
+        drop(store);
+
+        values.sort_by_key(|value| matches!(value, OwnedEntry::Text(_)));
+        assert_eq!(values, vec![OwnedEntry::Int(2), OwnedEntry::Text("one".into())]);
+        let entries = std::thread::spawn(move || {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        }).join().unwrap();
+        assert_eq!(entries[0], (Key::Int(1), OwnedEntry::Text("one".into())));
+        assert_eq!(entries[1], (Key::String("two".into()), OwnedEntry::Int(2)));
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut store = Store::new();
+        store.put(Key::Int(1), Value::String("fine".into()));
+        store.put(Key::Int(2), Value::String("broken".into()));
+
+        let last = store.data.len() - 1;
+        store.data.to_mut()[last] ^= 0xFF;
+
+        assert_eq!(store.peek(&Key::Int(1)), Some(OwnedEntry::Text("fine".into())));
+        assert_eq!(store.peek(&Key::Int(2)), None);
+        assert_eq!(store.peek(&Key::Int(3)), None);
+    }
+
+    #[test]
+    fn test_keys_in_order() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        for name in ["a", "b", "c", "d"] {
+            store.put(Key::String(name.into()), Value::String(name.into()));
+        }
+        store.put(Key::String("b".into()), Value::String("b2".into()));
+        store.delete(&Key::String("c".into()))?;
+
+        let expected = vec![Key::String("a".into()), Key::String("d".into()), Key::String("b".into())];
+        assert_eq!(store.keys_in_order().cloned().collect::<Vec<_>>(), expected);
+
+        store.compact()?;
+        assert_eq!(store.keys_in_order().cloned().collect::<Vec<_>>(), expected);
+
+        store.put(Key::String("a".into()), Value::String("a2".into()));
+        while !store.compact_incremental(1)?.is_done() {
+            store.put(Key::String("e".into()), Value::String("e".into()));
+        }
+        let keys: Vec<Key> = store.keys_in_order().cloned().collect();
+        assert_eq!(keys, vec![
+            Key::String("d".into()),
+            Key::String("b".into()),
+            Key::String("a".into()),
+            Key::String("e".into()),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_mmap_matches_load() -> Result<(), StoreError> {
+        let path = "/tmp/test_store_mmap";
+        remove_store_files(path);
+        {
+            let mut store = Store::with_path(path)?;
+            for i in 0..100 {
+                store.put(Key::Int(i), Value::String(format!("value {}", i)));
+            }
+            store.put(Key::String("count".into()), Value::Int(100));
+            store.save()?;
+        }
+
+        let loaded = Store::load(path)?;
+        let mut mapped = Store::load_mmap(path)?;
+        assert!(mapped.data.is_mapped());
+        for key in loaded.keys() {
+            assert_eq!(mapped.get(key)?, loaded.get(key)?);
+        }
+        drop(loaded);
+
+        // First write switches to an owned copy
+        mapped.put(Key::Int(100), Value::String("value 100".into()));
+        assert!(!mapped.data.is_mapped());
+        assert_eq!(mapped.get_str(&Key::Int(5))?, "value 5");
+        mapped.close()?;
+
+        let reloaded = Store::load(path)?;
+        assert_eq!(reloaded.get_str(&Key::Int(100))?, "value 100");
+        drop(reloaded);
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_all_sorted() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::String("beta".into()), Value::Int(1));
+        store.put(Key::Int(10), Value::Int(2));
+        store.put(Key::String("alpha".into()), Value::Int(3));
+        store.put(Key::Int(-5), Value::String("neg".into()));
+        store.put(Key::Int(2), Value::Int(4));
+
+        let scanned: Vec<(Key, BorrowedEntry)> = store.scan_all_sorted()
+            .map(|(key, value)| value.map(|v| (key.clone(), v)))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(scanned, vec![
+            (Key::Int(-5), BorrowedEntry::Text("neg")),
+            (Key::Int(2), BorrowedEntry::Int(4)),
+            (Key::Int(10), BorrowedEntry::Int(2)),
+            (Key::String("alpha".into()), BorrowedEntry::Int(3)),
+            (Key::String("beta".into()), BorrowedEntry::Int(1)),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_key() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        store.put(Key::String("old".into()), Value::String("payload".into()));
+        store.put(Key::String("taken".into()), Value::Int(1));
+        let size = store.data_size();
+
+        store.rename_key(&Key::String("old".into()), &Key::String("new".into()))?;
+        assert_eq!(store.get_str(&Key::String("new".into()))?, "payload");
+        assert!(!store.contains_key(&Key::String("old".into())));
+        assert_eq!(store.data_size(), size);
+
+        store.rename_key(&Key::String("new".into()), &Key::String("taken".into()))?;
+        assert_eq!(store.get_str(&Key::String("taken".into()))?, "payload");
+        assert_eq!(store.keys().count(), 1);
+
+        let missing = store.rename_key(&Key::String("old".into()), &Key::String("other".into()));
+        assert!(matches!(missing, Err(StoreError::KeyNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_config() -> Result<(), StoreError> {
+        let lazy_path = "/tmp/test_store_config_lazy";
+        let eager_path = "/tmp/test_store_config_eager";
+        remove_store_files(lazy_path);
+        remove_store_files(eager_path);
+
+        let lazy = StoreConfig::builder()
+            .compaction_threshold(0.9)
+            .checksum(Checksum::Xxhash64)
+            .build();
+        let mut lazy_store = Store::with_config(lazy_path, lazy)?;
+        let mut eager_store = Store::with_config(eager_path, StoreConfig::default())?;
+        assert_eq!(lazy_store.config(), &lazy);
+
+        // Half the data is overwritten: above the default threshold, below 0.9
+        for store in [&mut lazy_store, &mut eager_store] {
+            for i in 0..10 {
+                store.put(Key::Int(i), Value::String("first".into()));
+                store.put(Key::Int(i), Value::String("again".into()));
+            }
+        }
+        let size = lazy_store.data_size();
+        lazy_store.save()?;
+        eager_store.save()?;
+        assert_eq!(lazy_store.data_size(), size);
+        assert!(eager_store.data_size() < size);
+
+        drop(lazy_store);
+        drop(eager_store);
+        assert_eq!(Store::load(lazy_path)?.checksum(), Checksum::Xxhash64);
+
+        // Reopening without a checksum keeps the store's own
+        let reopened = Store::with_config(lazy_path, StoreConfig::builder().compaction_threshold(0.5).build())?;
+        assert_eq!(reopened.checksum(), Checksum::Xxhash64);
+        drop(reopened);
+        let reopened = Store::with_config(lazy_path, StoreConfig::builder().checksum(Checksum::Crc32c).build())?;
+        assert_eq!(reopened.checksum(), Checksum::Crc32c);
+        drop(reopened);
+        remove_store_files(lazy_path);
+        remove_store_files(eager_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_budget_refuses_large_data_file() -> Result<(), StoreError> {
+        let path = "/tmp/test_store_memory_budget";
+        remove_store_files(path);
+        {
+            let mut store = Store::with_path(path)?;
+            for i in 0..100 {
+                store.put(Key::Int(i), Value::String("x".repeat(100)));
+            }
+            store.close()?;
+        }
+        let size = fs::metadata(Store::data_path(Path::new(path)))?.len();
+
+        let tight = StoreConfig::default().with_memory_budget(1024);
+        match Store::with_config(path, tight) {
+            Err(StoreError::TooLargeForMemory { size: reported, budget: 1024 }) => assert_eq!(reported, size),
+            _ => panic!("expected TooLargeForMemory"),
+        }
+        // Mapping doesn't read the file in, and a big enough budget loads
+        assert_eq!(Store::load_mmap(path)?.keys().count(), 100);
+        let roomy = StoreConfig::default().with_memory_budget(size);
+        assert_eq!(Store::with_config(path, roomy)?.keys().count(), 100);
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_clear_prefix() -> Result<(), StoreError> {
+        let mut store = Store::new();
+        for i in 0..5 {
+            store.put(Key::String(format!("telem:drone_1:{}", i)), Value::Int(i));
+            store.put(Key::String(format!("telem:drone_2:{}", i)), Value::Int(i));
+        }
+        store.put(Key::String("telem:drone_1".into()), Value::Int(-1));
+        store.put(Key::Bytes(b"telem:drone_1:9".to_vec()), Value::Int(9));
+        store.put(Key::Int(1), Value::Int(1));
+
+        assert_eq!(store.clear_prefix("telem:drone_1:"), 5);
+        assert!(store.get(&Key::String("telem:drone_1:0".into())).is_err());
+        assert_eq!(store.get_int(&Key::String("telem:drone_2:4".into()))?, 4);
+        assert_eq!(store.get_int(&Key::String("telem:drone_1".into()))?, -1);
+        assert_eq!(store.get_int(&Key::Bytes(b"telem:drone_1:9".to_vec()))?, 9);
+        assert_eq!(store.get_int(&Key::Int(1))?, 1);
+        assert_eq!(store.clear_prefix("telem:drone_1:"), 0);
+
+        // Also gone from a compaction that is underway
+        store.compact_incremental(1)?;
+        assert_eq!(store.clear_prefix("telem:"), 6);
+        while !store.compact_incremental(1024)?.is_done() {}
+        assert_eq!(store.keys().count(), 2);
+        Ok(())
+    }
+
     #[test]
     fn test_clear() -> Result<(), StoreError> {
         let mut store = Store::new();
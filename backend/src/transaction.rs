@@ -0,0 +1,59 @@
+use crate::types::{Key, Value};
+use crate::error::StoreError;
+use crate::Store;
+use std::collections::HashMap;
+
+pub(crate) enum Op {
+    Put(Key, Value),
+    Delete(Key),
+    Rename(Key, Key),
+}
+
+/// Writes staged by `Store::transaction`, applied together once its closure
+/// returns Ok. Deletes and renames are checked against the store as it would
+/// be after the writes before them, so applying them can't fail halfway.
+pub struct Transaction<'a> {
+    store: &'a Store,
+    pub(crate) ops: Vec<Op>,
+    // Keys created (true) or removed (false) by the staged writes
+    staged: HashMap<Key, bool>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn new(store: &'a Store) -> Self {
+        Transaction { store, ops: Vec::new(), staged: HashMap::new() }
+    }
+
+    /// Whether `key` exists, counting the writes staged so far
+    pub fn contains_key(&self, key: &Key) -> bool {
+        match self.staged.get(key) {
+            Some(&exists) => exists,
+            None => self.store.contains_key(key),
+        }
+    }
+
+    pub fn put(&mut self, key: Key, value: Value) {
+        self.staged.insert(key.clone(), true);
+        self.ops.push(Op::Put(key, value));
+    }
+
+    pub fn delete(&mut self, key: &Key) -> Result<(), StoreError> {
+        if !self.contains_key(key) {
+            return Err(StoreError::KeyNotFound(key.clone()));
+        }
+        self.staged.insert(key.clone(), false);
+        self.ops.push(Op::Delete(key.clone()));
+        Ok(())
+    }
+
+    /// See `Store::rename_key`
+    pub fn rename_key(&mut self, old: &Key, new: &Key) -> Result<(), StoreError> {
+        if !self.contains_key(old) {
+            return Err(StoreError::KeyNotFound(old.clone()));
+        }
+        self.staged.insert(old.clone(), false);
+        self.staged.insert(new.clone(), true);
+        self.ops.push(Op::Rename(old.clone(), new.clone()));
+        Ok(())
+    }
+}
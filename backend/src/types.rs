@@ -2,30 +2,194 @@
 pub enum Key {
     String(String),
     Int(i64),
+    Bytes(Vec<u8>),
+}
+
+impl Key {
+    fn kind_order(&self) -> u8 {
+        match self {
+            Key::Int(_) => 0,
+            Key::String(_) => 1,
+            Key::Bytes(_) => 2,
+        }
+    }
+}
+
+/// Int keys sort before String keys before Bytes keys, each in their natural
+/// order (bytes lexicographically)
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> std::cmp::Ordering {
+        match (self, other) {
+            (Key::Int(a), Key::Int(b)) => a.cmp(b),
+            (Key::String(a), Key::String(b)) => a.cmp(b),
+            (Key::Bytes(a), Key::Bytes(b)) => a.cmp(b),
+            _ => self.kind_order().cmp(&other.kind_order()),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub enum Value {
     String(String),
     Int(i64),
+    Bool(bool),
+    Bytes(Vec<u8>),
+}
+
+impl From<i64> for Key {
+    fn from(i: i64) -> Key {
+        Key::Int(i)
+    }
+}
+
+impl From<&str> for Key {
+    fn from(s: &str) -> Key {
+        Key::String(s.to_string())
+    }
+}
+
+impl From<String> for Key {
+    fn from(s: String) -> Key {
+        Key::String(s)
+    }
+}
+
+impl From<&[u8]> for Key {
+    fn from(b: &[u8]) -> Key {
+        Key::Bytes(b.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Key {
+    fn from(b: Vec<u8>) -> Key {
+        Key::Bytes(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Int(i)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Value {
+        Value::Bytes(b)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum BorrowedEntry<'a> {
     Int(i64),
     Text(&'a str),
+    Bool(bool),
+    Bytes(&'a [u8]),
+}
+
+impl BorrowedEntry<'_> {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            BorrowedEntry::Int(_) => "Int",
+            BorrowedEntry::Text(_) => "Text",
+            BorrowedEntry::Bool(_) => "Bool",
+            BorrowedEntry::Bytes(_) => "Bytes",
+        }
+    }
+}
+
+/// Outcome of a compaction pass
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CompactionReport {
+    pub bytes_reclaimed: usize,
+    pub entries_retained: usize,
+}
+
+/// Live values of one type and the bytes they occupy, headers included
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct TypeCount {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Live values tallied by type, from `Store::type_histogram`
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct TypeHistogram {
+    pub ints: TypeCount,
+    pub strings: TypeCount,
+    pub bools: TypeCount,
+    pub bytes: TypeCount,
+    /// Values that failed to deserialize
+    pub unreadable: usize,
+}
+
+/// Live keys that differ between two stores, from `Store::diff`, each sorted
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct StoreDiff {
+    /// Only in the other store
+    pub added: Vec<Key>,
+    /// Only in this store
+    pub removed: Vec<Key>,
+    /// In both, with different values
+    pub changed: Vec<Key>,
+}
+
+impl StoreDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Result of one `compact_incremental` step
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompactionProgress {
+    InProgress { bytes_moved: usize, keys_remaining: usize },
+    Done(CompactionReport),
+}
+
+impl CompactionProgress {
+    pub fn is_done(&self) -> bool {
+        matches!(self, CompactionProgress::Done(_))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum OwnedEntry {
     Int(i64),
     Text(String),
+    Bool(bool),
+    Bytes(Vec<u8>),
 }
 
 pub fn borrowed_to_owned(entry: &BorrowedEntry) -> OwnedEntry {
     match entry {
         BorrowedEntry::Int(i) => OwnedEntry::Int(*i),
         BorrowedEntry::Text(s) => OwnedEntry::Text(s.to_string()),
+        BorrowedEntry::Bool(b) => OwnedEntry::Bool(*b),
+        BorrowedEntry::Bytes(b) => OwnedEntry::Bytes(b.to_vec()),
     }
 }
 
@@ -33,5 +197,7 @@ pub fn owned_to_value(entry: &OwnedEntry) -> Value {
     match entry {
         OwnedEntry::Int(i) => Value::Int(*i),
         OwnedEntry::Text(s) => Value::String(s.clone()),
+        OwnedEntry::Bool(b) => Value::Bool(*b),
+        OwnedEntry::Bytes(b) => Value::Bytes(b.clone()),
     }
 }
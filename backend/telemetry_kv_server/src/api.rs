@@ -1,62 +1,869 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
-use serde::Serialize;
+use futures_util::stream::{self, Stream};
+use kiwi_store::{TypeCount, TypeHistogram};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
 use crate::websocket::AppState;
-use crate::types::{FlightMetadata, TelemetryPacket};
+use crate::error::ApiError;
+use crate::kml;
+use crate::import;
+use crate::types::{FlightMetadata, IntegrityReport, TelemetryPacket};
+use crate::wall_clock::absolute_timestamp;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TelemetryPacketWithPhase {
     #[serde(flatten)]
     pub packet: TelemetryPacket,
     pub flight_phase: String,
+    /// ISO 8601 wall-clock time, for packets of flights with a start epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+}
+
+impl From<TelemetryPacket> for TelemetryPacketWithPhase {
+    fn from(packet: TelemetryPacket) -> Self {
+        TelemetryPacketWithPhase {
+            flight_phase: packet.get_flight_phase().to_string(),
+            packet,
+            time: None,
+        }
+    }
+}
+
+impl TelemetryPacketWithPhase {
+    /// With `time` from the flight the packet belongs to
+    fn in_flight(packet: TelemetryPacket, flight: Option<&FlightMetadata>) -> Self {
+        TelemetryPacketWithPhase {
+            time: flight.and_then(|f| absolute_timestamp(&packet, f)),
+            ..packet.into()
+        }
+    }
+}
+
+const DEFAULT_FLIGHTS_PAGE_SIZE: usize = 100;
+const DEFAULT_DATA_PAGE_SIZE: usize = 1000;
+const MAX_PAGE_SIZE: usize = 10_000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// One page of a sorted result, with the size of the whole result
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<T>,
+}
+
+impl<T> Page<T> {
+    fn from_vec(items: Vec<T>, query: &PageQuery, default_limit: usize) -> Self {
+        let total = items.len();
+        let offset = query.offset.unwrap_or(0);
+        let limit = query.limit.unwrap_or(default_limit).min(MAX_PAGE_SIZE);
+        
+        let items = items.into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect();
+        
+        Page { total, offset, limit, items }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlightStatus {
+    Landed,
+    InProgress,
+    Catastrophic,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FlightQuery {
+    pub source: Option<String>,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub status: Option<FlightStatus>,
+}
+
+impl FlightQuery {
+    /// Whether a flight passes every filter given; `from`/`to` are inclusive
+    pub fn matches(&self, flight: &FlightMetadata) -> bool {
+        if let Some(source) = &self.source {
+            if flight.source_id.as_ref() != Some(source) {
+                return false;
+            }
+        }
+        if self.from.is_some_and(|from| flight.start_time < from) {
+            return false;
+        }
+        if self.to.is_some_and(|to| flight.start_time > to) {
+            return false;
+        }
+        
+        match self.status {
+            Some(FlightStatus::Landed) => flight.current_status == "Landed",
+            Some(FlightStatus::InProgress) => {
+                flight.ended_normally && flight.current_status != "Landed"
+            }
+            Some(FlightStatus::Catastrophic) => !flight.ended_normally,
+            None => true,
+        }
+    }
 }
 
 pub async fn list_flights(
+    Query(query): Query<FlightQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
-) -> Json<Vec<FlightMetadata>> {
+) -> Json<Page<FlightMetadata>> {
     let storage = state.storage.lock().await;
-    Json(storage.list_flights())
+    let mut flights = storage.list_flights();
+    flights.retain(|f| query.matches(f));
+    
+    Json(Page::from_vec(flights, &page, DEFAULT_FLIGHTS_PAGE_SIZE))
 }
 
 pub async fn get_flight(
     Path(flight_id): Path<String>,
     State(state): State<AppState>,
-) -> Result<Json<FlightMetadata>, StatusCode> {
+) -> Result<Json<FlightMetadata>, ApiError> {
     let storage = state.storage.lock().await;
     storage.get_flight(&flight_id)
         .map(Json)
-        .ok_or(StatusCode::NOT_FOUND)
+        .ok_or_else(|| ApiError::not_found(format!("flight {} not found", flight_id)))
 }
 
-pub async fn get_flight_data(
+/// The in-progress flight of the most recently heard-from source, 204 when idle
+pub async fn current_flight(State(state): State<AppState>) -> Response {
+    let storage = state.storage.lock().await;
+    match storage.get_current_flight_id().and_then(|id| storage.get_flight(&id)) {
+        Some(flight) => Json(flight).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LabelRequest {
+    pub label: Option<String>,
+}
+
+pub async fn update_flight_label(
+    Path(flight_id): Path<String>,
+    State(state): State<AppState>,
+    Json(request): Json<LabelRequest>,
+) -> Result<Json<FlightMetadata>, ApiError> {
+    let mut storage = state.storage.lock().await;
+    storage.set_flight_label(&flight_id, request.label)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("flight {} not found", flight_id)))
+}
+
+/// Rebuild a flight's derived metadata from its packets
+pub async fn recompute_flight(
+    Path(flight_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<FlightMetadata>, ApiError> {
+    let mut storage = state.storage.lock().await;
+    storage.recompute_flight(&flight_id)?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("flight {} not found", flight_id)))
+}
+
+/// Check a flight's stored packets against its integrity hash
+pub async fn flight_integrity(
     Path(flight_id): Path<String>,
     State(state): State<AppState>,
-) -> Json<Vec<TelemetryPacketWithPhase>> {
+) -> Result<Json<IntegrityReport>, ApiError> {
     let storage = state.storage.lock().await;
-    let packets = storage.get_flight_data(&flight_id);
+    storage.verify_integrity(&flight_id)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(format!("flight {} not found", flight_id)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FlightDataQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// `next_cursor` of an earlier response; takes precedence over `offset`
+    pub after: Option<String>,
+    /// Round float fields to this many decimals, full precision if unset
+    pub precision: Option<u32>,
+}
+
+const MAX_PRECISION: u32 = 12;
+
+/// Serializes `value` with its floats rounded to `decimals`, or unchanged
+/// without. Integers and strings are left alone.
+#[derive(Debug)]
+pub struct Rounded<T> {
+    pub value: T,
+    pub decimals: Option<u32>,
+}
+
+impl<T: Serialize> Serialize for Rounded<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(decimals) = self.decimals else {
+            return self.value.serialize(serializer);
+        };
+        let mut json = serde_json::to_value(&self.value).map_err(serde::ser::Error::custom)?;
+        round_floats(&mut json, 10f64.powi(decimals.min(MAX_PRECISION) as i32));
+        json.serialize(serializer)
+    }
+}
+
+fn round_floats(json: &mut serde_json::Value, scale: f64) {
+    match json {
+        serde_json::Value::Number(n) if n.is_f64() => {
+            let rounded = n.as_f64().map(|x| (x * scale).round() / scale);
+            if let Some(rounded) = rounded.and_then(serde_json::Number::from_f64) {
+                *n = rounded;
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(|item| round_floats(item, scale)),
+        serde_json::Value::Object(fields) => fields.values_mut().for_each(|field| round_floats(field, scale)),
+        _ => {}
+    }
+}
+
+/// A page of flight data and the cursor to pass as `?after=` for the next one
+#[derive(Debug, Serialize)]
+pub struct FlightDataPage {
+    #[serde(flatten)]
+    pub page: Page<TelemetryPacketWithPhase>,
+    pub next_cursor: Option<String>,
+}
+
+/// Names a packet by its `(timestamp, packet_sequence)`
+fn packet_cursor(packet: &TelemetryPacket) -> String {
+    format!("{}-{}", packet.timestamp, packet.packet_sequence)
+}
+
+fn parse_cursor(cursor: &str) -> Option<(u64, u32)> {
+    match cursor.split_once('-') {
+        Some((timestamp, sequence)) => Some((timestamp.parse().ok()?, sequence.parse().ok()?)),
+        // A bare timestamp skips everything at that millisecond
+        None => Some((cursor.parse().ok()?, u32::MAX)),
+    }
+}
+
+pub async fn get_flight_data(
+    Path(flight_id): Path<String>,
+    Query(query): Query<FlightDataQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Rounded<FlightDataPage>>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_DATA_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let flight = state.storage.lock().await.get_flight(&flight_id);
+    let (items, total, offset) = match &query.after {
+        // Unlike offsets, a cursor doesn't shift as the active flight grows
+        Some(cursor) => {
+            let after = parse_cursor(cursor)
+                .ok_or_else(|| ApiError::bad_request(format!("invalid cursor {:?}", cursor)))?;
+            state.storage.lock().await.get_flight_data_after(&flight_id, after, limit)
+        }
+        None => {
+            let offset = query.offset.unwrap_or(0);
+            let (items, total) = state.storage.lock().await.get_flight_data_page(&flight_id, offset, limit);
+            (items, total, offset)
+        }
+    };
     
-    // Add flight_phase to each packet
-    let packets_with_phase: Vec<TelemetryPacketWithPhase> = packets
+    let next_cursor = items.last().map(packet_cursor).or(query.after);
+    
+    // Add flight_phase and wall-clock time to each packet
+    let packets_with_phase: Vec<TelemetryPacketWithPhase> = items
         .into_iter()
-        .map(|packet| TelemetryPacketWithPhase {
-            flight_phase: packet.get_flight_phase().to_string(),
-            packet,
-        })
+        .map(|packet| TelemetryPacketWithPhase::in_flight(packet, flight.as_ref()))
         .collect();
     
-    Json(packets_with_phase)
+    Ok(Json(Rounded {
+        value: FlightDataPage {
+            page: Page {
+                items: packets_with_phase,
+                total,
+                offset,
+                limit,
+            },
+            next_cursor,
+        },
+        decimals: query.precision,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    pub into: String,
+    pub from: String,
+}
+
+/// Join two flights that detection split apart, keeping `into`
+pub async fn merge_flights(
+    State(state): State<AppState>,
+    Json(request): Json<MergeRequest>,
+) -> Result<Json<FlightMetadata>, ApiError> {
+    let mut storage = state.storage.lock().await;
+    Ok(Json(storage.merge_flights(&request.into, &request.from)?))
+}
+
+/// Replay a CSV flight log through flight detection, as source `import`
+pub async fn import_flight(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<ImportReport>, ApiError> {
+    let parsed = import::parse_csv(&body).map_err(ApiError::bad_request)?;
+    
+    let mut storage = state.storage.lock().await;
+    storage.import_packets(&parsed.packets)?;
+    
+    Ok(Json(ImportReport {
+        accepted: parsed.packets.len(),
+        rejected: parsed.errors.len(),
+        errors: parsed.errors,
+    }))
+}
+
+/// A flight's packets as newline-delimited JSON, read and sent a page at a
+/// time so a long flight is never held in memory whole
+pub async fn get_flight_data_ndjson(
+    Path(flight_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Response, ApiError> {
+    let Some(flight) = state.storage.lock().await.get_flight(&flight_id) else {
+        return Err(ApiError::not_found(format!("flight {} not found", flight_id)));
+    };
+    
+    let lines = stream::unfold(Some(0), move |offset| {
+        let storage = state.storage.clone();
+        let flight_id = flight_id.clone();
+        let flight = flight.clone();
+        async move {
+            let offset = offset?;
+            let (packets, total) = storage.lock().await
+                .get_flight_data_page(&flight_id, offset, DEFAULT_DATA_PAGE_SIZE);
+            let mut chunk = Vec::new();
+            for packet in packets {
+                serde_json::to_writer(&mut chunk, &TelemetryPacketWithPhase::in_flight(packet, Some(&flight)))
+                    .expect("packets always serialize");
+                chunk.push(b'\n');
+            }
+            let next = offset + DEFAULT_DATA_PAGE_SIZE;
+            Some((Ok::<_, Infallible>(chunk), (next < total).then_some(next)))
+        }
+    });
+    
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    ).into_response())
+}
+
+/// Flight track as KML, for Google Earth
+pub async fn get_flight_kml(
+    Path(flight_id): Path<String>,
+    State(state): State<AppState>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), ApiError> {
+    let storage = state.storage.lock().await;
+    let flight = storage.get_flight(&flight_id)
+        .ok_or_else(|| ApiError::not_found(format!("flight {} not found", flight_id)))?;
+    let packets = storage.get_flight_data(&flight_id);
+    
+    Ok((
+        [(header::CONTENT_TYPE, "application/vnd.google-earth.kml+xml")],
+        kml::flight_track(&flight, &packets),
+    ))
 }
 
 pub async fn delete_flight(
     Path(flight_id): Path<String>,
     State(state): State<AppState>,
-) -> StatusCode {
+) -> Result<StatusCode, ApiError> {
+    // Also cleans up after an earlier delete that stopped halfway
+    if !state.storage.lock().await.delete_flight(&flight_id)? {
+        return Err(ApiError::not_found(format!("flight {} not found", flight_id)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfirmQuery {
+    pub confirm: Option<bool>,
+}
+
+pub async fn delete_all_flights(
+    Query(query): Query<ConfirmQuery>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, ApiError> {
+    // Wiping everything has to be asked for explicitly
+    if query.confirm != Some(true) {
+        return Err(ApiError::bad_request("pass confirm=true to delete all flights"));
+    }
+    
+    let mut storage = state.storage.lock().await;
+    storage.delete_all_flights()?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoreStats {
+    pub flights: usize,
+    pub data_bytes: usize,
+    pub fragmentation_ratio: f64,
+    pub value_types: ValueTypeStats,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+impl From<TypeCount> for TypeStats {
+    fn from(tally: TypeCount) -> Self {
+        TypeStats { count: tally.count, bytes: tally.bytes }
+    }
+}
+
+/// Live values by type: metadata is strings, counters ints, telemetry either
+/// strings or bytes
+#[derive(Debug, Serialize)]
+pub struct ValueTypeStats {
+    pub ints: TypeStats,
+    pub strings: TypeStats,
+    pub bools: TypeStats,
+    pub bytes: TypeStats,
+    pub unreadable: usize,
+}
+
+impl From<TypeHistogram> for ValueTypeStats {
+    fn from(histogram: TypeHistogram) -> Self {
+        ValueTypeStats {
+            ints: histogram.ints.into(),
+            strings: histogram.strings.into(),
+            bools: histogram.bools.into(),
+            bytes: histogram.bytes.into(),
+            unreadable: histogram.unreadable,
+        }
+    }
+}
+
+pub async fn stats(State(state): State<AppState>) -> Json<StoreStats> {
+    let storage = state.storage.lock().await;
+    Json(StoreStats {
+        flights: storage.list_flights().len(),
+        data_bytes: storage.data_size(),
+        fragmentation_ratio: storage.fragmentation_ratio(),
+        value_types: storage.type_histogram().into(),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompactResponse {
+    pub bytes_reclaimed: usize,
+}
+
+/// Compact the store now. Ingest waits on the storage lock meanwhile,
+/// so this has to be asked for explicitly like `delete_all_flights`.
+pub async fn compact(
+    Query(query): Query<ConfirmQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<CompactResponse>, ApiError> {
+    if query.confirm != Some(true) {
+        return Err(ApiError::bad_request("pass confirm=true to compact the store"));
+    }
+    
     let mut storage = state.storage.lock().await;
-    match storage.delete_flight(&flight_id) {
-        Ok(_) => StatusCode::NO_CONTENT,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    let report = storage.compact()?;
+    Ok(Json(CompactResponse { bytes_reclaimed: report.bytes_reclaimed }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct LivePacket {
+    #[serde(flatten)]
+    pub packet: TelemetryPacketWithPhase,
+    /// Estimated flight time left on the pack of this packet's source
+    pub time_to_empty_secs: Option<u64>,
+}
+
+/// Most recent packet from any source, 204 until one has arrived
+pub async fn latest_packet(State(state): State<AppState>) -> Response {
+    let Some(latest) = state.latest_packet.lock().await.clone() else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    let time_to_empty_secs = state.storage.lock().await.time_to_empty_secs(latest.source_id.as_deref());
+    Json(LivePacket { packet: latest.packet.into(), time_to_empty_secs }).into_response()
+}
+
+/// Server-sent event stream of flight alerts
+pub async fn alerts_stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    sse_stream(state.alert_tx.subscribe())
+}
+
+/// Server-sent event stream of flight starts and ends
+pub async fn events_stream(
+    State(state): State<AppState>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    sse_stream(state.event_tx.subscribe())
+}
+
+/// One JSON event per message, skipping over any a slow client missed
+fn sse_stream<T: Serialize + Clone + Send + 'static>(
+    rx: broadcast::Receiver<T>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(message) => return Some((Event::default().json_data(message), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::{airborne_packet, remove_store_files};
+    use crate::types::SourcedPacket;
+
+    fn flight(flight_id: &str, start_time: u64, status: &str, ended_normally: bool) -> FlightMetadata {
+        FlightMetadata {
+            flight_id: flight_id.to_string(),
+            start_time,
+            end_time: start_time + 1000,
+            duration_secs: 1,
+            packet_count: 2,
+            ended_normally,
+            current_status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn matching_ids(query: &FlightQuery, flights: &[FlightMetadata]) -> Vec<String> {
+        flights.iter()
+            .filter(|f| query.matches(f))
+            .map(|f| f.flight_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_date_filter_is_inclusive() {
+        let flights = vec![
+            flight("flight_001", 1000, "Landed", true),
+            flight("flight_002", 2000, "Landed", true),
+            flight("flight_003", 3000, "Landed", true),
+            flight("flight_004", 4000, "Landed", true),
+        ];
+
+        let query = FlightQuery { from: Some(2000), to: Some(3000), ..Default::default() };
+        assert_eq!(matching_ids(&query, &flights), vec!["flight_002", "flight_003"]);
+
+        let query = FlightQuery { from: Some(2001), ..Default::default() };
+        assert_eq!(matching_ids(&query, &flights), vec!["flight_003", "flight_004"]);
+
+        let query = FlightQuery { to: Some(1000), ..Default::default() };
+        assert_eq!(matching_ids(&query, &flights), vec!["flight_001"]);
+
+        assert_eq!(matching_ids(&FlightQuery::default(), &flights).len(), 4);
+    }
+
+    #[test]
+    fn test_status_filter() {
+        let flights = vec![
+            flight("flight_001", 1000, "Landed", true),
+            flight("flight_002", 2000, "Cruise", true),
+            flight("flight_003", 3000, "Cruise", false),
+        ];
+
+        let by_status = |status| FlightQuery { status: Some(status), ..Default::default() };
+        assert_eq!(matching_ids(&by_status(FlightStatus::Landed), &flights), vec!["flight_001"]);
+        assert_eq!(matching_ids(&by_status(FlightStatus::InProgress), &flights), vec!["flight_002"]);
+        assert_eq!(matching_ids(&by_status(FlightStatus::Catastrophic), &flights), vec!["flight_003"]);
+    }
+
+    #[test]
+    fn test_page_slicing() {
+        let items: Vec<u32> = (0..25).collect();
+
+        let page = Page::from_vec(items.clone(), &PageQuery { limit: Some(10), offset: Some(20) }, 100);
+        assert_eq!(page.total, 25);
+        assert_eq!(page.offset, 20);
+        assert_eq!(page.limit, 10);
+        assert_eq!(page.items, vec![20, 21, 22, 23, 24]);
+
+        let page = Page::from_vec(items.clone(), &PageQuery { limit: Some(5), offset: Some(30) }, 100);
+        assert_eq!(page.total, 25);
+        assert!(page.items.is_empty());
+    }
+
+    #[test]
+    fn test_page_defaults_are_capped() {
+        let items: Vec<u32> = (0..250).collect();
+
+        let page = Page::from_vec(items.clone(), &PageQuery::default(), 100);
+        assert_eq!(page.total, 250);
+        assert_eq!(page.limit, 100);
+        assert_eq!(page.items.len(), 100);
+        assert_eq!(page.items[0], 0);
+
+        let page = Page::from_vec(items, &PageQuery { limit: Some(usize::MAX), offset: None }, 100);
+        assert_eq!(page.limit, MAX_PAGE_SIZE);
+    }
+
+    fn app_state(storage: crate::storage::TelemetryStorage) -> AppState {
+        use std::sync::Arc;
+        use tokio::sync::{broadcast, Mutex};
+
+        AppState {
+            storage: Arc::new(Mutex::new(storage)),
+            broadcast_tx: broadcast::channel(1).0,
+            alert_tx: broadcast::channel(1).0,
+            event_tx: broadcast::channel(1).0,
+            link: Arc::new(crate::health::LinkStatus::new()),
+            latest_packet: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_missing_flight_is_json_404() {
+        let path = "/tmp/test_api_missing_flight";
+        remove_store_files(path);
+        let state = app_state(crate::storage::TelemetryStorage::new(path).unwrap());
+
+        let err = get_flight(Path("flight_404".to_string()), State(state)).await.unwrap_err();
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "error": "not_found",
+            "detail": "flight flight_404 not found",
+        }));
+
+        remove_store_files(path);
+    }
+
+    #[tokio::test]
+    async fn test_compact_route_reclaims_space() {
+        let path = "/tmp/test_api_compact";
+        remove_store_files(path);
+        let mut storage = crate::storage::TelemetryStorage::new(path).unwrap();
+        // Every packet rewrites the flight metadata, leaving the old copy behind
+        for i in 0..20 {
+            storage.save_packet(&airborne_packet(1000 + i * 100)).unwrap();
+        }
+        let state = app_state(storage);
+
+        let unconfirmed = compact(Query(ConfirmQuery::default()), State(state.clone())).await;
+        assert_eq!(unconfirmed.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        let Json(before) = stats(State(state.clone())).await;
+        assert!(before.fragmentation_ratio > 0.0);
+
+        let confirm = ConfirmQuery { confirm: Some(true) };
+        let Json(response) = compact(Query(confirm), State(state.clone())).await.unwrap();
+        assert!(response.bytes_reclaimed > 0);
+
+        let Json(after) = stats(State(state.clone())).await;
+        assert_eq!(after.fragmentation_ratio, 0.0);
+        assert_eq!(after.flights, 1);
+
+        drop(state);
+        remove_store_files(path);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pages_survive_appends() {
+        let path = "/tmp/test_api_cursor";
+        remove_store_files(path);
+        // Pairs of packets share a timestamp
+        let packet = |i: u64| TelemetryPacket { packet_sequence: i as u32, ..airborne_packet(1000 + (i / 2) * 100) };
+        let mut storage = crate::storage::TelemetryStorage::new(path).unwrap();
+        for i in 0..25 {
+            storage.save_packet(&packet(i)).unwrap();
+        }
+        let state = app_state(storage);
+        let fetch = |after: Option<String>| {
+            let query = FlightDataQuery { limit: Some(10), after, ..Default::default() };
+            get_flight_data(Path("flight_001".to_string()), Query(query), State(state.clone()))
+        };
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        for round in 0..5 {
+            let Json(Rounded { value: page, .. }) = fetch(cursor.clone()).await.unwrap();
+            seen.extend(page.page.items.iter().map(|p| p.packet.packet_sequence));
+            cursor = page.next_cursor;
+            if round == 0 {
+                // New packets arrive between fetches
+                let mut storage = state.storage.lock().await;
+                for i in 25..40 {
+                    storage.save_packet(&packet(i)).unwrap();
+                }
+            }
+        }
+        assert_eq!(seen, (0..40).collect::<Vec<u32>>());
+
+        // Nothing new: the cursor stays put
+        let Json(Rounded { value: empty, .. }) = fetch(cursor.clone()).await.unwrap();
+        assert!(empty.page.items.is_empty());
+        assert_eq!(empty.next_cursor, cursor);
+
+        let invalid = fetch(Some("soon".to_string())).await;
+        assert_eq!(invalid.unwrap_err().status, StatusCode::BAD_REQUEST);
+
+        drop(state);
+        remove_store_files(path);
+    }
+
+    #[tokio::test]
+    async fn test_latest_packet_returns_cached_packet() {
+        let state = app_state(crate::storage::TelemetryStorage::new_in_memory());
+
+        let response = latest_packet(State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let packet = TelemetryPacket {
+            altitude_baro: 150.0,
+            ground_speed: 25.0,
+            timestamp: 4242,
+            ..Default::default()
+        };
+        *state.latest_packet.lock().await = Some(SourcedPacket { source_id: None, packet });
+
+        let response = latest_packet(State(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["timestamp"], 4242);
+        assert_eq!(json["flight_phase"], "Cruise");
+        assert_eq!(json["time_to_empty_secs"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_latest_packet_estimate_is_for_its_source() {
+        let mut storage = crate::storage::TelemetryStorage::new_in_memory();
+        let flying = |i: u64| TelemetryPacket { battery_power: 150.0, battery_voltage: 16.0, ..airborne_packet(1000 + i * 200) };
+        for i in 0..20 {
+            storage.save_packet_from(Some("drone_1"), &flying(i)).unwrap();
+        }
+        let parked = TelemetryPacket { latitude: 49.8728, longitude: 8.6512, timestamp: 9000, ..Default::default() };
+        storage.save_packet_from(Some("drone_2"), &parked).unwrap();
+        let state = app_state(storage);
+
+        let estimate = |source_id: &str, packet| {
+            let state = state.clone();
+            let source_id = Some(source_id.to_string());
+            async move {
+                *state.latest_packet.lock().await = Some(SourcedPacket { source_id, packet });
+                let response = latest_packet(State(state)).await;
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()["time_to_empty_secs"].clone()
+            }
+        };
+        assert!(estimate("drone_1", flying(19)).await.is_u64());
+        assert_eq!(estimate("drone_2", parked).await, serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_streams_one_line_per_packet() {
+        let mut storage = crate::storage::TelemetryStorage::new_in_memory();
+        // Spans several pages
+        for i in 0..2500 {
+            storage.save_packet(&airborne_packet(1000 + i * 100)).unwrap();
+        }
+        let packet_count = storage.get_flight("flight_001").unwrap().packet_count;
+        let state = app_state(storage);
+
+        let response = get_flight_data_ndjson(Path("flight_001".to_string()), State(state.clone())).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.ends_with('\n'));
+        let lines: Vec<serde_json::Value> = body.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), packet_count);
+        assert!(lines.windows(2).all(|pair| pair[0]["timestamp"].as_u64() < pair[1]["timestamp"].as_u64()));
+        assert_eq!(lines[0]["flight_phase"], "Cruise");
+
+        let missing = get_flight_data_ndjson(Path("flight_999".to_string()), State(state)).await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_current_flight() {
+        let state = app_state(crate::storage::TelemetryStorage::new_in_memory());
+        let response = current_flight(State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        for i in 0..10 {
+            state.storage.lock().await.save_packet(&airborne_packet(1000 + i * 200)).unwrap();
+        }
+        let response = current_flight(State(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let flight: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(flight["flight_id"], "flight_001");
+        assert_eq!(flight["current_status"], "Cruise");
+    }
+
+    #[tokio::test]
+    async fn test_precision_rounds_floats() {
+        let mut storage = crate::storage::TelemetryStorage::new_in_memory();
+        for i in 0..6 {
+            storage.save_packet(&TelemetryPacket {
+                latitude: 49.87281234567,
+                battery_voltage: 16.8,
+                ..airborne_packet(1000 + i * 200)
+            }).unwrap();
+        }
+        let state = app_state(storage);
+        let fetch = |precision| {
+            let query = FlightDataQuery { precision, ..Default::default() };
+            get_flight_data(Path("flight_001".to_string()), Query(query), State(state.clone()))
+        };
+
+        let Json(page) = fetch(Some(6)).await.unwrap();
+        let json = serde_json::to_value(&page).unwrap();
+        let packet = &json["items"][0];
+        assert_eq!(packet["latitude"], 49.872812);
+        assert_eq!(packet["timestamp"], 1000);
+        assert_eq!(json["next_cursor"], page.value.next_cursor.clone().unwrap());
+
+        let Json(page) = fetch(Some(2)).await.unwrap();
+        let json = serde_json::to_value(&page).unwrap();
+        assert_eq!(json["items"][0]["latitude"], 49.87);
+        assert_eq!(json["items"][0]["battery_voltage"], 16.8);
+
+        // f32 fields show their float noise at full precision
+        let Json(page) = fetch(None).await.unwrap();
+        let json = serde_json::to_value(&page).unwrap();
+        assert_eq!(json["items"][0]["latitude"], 49.87281234567);
+        assert_ne!(json["items"][0]["battery_voltage"], 16.8);
     }
 }
@@ -2,56 +2,172 @@ use tokio_tungstenite::{connect_async, tungstenite::Message};
 use futures_util::StreamExt;
 use tokio::sync::{Mutex, broadcast};
 use std::sync::Arc;
-use crate::storage::TelemetryStorage;
-use crate::types::TelemetryPacket;
+use tokio::time::{Duration, Instant};
+use crate::storage::{TelemetryStorage, StreamEnd};
+use crate::types::{TelemetryPacket, SourcedPacket};
+use crate::health::LinkStatus;
 
+/// Packets arriving this soon after the first of a burst are stored together
+const BATCH_WINDOW: Duration = Duration::from_millis(20);
+const MAX_BATCH: usize = 64;
+
+/// Connect to every configured telemetry source.
+///
+/// Sources are read from the comma-separated `SIMULATOR_WS_URLS`, falling back
+/// to the single `SIMULATOR_WS_URL`. With more than one source, each one is
+/// tagged `drone_1`, `drone_2`, ... so its flights are stored separately.
 pub async fn run_binary_client(
     storage: Arc<Mutex<TelemetryStorage>>,
-    broadcast_tx: broadcast::Sender<TelemetryPacket>,
+    broadcast_tx: broadcast::Sender<SourcedPacket>,
+    link: Arc<LinkStatus>,
+    latest_packet: Arc<Mutex<Option<SourcedPacket>>>,
+) {
+    let urls = source_urls();
+    let multi_source = urls.len() > 1;
+    
+    let mut tasks = Vec::new();
+    for (i, url) in urls.into_iter().enumerate() {
+        let source_id = if multi_source {
+            Some(format!("drone_{}", i + 1))
+        } else {
+            None
+        };
+        
+        tasks.push(tokio::spawn(run_source(
+            source_id,
+            url,
+            storage.clone(),
+            broadcast_tx.clone(),
+            link.clone(),
+            latest_packet.clone(),
+        )));
+    }
+    
+    // Each source reconnects on its own, so one going away never stops the others
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+fn source_urls() -> Vec<String> {
+    let urls: Vec<String> = std::env::var("SIMULATOR_WS_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    
+    if !urls.is_empty() {
+        return urls;
+    }
+    
+    vec![std::env::var("SIMULATOR_WS_URL")
+        .unwrap_or_else(|_| "ws://localhost:8080/ws/binary".to_string())]
+}
+
+async fn run_source(
+    source_id: Option<String>,
+    url: String,
+    storage: Arc<Mutex<TelemetryStorage>>,
+    broadcast_tx: broadcast::Sender<SourcedPacket>,
+    link: Arc<LinkStatus>,
+    latest_packet: Arc<Mutex<Option<SourcedPacket>>>,
 ) {
-    let url = std::env::var("SIMULATOR_WS_URL")
-        .unwrap_or_else(|_| "ws://localhost:8080/ws/binary".to_string());
+    let label = source_id.as_deref().unwrap_or("telemetry sim");
     
     loop {
-        println!("[Server] Connecting to telemetry sim at {}...", url);
+        println!("[Server] Connecting to {} at {}...", label, url);
         
         match connect_async(&url).await {
             Ok((ws_stream, _)) => {
-                println!("[Server] Connected to telemetry sim");
+                println!("[Server] Connected to {}", label);
+                link.source_connected();
                 let (_, mut read) = ws_stream.split();
+                let mut batch: Vec<TelemetryPacket> = Vec::new();
+                let mut flush_at: Option<Instant> = None;
+                let mut end = StreamEnd::Failed;
                 
-                while let Some(msg) = read.next().await {
+                loop {
+                    let msg = match flush_at {
+                        Some(deadline) => match tokio::time::timeout_at(deadline, read.next()).await {
+                            Ok(msg) => msg,
+                            Err(_) => {
+                                flush_batch(&storage, source_id.as_deref(), &mut batch, label).await;
+                                flush_at = None;
+                                continue;
+                            }
+                        },
+                        None => read.next().await,
+                    };
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    
                     match msg {
                         Ok(Message::Binary(bytes)) => {
-                            if let Ok(packet) = TelemetryPacket::from_bytes(&bytes) {
-                                // Store packet
-                                if let Err(e) = storage.lock().await.save_packet(&packet) {
-                                    eprintln!("Error saving packet: {}", e);
+                            let packet = match TelemetryPacket::from_bytes(&bytes) {
+                                Ok(packet) => packet,
+                                Err(e) => {
+                                    eprintln!("Dropping frame from {}: {}", label, e);
+                                    continue;
                                 }
-                                
-                                // Broadcast to WebSocket clients
-                                let _ = broadcast_tx.send(packet);
+                            };
+                            link.packet_received();
+                            let sourced = SourcedPacket { source_id: source_id.clone(), packet };
+                            *latest_packet.lock().await = Some(sourced.clone());
+                            
+                            // Live clients get the packet right away, storage waits for the batch
+                            let _ = broadcast_tx.send(sourced);
+                            
+                            batch.push(packet);
+                            if batch.len() >= MAX_BATCH {
+                                flush_batch(&storage, source_id.as_deref(), &mut batch, label).await;
+                                flush_at = None;
+                            } else if flush_at.is_none() {
+                                flush_at = Some(Instant::now() + BATCH_WINDOW);
                             }
                         }
                         Ok(Message::Close(_)) => {
-                            println!("[Server] Telemetry sim closed connection");
+                            println!("[Server] {} closed connection", label);
+                            end = StreamEnd::Closed;
                             break;
                         }
                         Err(e) => {
-                            eprintln!("WebSocket error: {}", e);
+                            eprintln!("WebSocket error from {}: {}", label, e);
                             break;
                         }
                         _ => {}
                     }
                 }
+                flush_batch(&storage, source_id.as_deref(), &mut batch, label).await;
+                if let Err(e) = storage.lock().await.on_stream_lost(source_id.as_deref(), end) {
+                    eprintln!("Error ending flight of {}: {}", label, e);
+                }
+                link.source_disconnected();
             }
             Err(e) => {
-                eprintln!("Failed to connect to telemetry sim: {}", e);
+                eprintln!("Failed to connect to {}: {}", label, e);
                 tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
             }
         }
         
-        println!("Reconnecting in 5 seconds...");
+        println!("Reconnecting to {} in 5 seconds...", label);
         tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
     }
 }
+
+async fn flush_batch(
+    storage: &Mutex<TelemetryStorage>,
+    source_id: Option<&str>,
+    batch: &mut Vec<TelemetryPacket>,
+    label: &str,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let errors = storage.lock().await.ingest_batch(source_id, batch);
+    for e in &errors {
+        eprintln!("Error saving packet from {}: {}", label, e);
+    }
+    batch.clear();
+}
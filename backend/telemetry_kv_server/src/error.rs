@@ -0,0 +1,63 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use kiwi_store::StoreError;
+use crate::storage::MergeRejected;
+use serde::Serialize;
+
+/// REST error with a JSON body: `{ "error": "not_found", "detail": "..." }`
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub error: &'static str,
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+    detail: &'a str,
+}
+
+impl ApiError {
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self { status: StatusCode::NOT_FOUND, error: "not_found", detail: detail.into() }
+    }
+
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, error: "bad_request", detail: detail.into() }
+    }
+
+    pub fn internal(detail: impl Into<String>) -> Self {
+        Self { status: StatusCode::INTERNAL_SERVER_ERROR, error: "internal", detail: detail.into() }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(rejected) = err.downcast_ref::<MergeRejected>() {
+            return match rejected {
+                MergeRejected::UnknownFlight(_) => ApiError::not_found(rejected.to_string()),
+                _ => ApiError::bad_request(rejected.to_string()),
+            };
+        }
+        match err.downcast_ref::<StoreError>() {
+            Some(StoreError::KeyNotFound(key)) => ApiError::not_found(format!("{:?} not found", key)),
+            Some(StoreError::DataCorruption { cause }) => ApiError {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                error: "data_corruption",
+                detail: cause.to_string(),
+            },
+            _ => ApiError::internal(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody { error: self.error, detail: &self.detail };
+        (self.status, Json(body)).into_response()
+    }
+}
@@ -0,0 +1,130 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::wall_clock::now_epoch_ms;
+use crate::websocket::AppState;
+
+/// How long the simulator may be gone before /health reports 503
+const DISCONNECT_THRESHOLD_MS: u64 = 30_000;
+
+/// Simulator link state, updated by the binary client and read by /health
+pub struct LinkStatus {
+    connected_sources: AtomicUsize,
+    last_seen_ms: AtomicU64,   // Last connect, disconnect or packet
+    last_packet_ms: AtomicU64, // 0 until the first packet
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthStatus {
+    pub storage_ok: bool,
+    pub simulator_connected: bool,
+    pub last_packet_age_ms: Option<u64>,
+}
+
+impl LinkStatus {
+    pub fn new() -> Self {
+        Self {
+            connected_sources: AtomicUsize::new(0),
+            last_seen_ms: AtomicU64::new(now_epoch_ms()),
+            last_packet_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn source_connected(&self) {
+        self.connected_sources.fetch_add(1, Ordering::Relaxed);
+        self.last_seen_ms.store(now_epoch_ms(), Ordering::Relaxed);
+    }
+
+    pub fn source_disconnected(&self) {
+        self.connected_sources.fetch_sub(1, Ordering::Relaxed);
+        self.last_seen_ms.store(now_epoch_ms(), Ordering::Relaxed);
+    }
+
+    pub fn packet_received(&self) {
+        let now = now_epoch_ms();
+        self.last_seen_ms.store(now, Ordering::Relaxed);
+        self.last_packet_ms.store(now, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected_sources.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn last_packet_age_ms(&self, now: u64) -> Option<u64> {
+        match self.last_packet_ms.load(Ordering::Relaxed) {
+            0 => None,
+            last => Some(now.saturating_sub(last)),
+        }
+    }
+
+    /// Short reconnects are fine, only a link down past the threshold is unhealthy
+    pub fn disconnected_too_long(&self, now: u64) -> bool {
+        let since_seen = now.saturating_sub(self.last_seen_ms.load(Ordering::Relaxed));
+        !self.is_connected() && since_seen > DISCONNECT_THRESHOLD_MS
+    }
+}
+
+impl Default for LinkStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub async fn health(State(state): State<AppState>) -> (StatusCode, Json<HealthStatus>) {
+    let storage_ok = state.storage.lock().await.last_save_error().is_none();
+    let now = now_epoch_ms();
+
+    let status = HealthStatus {
+        storage_ok,
+        simulator_connected: state.link.is_connected(),
+        last_packet_age_ms: state.link.last_packet_age_ms(now),
+    };
+    let code = if storage_ok && !state.link.disconnected_too_long(now) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (code, Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_status_json() {
+        let status = HealthStatus {
+            storage_ok: true,
+            simulator_connected: false,
+            last_packet_age_ms: Some(1500),
+        };
+        assert_eq!(serde_json::to_value(&status).unwrap(), serde_json::json!({
+            "storage_ok": true,
+            "simulator_connected": false,
+            "last_packet_age_ms": 1500,
+        }));
+    }
+
+    #[test]
+    fn test_disconnect_threshold() {
+        let link = LinkStatus::new();
+        let start = now_epoch_ms();
+
+        // Not connected yet, but within the grace period
+        assert!(!link.disconnected_too_long(start + 1000));
+        assert!(link.disconnected_too_long(start + DISCONNECT_THRESHOLD_MS + 1000));
+
+        link.source_connected();
+        assert!(!link.disconnected_too_long(start + DISCONNECT_THRESHOLD_MS * 10));
+        assert_eq!(link.last_packet_age_ms(start), None);
+
+        let before = now_epoch_ms();
+        link.packet_received();
+        link.source_disconnected();
+        let after = now_epoch_ms();
+        assert!(!link.disconnected_too_long(before + DISCONNECT_THRESHOLD_MS));
+        assert!(link.disconnected_too_long(after + DISCONNECT_THRESHOLD_MS + 1));
+        assert!(link.last_packet_age_ms(after + 200).unwrap() >= 200);
+    }
+}
@@ -0,0 +1,133 @@
+use serde_json::{Map, Number, Value};
+use crate::types::TelemetryPacket;
+
+/// Result of parsing a CSV flight log
+#[derive(Debug, Default)]
+pub struct ParsedCsv {
+    pub packets: Vec<TelemetryPacket>,
+    /// One message per rejected row, with its line number
+    pub errors: Vec<String>,
+}
+
+/// Parse a CSV log whose header names the `TelemetryPacket` fields, in any
+/// order. Every field must be present; rows that don't fit are rejected.
+pub fn parse_csv(text: &str) -> Result<ParsedCsv, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Err("empty CSV, expected a header row".to_string());
+    };
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+
+    let mut parsed = ParsedCsv::default();
+    for (i, line) in lines {
+        match parse_row(&columns, line) {
+            Ok(packet) => parsed.packets.push(packet),
+            Err(e) => parsed.errors.push(format!("line {}: {}", i + 1, e)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_row(columns: &[&str], line: &str) -> Result<TelemetryPacket, String> {
+    let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+    if cells.len() != columns.len() {
+        return Err(format!("expected {} columns, found {}", columns.len(), cells.len()));
+    }
+
+    let mut fields = Map::new();
+    for (column, cell) in columns.iter().zip(cells) {
+        let number = if let Ok(int) = cell.parse::<i64>() {
+            Number::from(int)
+        } else {
+            cell.parse::<f64>().ok()
+                .and_then(Number::from_f64)
+                .ok_or_else(|| format!("{} is not a number: {:?}", column, cell))?
+        };
+        fields.insert(column.to_string(), Value::Number(number));
+    }
+
+    serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_csv(packets: &[TelemetryPacket]) -> String {
+        let rows: Vec<Map<String, Value>> = packets.iter()
+            .map(|p| match serde_json::to_value(p).unwrap() {
+                Value::Object(fields) => fields,
+                _ => unreachable!(),
+            })
+            .collect();
+        let columns: Vec<String> = rows[0].keys().cloned().collect();
+
+        let mut csv = columns.join(",");
+        for row in &rows {
+            let cells: Vec<String> = columns.iter().map(|c| row[c].to_string()).collect();
+            csv.push('\n');
+            csv.push_str(&cells.join(","));
+        }
+        csv
+    }
+
+    #[test]
+    fn test_rejects_bad_rows() {
+        let mut csv = to_csv(&[TelemetryPacket { timestamp: 1000, ..Default::default() }]);
+        csv.push_str("\n1,2,3");
+        csv.push('\n');
+        csv.push_str(&to_csv(&[TelemetryPacket { num_satellites: 9, ..Default::default() }])
+            .lines().nth(1).unwrap()
+            .replacen("9", "900", 1));
+
+        let parsed = parse_csv(&csv).unwrap();
+        assert_eq!(parsed.packets.len(), 1);
+        assert_eq!(parsed.packets[0].timestamp, 1000);
+        assert_eq!(parsed.errors.len(), 2);
+        assert!(parsed.errors[0].starts_with("line 3:"));
+        assert!(parse_csv("").is_err());
+    }
+
+    #[test]
+    fn test_exported_flight_round_trips() -> anyhow::Result<()> {
+        use crate::storage::tests::remove_store_files;
+        use crate::storage::TelemetryStorage;
+
+        let source_path = "/tmp/test_import_source";
+        let target_path = "/tmp/test_import_target";
+        let remove_files = || {
+            remove_store_files(source_path);
+            remove_store_files(target_path);
+        };
+        remove_files();
+        {
+            let mut source = TelemetryStorage::new(source_path)?;
+            for i in 0..20 {
+                source.save_packet(&TelemetryPacket {
+                    latitude: 49.8728 + i as f64 * 0.0001,
+                    longitude: 8.6512,
+                    altitude_gps: 50.0,
+                    altitude_baro: 50.0,
+                    ground_speed: 20.0,
+                    battery_voltage: 16.8,
+                    timestamp: 1000 + i * 100,
+                    packet_sequence: i as u32,
+                    ..Default::default()
+                })?;
+            }
+            let exported = source.get_flight_data("flight_001");
+
+            let parsed = parse_csv(&to_csv(&exported)).unwrap();
+            assert!(parsed.errors.is_empty());
+
+            let mut target = TelemetryStorage::new(target_path)?;
+            target.import_packets(&parsed.packets)?;
+            let imported = target.get_flight_data("import:flight_001");
+            assert_eq!(imported.len(), exported.len());
+            assert_eq!(imported[5].latitude, exported[5].latitude);
+            assert_eq!(target.get_flight("import:flight_001").unwrap().current_status, "Landed");
+        }
+        remove_files();
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::types::{FlightMetadata, TelemetryPacket};
+use crate::wall_clock::format_iso8601;
+
+/// KML document with the flight as one extruded LineString placemark.
+/// Packets are expected sorted by timestamp.
+pub fn flight_track(flight: &FlightMetadata, packets: &[TelemetryPacket]) -> String {
+    // `{}` prints the shortest form that round-trips, so no GPS precision is lost
+    let coordinates: Vec<String> = packets.iter()
+        .map(|p| format!("{},{},{}", p.longitude, p.latitude, p.altitude_baro))
+        .collect();
+    let name = escape(flight.label.as_deref().unwrap_or(&flight.flight_id));
+    // Lets Google Earth's time slider find the flight
+    let time_span = match flight.start_epoch_ms {
+        Some(epoch) => format!(
+            "\n      <TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+            format_iso8601(epoch),
+            format_iso8601(epoch + flight.end_time.saturating_sub(flight.start_time)),
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+  <Document>
+    <Placemark>
+      <name>{name}</name>{time_span}
+      <ExtendedData>
+        <Data name="flight_id"><value>{flight_id}</value></Data>
+        <Data name="duration_secs"><value>{duration}</value></Data>
+        <Data name="distance_km"><value>{distance}</value></Data>
+      </ExtendedData>
+      <LineString>
+        <extrude>1</extrude>
+        <altitudeMode>relativeToGround</altitudeMode>
+        <coordinates>{coordinates}</coordinates>
+      </LineString>
+    </Placemark>
+  </Document>
+</kml>
+"#,
+        flight_id = escape(&flight.flight_id),
+        duration = flight.duration_secs,
+        distance = flight.distance_km,
+        coordinates = coordinates.join(" "),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_coordinates() {
+        let flight = FlightMetadata {
+            flight_id: "drone_1:flight_001".to_string(),
+            source_id: Some("drone_1".to_string()),
+            start_time: 1000,
+            end_time: 121_000,
+            duration_secs: 120,
+            distance_km: 1.5,
+            label: Some("Survey <north>".to_string()),
+            start_epoch_ms: Some(1_717_243_200_000),
+            ..Default::default()
+        };
+        let packets = [
+            TelemetryPacket { latitude: 49.87281234567, longitude: 8.65123456789, altitude_baro: 12.5, timestamp: 1000, ..Default::default() },
+            TelemetryPacket { latitude: 49.8729, longitude: 8.6513, altitude_baro: 30.0, timestamp: 2000, ..Default::default() },
+        ];
+
+        let kml = flight_track(&flight, &packets);
+        assert!(kml.contains("<coordinates>8.65123456789,49.87281234567,12.5 8.6513,49.8729,30</coordinates>"));
+        assert!(kml.contains("<altitudeMode>relativeToGround</altitudeMode>"));
+        assert!(kml.contains("<extrude>1</extrude>"));
+        assert!(kml.contains("<name>Survey &lt;north&gt;</name>"));
+        assert!(kml.contains("<value>drone_1:flight_001</value>"));
+        assert!(kml.contains("<value>120</value>"));
+        assert!(kml.contains("<TimeSpan><begin>2024-06-01T12:00:00.000Z</begin><end>2024-06-01T12:02:00.000Z</end></TimeSpan>"));
+    }
+}
@@ -3,11 +3,19 @@ mod storage;
 mod binary_client;
 mod websocket;
 mod api;
+mod error;
+mod health;
+mod kml;
+mod import;
+mod metrics;
+mod wall_clock;
 
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::{Mutex, broadcast};
 use axum::{
-    routing::get,
+    routing::{get, post},
     Router,
     response::Html,
 };
@@ -15,6 +23,8 @@ use tower_http::cors::CorsLayer;
 
 use storage::TelemetryStorage;
 use websocket::AppState;
+use types::{Geofence, FlightIdFormat, FlightDetectionConfig};
+use health::LinkStatus;
 
 async fn serve_control_panel() -> Html<&'static str> {
     Html(include_str!("../../control_panel.html"))
@@ -24,58 +34,247 @@ async fn serve_control_panel() -> Html<&'static str> {
 async fn main() {
     println!("[Server] Telemetry KV Server\n");
     
+    let store_path = std::env::var("KV_STORE_PATH").unwrap_or_else(|_| "telemetry_data".to_string());
+    let bind_addr = std::env::var("KV_BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:9090".to_string());
+    let bind_addr: SocketAddr = match bind_addr.parse() {
+        Ok(addr) => addr,
+        Err(_) => {
+            eprintln!("[Server] KV_BIND_ADDR '{}' is not a valid address, expected host:port like 0.0.0.0:9090", bind_addr);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = check_storage_writable(&store_path) {
+        eprintln!("[Server] Cannot write storage at '{}': {}. Set KV_STORE_PATH to a writable location.", store_path, e);
+        std::process::exit(1);
+    }
+    println!("[Server] Storage: {}", store_path);
+    println!("[Server] Bind address: {}", bind_addr);
+    
+    // Alerts raised while processing flights
+    let (alert_tx, _) = broadcast::channel(100);
+    let (event_tx, _) = broadcast::channel(100);
+    
     // Initialize storage
-    let storage = Arc::new(Mutex::new(
-        TelemetryStorage::new("telemetry_data")
-            .expect("Failed to initialize storage")
-    ));
+    let mut telemetry_storage = match TelemetryStorage::new(&store_path) {
+        Ok(storage) => storage.with_alerts(alert_tx.clone()).with_events(event_tx.clone()),
+        Err(e) => {
+            eprintln!("[Server] Failed to open storage at '{}': {}", store_path, e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(volts) = std::env::var("LOW_BATTERY_VOLTAGE").ok().and_then(|v| v.parse().ok()) {
+        telemetry_storage = telemetry_storage.with_low_battery_threshold(volts);
+    }
+    if let Some(mah) = std::env::var("BATTERY_CAPACITY_MAH").ok().and_then(|v| v.parse().ok()) {
+        telemetry_storage = telemetry_storage.with_battery_capacity(mah);
+    }
+    if let Some(fence) = geofence_from_env() {
+        println!("[Server] Geofence: {:.1} km around {:.5},{:.5}",
+                 fence.radius_km, fence.center_lat, fence.center_lon);
+        telemetry_storage = telemetry_storage.with_geofence(fence);
+    }
+    if let Some(max) = std::env::var("MAX_IN_MEMORY_PACKETS").ok().and_then(|v| v.parse().ok()) {
+        println!("[Server] Archiving completed flights beyond {} packets in memory", max);
+        telemetry_storage = telemetry_storage.with_max_in_memory_packets(max);
+    }
+    if let Some(max) = std::env::var("MAX_FLIGHTS").ok().and_then(|v| v.parse().ok()) {
+        if max > 0 {
+            println!("[Server] Keeping at most {} flights", max);
+        }
+        telemetry_storage = telemetry_storage.with_max_flights(max);
+    }
+    let detection = flight_detection_from_env();
+    if detection != FlightDetectionConfig::default() {
+        println!("[Server] Takeoff confirmed after {} ms, landing after {} ms",
+                 detection.takeoff_confirm_ms, detection.landing_confirm_ms);
+        telemetry_storage = telemetry_storage.with_flight_detection(detection);
+    }
+    if let Some(format) = flight_id_format_from_env() {
+        println!("[Server] Naming flights like {}", format.format(1));
+        telemetry_storage = telemetry_storage.with_flight_id_format(format);
+    }
+    if std::env::args().any(|arg| arg == "--skip-invalid-gps") {
+        println!("[Server] Not storing packets without a GPS fix");
+        telemetry_storage = telemetry_storage.with_invalid_gps_skipped();
+    }
+    if std::env::args().any(|arg| arg == "--binary-packets") {
+        println!("[Server] Storing packets in binary instead of JSON");
+        telemetry_storage = telemetry_storage.with_binary_packets();
+    }
+    if std::env::args().any(|arg| arg == "--derive-vspeed") {
+        println!("[Server] Deriving vertical speed from baro altitude");
+        telemetry_storage = telemetry_storage.with_derived_vertical_speed();
+    }
+    let storage = Arc::new(Mutex::new(telemetry_storage));
+    let storage_for_shutdown = storage.clone();
     
     // Create broadcast channel for real-time streaming
     let (broadcast_tx, _) = broadcast::channel(1000);
     
+    // Simulator link state for /health
+    let link = Arc::new(LinkStatus::new());
+    let latest_packet = Arc::new(Mutex::new(None));
+    
     // Start binary client task
     let storage_clone = storage.clone();
     let tx_clone = broadcast_tx.clone();
+    let link_clone = link.clone();
+    let latest_clone = latest_packet.clone();
     tokio::spawn(async move {
-        binary_client::run_binary_client(storage_clone, tx_clone).await;
+        binary_client::run_binary_client(storage_clone, tx_clone, link_clone, latest_clone).await;
     });
     
     // Create app state
     let state = AppState {
         storage,
         broadcast_tx,
+        alert_tx,
+        event_tx,
+        link,
+        latest_packet,
     };
     
     // Build router with all routes
     let app = Router::new()
         .route("/", get(serve_control_panel))
-        .route("/health", get(|| async { "OK" }))
+        .route("/health", get(health::health))
+        .route("/metrics", get(metrics::metrics))
         .route("/ws/stream", get(websocket::websocket_handler))
-        .route("/api/flights", get(api::list_flights))
+        .route("/api/alerts", get(api::alerts_stream))
+        .route("/api/events", get(api::events_stream))
+        .route("/api/live/latest", get(api::latest_packet))
+        .route("/api/stats", get(api::stats))
+        .route("/api/admin/compact", post(api::compact))
+        .route("/api/flights",
+            get(api::list_flights)
+                .delete(api::delete_all_flights))
+        .route("/api/flights/current", get(api::current_flight))
+        .route("/api/flights/import", post(api::import_flight))
+        .route("/api/flights/merge", post(api::merge_flights))
         .route("/api/flights/:id/data", get(api::get_flight_data))
+        .route("/api/flights/:id/data.ndjson", get(api::get_flight_data_ndjson))
+        .route("/api/flights/:id/track.kml", get(api::get_flight_kml))
+        .route("/api/flights/:id/recompute", post(api::recompute_flight))
+        .route("/api/flights/:id/integrity", get(api::flight_integrity))
         .route("/api/flights/:id", 
             get(api::get_flight)
+                .patch(api::update_flight_label)
                 .delete(api::delete_flight))
         .with_state(state)
         .layer(CorsLayer::permissive());
     
     
-    println!("[Server] Control Panel: http://0.0.0.0:9090");
-    println!("[Server] WebSocket: ws://0.0.0.0:9090/ws/stream");
-    println!("[Server] REST API:  http://0.0.0.0:9090/api");
+    println!("[Server] Control Panel: http://{}", bind_addr);
+    println!("[Server] WebSocket: ws://{}/ws/stream", bind_addr);
+    println!("[Server] REST API:  http://{}/api", bind_addr);
     println!("\nEndpoints:");
     println!("  GET    /api/flights          - List all flights");
+    println!("  GET    /api/flights/current  - Active flight, if any");
     println!("  GET    /api/flights/:id      - Get flight details");
     println!("  GET    /api/flights/:id/data - Get flight telemetry");
+    println!("  GET    /api/flights/:id/data.ndjson - Flight telemetry as NDJSON");
+    println!("  GET    /api/flights/:id/track.kml - Flight track as KML");
+    println!("  POST   /api/flights/import   - Import a CSV flight log");
+    println!("  POST   /api/flights/:id/recompute - Rebuild flight metadata");
+    println!("  GET    /api/flights/:id/integrity - Verify flight packets");
+    println!("  POST   /api/flights/merge    - Merge two flights");
+    println!("  PATCH  /api/flights/:id      - Set flight label");
     println!("  DELETE /api/flights/:id      - Delete flight");
+    println!("  DELETE /api/flights?confirm=true - Delete all flights");
+    println!("  GET    /api/live/latest      - Most recent packet");
+    println!("  GET    /api/alerts           - Alert stream (SSE)");
+    println!("  GET    /api/events           - Flight start/end stream (SSE)");
+    println!("  GET    /api/stats            - Store size and fragmentation");
+    println!("  POST   /api/admin/compact?confirm=true - Compact the store");
     println!("\nWaiting for telemetry data...\n");
     
     // Start server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:9090")
-        .await
-        .expect("Failed to bind to port 9090");
+    let listener = match tokio::net::TcpListener::bind(bind_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("[Server] Failed to bind {}: {}", bind_addr, e);
+            std::process::exit(1);
+        }
+    };
     
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .expect("Server error");
+    
+    println!("[Server] Shutting down, saving storage...");
+    let result = storage_for_shutdown.lock().await.close();
+    if let Err(e) = result {
+        eprintln!("[Server] Failed to save storage: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Probe the directory the store files will live in
+fn check_storage_writable(store_path: &str) -> std::io::Result<()> {
+    let dir = match Path::new(store_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let probe = dir.join(".kv_write_test");
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Resolves on Ctrl+C, or SIGTERM (docker stop) on unix
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// GEOFENCE_CENTER_LAT, GEOFENCE_CENTER_LON and GEOFENCE_RADIUS_KM, all required
+fn geofence_from_env() -> Option<Geofence> {
+    let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<f64>().ok());
+    Some(Geofence {
+        center_lat: var("GEOFENCE_CENTER_LAT")?,
+        center_lon: var("GEOFENCE_CENTER_LON")?,
+        radius_km: var("GEOFENCE_RADIUS_KM")?,
+    })
+}
+
+/// `TAKEOFF_CONFIRM_MS` and `LANDING_CONFIRM_MS`, each falling back to the default
+fn flight_detection_from_env() -> FlightDetectionConfig {
+    let var = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+    let default = FlightDetectionConfig::default();
+    FlightDetectionConfig {
+        takeoff_confirm_ms: var("TAKEOFF_CONFIRM_MS").unwrap_or(default.takeoff_confirm_ms),
+        landing_confirm_ms: var("LANDING_CONFIRM_MS").unwrap_or(default.landing_confirm_ms),
+    }
+}
+
+/// `FLIGHT_ID_PREFIX` and `FLIGHT_ID_WIDTH`, each falling back to the default
+fn flight_id_format_from_env() -> Option<FlightIdFormat> {
+    let prefix = std::env::var("FLIGHT_ID_PREFIX").ok();
+    let width = std::env::var("FLIGHT_ID_WIDTH").ok().and_then(|v| v.parse().ok());
+    if prefix.is_none() && width.is_none() {
+        return None;
+    }
+    let default = FlightIdFormat::default();
+    Some(FlightIdFormat {
+        prefix: prefix.unwrap_or(default.prefix),
+        width: width.unwrap_or(default.width),
+    })
 }
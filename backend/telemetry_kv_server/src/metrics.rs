@@ -0,0 +1,108 @@
+use axum::{extract::State, http::header};
+use std::fmt::Write;
+use crate::wall_clock::now_epoch_ms;
+use crate::websocket::AppState;
+
+/// Snapshot of everything `/metrics` reports
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub packets_ingested: u64,
+    pub active_flights: usize,
+    pub data_bytes: usize,
+    pub live_bytes: usize,
+    pub fragmentation_ratio: f64,
+    pub stream_subscribers: usize,
+    pub last_packet_age_ms: Option<u64>,
+}
+
+impl Metrics {
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        metric("telemetry_packets_ingested_total", "counter",
+               "Packets received from all sources", self.packets_ingested as f64);
+        metric("telemetry_active_flights", "gauge",
+               "Flights currently in progress", self.active_flights as f64);
+        metric("telemetry_store_data_bytes", "gauge",
+               "Bytes held by the store, live or not", self.data_bytes as f64);
+        metric("telemetry_store_live_bytes", "gauge",
+               "Bytes held by live values", self.live_bytes as f64);
+        metric("telemetry_store_fragmentation_ratio", "gauge",
+               "Share of store bytes held by dead values", self.fragmentation_ratio);
+        metric("telemetry_stream_subscribers", "gauge",
+               "WebSocket clients on the live stream", self.stream_subscribers as f64);
+        // Absent until the first packet, rather than a misleading 0
+        if let Some(age) = self.last_packet_age_ms {
+            metric("telemetry_last_packet_age_seconds", "gauge",
+                   "Time since the last packet", age as f64 / 1000.0);
+        }
+        out
+    }
+}
+
+pub async fn metrics(State(state): State<AppState>) -> ([(header::HeaderName, &'static str); 1], String) {
+    let metrics = {
+        let storage = state.storage.lock().await;
+        Metrics {
+            packets_ingested: storage.packets_ingested(),
+            active_flights: storage.active_flights(),
+            data_bytes: storage.data_size(),
+            live_bytes: storage.live_size(),
+            fragmentation_ratio: storage.fragmentation_ratio(),
+            stream_subscribers: state.broadcast_tx.receiver_count(),
+            last_packet_age_ms: state.link.last_packet_age_ms(now_epoch_ms()),
+        }
+    };
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_is_valid_exposition() {
+        let metrics = Metrics {
+            packets_ingested: 1200,
+            active_flights: 1,
+            data_bytes: 4096,
+            live_bytes: 3072,
+            fragmentation_ratio: 0.25,
+            stream_subscribers: 2,
+            last_packet_age_ms: Some(1500),
+        };
+        let text = metrics.render();
+
+        let mut typed = Vec::new();
+        let mut samples = Vec::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = rest.split_once(' ').unwrap();
+                assert!(kind == "counter" || kind == "gauge");
+                typed.push(name.to_string());
+            } else if !line.starts_with("# HELP ") {
+                let (name, value) = line.split_once(' ').unwrap();
+                assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+                assert_eq!(typed.last().map(String::as_str), Some(name), "{} has no TYPE line", name);
+                samples.push((name.to_string(), value.parse::<f64>().unwrap()));
+            }
+        }
+
+        assert_eq!(samples.len(), 7);
+        assert!(samples.contains(&("telemetry_packets_ingested_total".to_string(), 1200.0)));
+        assert!(samples.contains(&("telemetry_last_packet_age_seconds".to_string(), 1.5)));
+
+        let idle = Metrics::default().render();
+        assert!(!idle.contains("telemetry_last_packet_age_seconds"));
+    }
+}
@@ -1,6 +1,13 @@
-use kiwi_store::{Store, Key, Value, BorrowedEntry, StoreError};
-use crate::types::{TelemetryPacket, FlightMetadata};
+use kiwi_store::{Store, Key, Value, BorrowedEntry, Checksum, CompactionReport, StoreError, TypeHistogram, borrowed_to_owned, owned_to_value};
+use serde::de::DeserializeOwned;
+use crate::types::{TelemetryPacket, FlightMetadata, IntegrityReport, Alert, AlertKind, FlightEvent, Geofence, FlightIdFormat, FlightDetectionConfig};
+use crate::wall_clock::now_epoch_ms;
 use anyhow::Result;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Instant;
+use tokio::sync::broadcast;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum FlightState {
@@ -9,15 +16,130 @@ enum FlightState {
     Landing,
 }
 
-pub struct TelemetryStorage {
-    store: Store,
+/// How a source's stream went away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamEnd {
+    /// The source closed the connection, no more packets are coming
+    Closed,
+    /// Read error or dropped connection, the source may be back soon
+    Failed,
+}
+
+/// Why two flights can't be merged
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeRejected {
+    UnknownFlight(String),
+    SameFlight,
+    DifferentSources,
+    InProgress(String),
+    Overlapping,
+}
+
+impl std::fmt::Display for MergeRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeRejected::UnknownFlight(id) => write!(f, "flight {} not found", id),
+            MergeRejected::SameFlight => write!(f, "can't merge a flight into itself"),
+            MergeRejected::DifferentSources => write!(f, "flights come from different sources"),
+            MergeRejected::InProgress(id) => write!(f, "flight {} is still in progress", id),
+            MergeRejected::Overlapping => write!(f, "flights overlap in time"),
+        }
+    }
+}
+
+impl std::error::Error for MergeRejected {}
+
+/// Flight detection state for a single telemetry source
+struct FlightTracker {
+    source_id: Option<String>,
     current_flight_id: Option<String>,
     flight_state: FlightState,
+    takeoff_check_start: Option<u64>,
+    // Airborne packets seen while the takeoff is being confirmed
+    pending_takeoff: Vec<TelemetryPacket>,
     landing_check_start: Option<u64>,
     last_position: Option<(f64, f64)>,
     last_packet_time: Option<u64>,
+    // When the server last heard from the source, comparable across sources
+    // unlike their own clocks
+    last_received: Option<Instant>,
     total_distance_km: f64,
     last_phase: Option<String>,
+    low_battery_latched: bool,
+    outside_geofence: bool,
+    last_baro_sample: Option<(u64, f32)>,
+    derived_vertical_speed: Option<f32>,
+    gap_count: u32,
+    total_gap_ms: u64,
+    // Consecutive packets with GPS and baro altitude too far apart
+    altitude_disagreement_run: u32,
+    sensor_warning: bool,
+    // Latest packets of the current flight, for the time-to-empty estimate
+    recent_packets: VecDeque<TelemetryPacket>,
+    // Key order of the last packet in the current flight's integrity chain
+    chain_end: Option<(u64, Option<u32>)>,
+}
+
+impl FlightTracker {
+    fn new(source_id: Option<String>) -> Self {
+        Self {
+            source_id,
+            current_flight_id: None,
+            flight_state: FlightState::OnGround,
+            takeoff_check_start: None,
+            pending_takeoff: Vec::new(),
+            landing_check_start: None,
+            last_position: None,
+            last_packet_time: None,
+            last_received: None,
+            total_distance_km: 0.0,
+            last_phase: None,
+            low_battery_latched: false,
+            outside_geofence: false,
+            last_baro_sample: None,
+            derived_vertical_speed: None,
+            gap_count: 0,
+            total_gap_ms: 0,
+            altitude_disagreement_run: 0,
+            sensor_warning: false,
+            recent_packets: VecDeque::new(),
+            chain_end: None,
+        }
+    }
+}
+
+/// Flight tracking and storage for the telemetry stream.
+///
+/// All timing (takeoff and landing confirmation, link gaps, timeouts, clock
+/// resets) is measured on the packets' own `timestamp`, never the wall clock,
+/// so feeding the same packets always gives the same flights. The wall clock
+/// is only read to record each flight's `start_epoch_ms`, and the receive
+/// time only to tell which source's flight is the current one.
+pub struct TelemetryStorage {
+    store: Store,
+    trackers: HashMap<Option<String>, FlightTracker>,
+    alert_tx: Option<broadcast::Sender<Alert>>,
+    event_tx: Option<broadcast::Sender<FlightEvent>>,
+    low_battery_threshold: f32,
+    battery_capacity_mah: f32,
+    geofence: Option<Geofence>,
+    derive_vertical_speed: bool,
+    // Completed flights' telemetry moves here once memory holds too many
+    // packets. None for in-memory storage, which never touches the disk.
+    archive_dir: Option<PathBuf>,
+    max_in_memory_packets: Option<usize>,
+    // Oldest completed flights are deleted beyond this many, 0 keeps all
+    max_flights: usize,
+    packets_in_memory: usize,
+    eviction_threshold: usize,
+    packets_ingested: u64,
+    // Telem keys per flight in timestamp order, for paging without re-sorting
+    page_keys: HashMap<String, Vec<Key>>,
+    flight_id_format: FlightIdFormat,
+    detection: FlightDetectionConfig,
+    skip_invalid_gps: bool,
+    // Store packets in their wire format instead of JSON
+    binary_packets: bool,
 }
 
 impl TelemetryStorage {
@@ -25,82 +147,396 @@ impl TelemetryStorage {
     const ALTITUDE_THRESHOLD: f32 = 5.0;  // Only detect flights when airborne
     const SPEED_THRESHOLD: f32 = 2.0;     // Minimum airspeed
     const GPS_STABLE_THRESHOLD: f64 = 0.0001;
-    const LANDING_CONFIRM_MS: u64 = 5000;
     const TIMEOUT_MS: u64 = 60000;
+    const GAP_MS: u64 = 5000;  // Silence longer than this is a link gap
+    const CLOCK_RESET_MS: u64 = 5000;  // Further back than reordering explains
+    const DEFAULT_LOW_BATTERY_VOLTAGE: f32 = 14.0; // ~3.5V/cell on the 16.8V pack
+    const DEFAULT_BATTERY_CAPACITY_MAH: f32 = 5000.0; // The simulator's default pack
+    const ENDURANCE_WINDOW: usize = 25;  // Power draw to average, ~12s at the sim's 2 Hz
+    const COMPACTION_STEP_BYTES: usize = 64 * 1024; // Moved per packet while compacting
+    const IMPORT_SOURCE: &'static str = "import";
+    const MAX_PHASE_TRANSITIONS: usize = 256;
+    const ALTITUDE_DISAGREEMENT_M: f32 = 30.0;  // GPS vs baro, beyond normal drift
+    const ALTITUDE_DISAGREEMENT_PACKETS: u32 = 10;  // ~2s at 5 Hz, ignores glitches
     
     pub fn new(path: &str) -> Result<Self> {
-        Ok(Self {
-            store: Store::with_path(path)?,
-            current_flight_id: None,
-            flight_state: FlightState::OnGround,
-            landing_check_start: None,
-            last_position: None,
-            last_packet_time: None,
-            total_distance_km: 0.0,
-            last_phase: None,
-        })
+        let store = Store::with_path(path)?;
+        let mut corrupt = 0;
+        for (key, err) in store.errors() {
+            eprintln!("[Storage] Unreadable value for {:?}: {}", key, err);
+            corrupt += 1;
+        }
+        if corrupt > 0 {
+            eprintln!("[Storage] {} corrupt entries in {}", corrupt, path);
+        }
+        
+        Ok(Self::from_store(store, Some(PathBuf::from(format!("{}_archive", path)))))
+    }
+    
+    /// Storage that lives only in memory: nothing is loaded, archived or saved
+    #[allow(dead_code)] // For tests, the server always persists
+    pub fn new_in_memory() -> Self {
+        Self::from_store(Store::new(), None)
+    }
+    
+    fn from_store(store: Store, archive_dir: Option<PathBuf>) -> Self {
+        let packets_in_memory = store.keys()
+            .filter(|k| matches!(k, Key::String(s) if s.starts_with("telem:")))
+            .count();
+        
+        Self {
+            store,
+            trackers: HashMap::new(),
+            alert_tx: None,
+            event_tx: None,
+            low_battery_threshold: Self::DEFAULT_LOW_BATTERY_VOLTAGE,
+            battery_capacity_mah: Self::DEFAULT_BATTERY_CAPACITY_MAH,
+            geofence: None,
+            derive_vertical_speed: false,
+            archive_dir,
+            max_in_memory_packets: None,
+            max_flights: 0,
+            packets_in_memory,
+            eviction_threshold: 0,
+            packets_ingested: 0,
+            page_keys: HashMap::new(),
+            flight_id_format: FlightIdFormat::default(),
+            detection: FlightDetectionConfig::default(),
+            skip_invalid_gps: false,
+            binary_packets: false,
+        }
+    }
+    
+    /// Publish flight alerts on this channel
+    pub fn with_alerts(mut self, alert_tx: broadcast::Sender<Alert>) -> Self {
+        self.alert_tx = Some(alert_tx);
+        self
+    }
+    
+    /// Publish flight starts and ends on this channel
+    pub fn with_events(mut self, event_tx: broadcast::Sender<FlightEvent>) -> Self {
+        self.event_tx = Some(event_tx);
+        self
+    }
+    
+    pub fn with_low_battery_threshold(mut self, volts: f32) -> Self {
+        self.low_battery_threshold = volts;
+        self
+    }
+    
+    /// Pack capacity the time-to-empty estimate counts down from
+    pub fn with_battery_capacity(mut self, mah: f32) -> Self {
+        self.battery_capacity_mah = mah;
+        self
+    }
+    
+    /// Alert when a flight leaves (and re-enters) this area
+    pub fn with_geofence(mut self, geofence: Geofence) -> Self {
+        self.geofence = Some(geofence);
+        self
+    }
+    
+    /// Replace the packet's vertical speed with one computed from baro altitude
+    pub fn with_derived_vertical_speed(mut self) -> Self {
+        self.derive_vertical_speed = true;
+        self
+    }
+    
+    /// Store new packets as `Value::Bytes` in the wire format rather than as
+    /// JSON. Metadata stays JSON, and packets already stored either way read back.
+    pub fn with_binary_packets(mut self) -> Self {
+        self.binary_packets = true;
+        self
+    }
+    
+    /// Archive completed flights to disk whenever more than `max` packets
+    /// are held in memory. Archived telemetry is reloaded on request.
+    pub fn with_max_in_memory_packets(mut self, max: usize) -> Self {
+        self.max_in_memory_packets = Some(max);
+        self.eviction_threshold = max;
+        self
+    }
+    
+    /// Keep at most `max` flights, deleting the oldest completed one (by
+    /// start time) when a new flight starts. 0 keeps every flight.
+    pub fn with_max_flights(mut self, max: usize) -> Self {
+        self.max_flights = max;
+        self
+    }
+    
+    /// Name new flights with this format. Counters keep counting, so a
+    /// changed prefix continues from the old numbers.
+    pub fn with_flight_id_format(mut self, format: FlightIdFormat) -> Self {
+        self.flight_id_format = format;
+        self
     }
     
+    /// Don't store packets without a usable GPS position. They still count
+    /// for flight detection, so a dropout doesn't end the flight.
+    pub fn with_invalid_gps_skipped(mut self) -> Self {
+        self.skip_invalid_gps = true;
+        self
+    }
+    
+    pub fn with_flight_detection(mut self, detection: FlightDetectionConfig) -> Self {
+        self.detection = detection;
+        self
+    }
+    
+    #[allow(dead_code)] // The server always knows the source, tests often don't
     pub fn save_packet(&mut self, packet: &TelemetryPacket) -> Result<()> {
+        self.save_packet_from(None, packet)
+    }
+    
+    /// Store a packet received from a specific source. Each source runs its
+    /// own flight detection, and its flights are namespaced by the source id.
+    pub fn save_packet_from(&mut self, source_id: Option<&str>, packet: &TelemetryPacket) -> Result<()> {
+        self.save_packet_for(source_id.map(str::to_string), packet)
+    }
+    
+    /// Store a burst of packets from one source under a single lock.
+    /// Same result as saving them one by one: a packet that fails doesn't
+    /// stop the rest. Returns the errors of the packets that failed.
+    pub fn ingest_batch(&mut self, source_id: Option<&str>, packets: &[TelemetryPacket]) -> Vec<anyhow::Error> {
+        packets.iter()
+            .filter_map(|packet| self.save_packet_from(source_id, packet).err())
+            .collect()
+    }
+    
+    /// A source's stream ended. A clean close ends its flight right away as
+    /// not ended normally; after a failure the packet-gap timeout decides, so
+    /// a quick reconnect continues the flight.
+    pub fn on_stream_lost(&mut self, source_id: Option<&str>, end: StreamEnd) -> Result<()> {
+        if end == StreamEnd::Failed {
+            return Ok(());
+        }
+        let source_key = source_id.map(str::to_string);
+        if let Some(mut tracker) = self.trackers.remove(&source_key) {
+            let result = self.end_current_flight_catastrophic(&mut tracker);
+            self.trackers.insert(source_key, tracker);
+            result?;
+        }
+        Ok(())
+    }
+    
+    /// Replay a logged flight as the `import` source so flight detection runs
+    /// as if live. A flight still open at the end of the log is closed as landed.
+    pub fn import_packets(&mut self, packets: &[TelemetryPacket]) -> Result<()> {
+        let source_key = Some(Self::IMPORT_SOURCE.to_string());
+        self.trackers.remove(&source_key);
+        for packet in packets {
+            self.save_packet_for(source_key.clone(), packet)?;
+        }
+        
+        if let (Some(mut tracker), Some(last)) = (self.trackers.remove(&source_key), packets.last()) {
+            self.end_current_flight(&mut tracker, last)?;
+        }
+        Ok(())
+    }
+    
+    fn save_packet_for(&mut self, source_key: Option<String>, packet: &TelemetryPacket) -> Result<()> {
+        let mut tracker = self.trackers.remove(&source_key)
+            .unwrap_or_else(|| FlightTracker::new(source_key.clone()));
+        
+        self.packets_ingested += 1;
+        let mut packet = *packet;
+        if self.derive_vertical_speed {
+            packet.vertical_speed = Self::derive_vertical_speed(&mut tracker, &packet);
+        }
+        
+        let result = self.process_packet(&mut tracker, &packet);
+        tracker.last_received = Some(Instant::now());
+        self.trackers.insert(source_key, tracker);
+        
+        // Finish a pending compaction a slice at a time between packets
+        if self.store.compaction_in_progress() {
+            self.store.compact_incremental(Self::COMPACTION_STEP_BYTES)?;
+        }
+        
+        if let Some(max) = self.max_in_memory_packets {
+            if self.packets_in_memory > self.eviction_threshold {
+                // The packet is stored either way, a failed eviction is only logged
+                if let Err(e) = self.evict_completed_flights() {
+                    eprintln!("[Storage] Warning: archiving completed flights failed: {}", e);
+                }
+                // Flights still in progress can't be evicted, and neither can
+                // anything after a failure: don't retry on every packet
+                self.eviction_threshold = self.packets_in_memory + max;
+            }
+        }
+        result
+    }
+    
+    /// Climb rate from the previous baro sample of this source. Duplicate or
+    /// out-of-order timestamps keep the last rate, the first sample uses the packet's.
+    fn derive_vertical_speed(tracker: &mut FlightTracker, packet: &TelemetryPacket) -> f32 {
+        match tracker.last_baro_sample {
+            Some((last_time, last_alt)) if packet.timestamp > last_time => {
+                let dt = (packet.timestamp - last_time) as f32 / 1000.0;
+                tracker.derived_vertical_speed = Some((packet.altitude_baro - last_alt) / dt);
+                tracker.last_baro_sample = Some((packet.timestamp, packet.altitude_baro));
+            }
+            Some(_) => {}
+            None => tracker.last_baro_sample = Some((packet.timestamp, packet.altitude_baro)),
+        }
+        
+        tracker.derived_vertical_speed.unwrap_or(packet.vertical_speed)
+    }
+    
+    fn process_packet(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> Result<()> {
+        // A source that restarted (e.g. the sim after a reconnect) counts from zero again
+        if let Some(last_time) = tracker.last_packet_time {
+            if packet.timestamp.saturating_add(Self::CLOCK_RESET_MS) < last_time {
+                println!("⚠️  Clock went back {:.1}s - source restarted, ending flight",
+                         (last_time - packet.timestamp) as f64 / 1000.0);
+                self.end_current_flight_catastrophic(tracker)?;
+                tracker.last_packet_time = None;
+                tracker.last_position = None;
+                tracker.takeoff_check_start = None;
+                tracker.pending_takeoff.clear();
+                tracker.last_baro_sample = None;
+            }
+        }
+        
         // Check for timeout (catastrophic stop)
-        if let Some(last_time) = self.last_packet_time {
+        if let Some(last_time) = tracker.last_packet_time {
             let gap = packet.timestamp.saturating_sub(last_time);
-            if gap > Self::TIMEOUT_MS && self.current_flight_id.is_some() {
+            if gap > Self::TIMEOUT_MS && tracker.current_flight_id.is_some() {
                 println!("⚠️  Stream timeout detected ({:.1}s gap) - ending flight", 
                          gap as f64 / 1000.0);
-                self.end_current_flight_catastrophic()?;
+                self.end_current_flight_catastrophic(tracker)?;
+            } else if gap > Self::GAP_MS && tracker.current_flight_id.is_some() {
+                tracker.gap_count += 1;
+                tracker.total_gap_ms += gap;
+                // We didn't see the path flown during the dropout
+                tracker.last_position = None;
             }
         }
         
-        let new_state = self.detect_flight_state(packet);
+        let new_state = Self::detect_flight_state(&self.detection, tracker, packet);
+        let skip_position = self.skip_invalid_gps && packet.check_gps().is_err();
         
         // Calculate distance if in flight
-        if self.current_flight_id.is_some() {
-            if let Some((last_lat, last_lon)) = self.last_position {
+        if tracker.current_flight_id.is_some() && !skip_position {
+            if let Some((last_lat, last_lon)) = tracker.last_position {
                 let distance = Self::haversine_distance(
                     last_lat, last_lon,
                     packet.latitude, packet.longitude
                 );
-                self.total_distance_km += distance;
+                tracker.total_distance_km += distance;
             }
         }
         
         // State transitions
-        match (self.flight_state, new_state) {
+        match (tracker.flight_state, new_state) {
             (FlightState::OnGround, FlightState::InFlight) => {
-                self.start_new_flight(packet)?;
+                // The flight began with the first of the packets that confirmed it
+                let pending = std::mem::take(&mut tracker.pending_takeoff);
+                self.start_new_flight(tracker, pending.first().unwrap_or(packet))?;
+                tracker.last_position = None;
+                for early in &pending {
+                    self.record_pending_packet(tracker, early)?;
+                }
+                if let (Some((last_lat, last_lon)), false) = (tracker.last_position, skip_position) {
+                    tracker.total_distance_km += Self::haversine_distance(
+                        last_lat, last_lon,
+                        packet.latitude, packet.longitude
+                    );
+                }
+            }
+            (FlightState::OnGround, FlightState::OnGround) => {
+                if tracker.takeoff_check_start.is_some() {
+                    tracker.pending_takeoff.push(*packet);
+                } else {
+                    tracker.pending_takeoff.clear();
+                }
             }
             (FlightState::Landing, FlightState::OnGround) => {
-                self.end_current_flight(packet, true)?;
+                self.end_current_flight(tracker, packet)?;
             }
             _ => {}
         }
         
-        self.flight_state = new_state;
+        tracker.flight_state = new_state;
         
-        // Store packet if in flight
-        if let Some(flight_id) = &self.current_flight_id {
-            let key = format!("telem:{}:{}", flight_id, packet.timestamp);
-            let value = serde_json::to_string(packet)?;
-            self.store.put(Key::String(key), Value::String(value));
+        // Store packet if in flight and its position is usable
+        if !skip_position {
+            self.store_flight_packet(tracker, packet)?;
+            tracker.last_position = Some((packet.latitude, packet.longitude));
+        }
+        tracker.last_packet_time = Some(packet.timestamp);
+        Ok(())
+    }
+    
+    /// Store a packet held back while its flight's takeoff was confirmed
+    fn record_pending_packet(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> Result<()> {
+        if self.skip_invalid_gps && packet.check_gps().is_err() {
+            return Ok(());
+        }
+        if let Some((last_lat, last_lon)) = tracker.last_position {
+            tracker.total_distance_km += Self::haversine_distance(
+                last_lat, last_lon,
+                packet.latitude, packet.longitude
+            );
+        }
+        self.store_flight_packet(tracker, packet)?;
+        tracker.last_position = Some((packet.latitude, packet.longitude));
+        Ok(())
+    }
+    
+    fn store_flight_packet(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> Result<()> {
+        if let Some(flight_id) = &tracker.current_flight_id {
+            let mut key = Key::String(format!("telem:{}:{}", flight_id, packet.timestamp));
+            let mut order = (packet.timestamp, None);
+            if self.store.contains_key(&key) {
+                // Same millisecond as an earlier packet, keep both
+                key = Key::String(format!("telem:{}:{}-{}", flight_id, packet.timestamp, packet.packet_sequence));
+                order.1 = Some(packet.packet_sequence);
+            }
+            // Only a new packet after the last chained one extends the chain,
+            // a late or repeated one means chaining the stored packets again
+            let extends_chain = !self.store.contains_key(&key)
+                && tracker.chain_end.is_some_and(|end| order > end);
+            tracker.chain_end = Some(tracker.chain_end.map_or(order, |end| end.max(order)));
+            let value = if self.binary_packets {
+                Value::Bytes(packet.to_bytes())
+            } else {
+                Value::String(serde_json::to_string(packet)?)
+            };
+            self.store.put(key, value);
+            self.packets_in_memory += 1;
+            self.page_keys.remove(flight_id);
+            
+            if tracker.recent_packets.len() == Self::ENDURANCE_WINDOW {
+                tracker.recent_packets.pop_front();
+            }
+            tracker.recent_packets.push_back(*packet);
             
-            self.update_flight_metadata(packet)?;
+            self.check_altitude_sensors(tracker, packet);
+            self.update_flight_metadata(tracker, packet, extends_chain)?;
+            self.check_low_battery(tracker, packet);
+            self.check_geofence(tracker, packet);
         }
-        
-        self.last_position = Some((packet.latitude, packet.longitude));
-        self.last_packet_time = Some(packet.timestamp);
         Ok(())
     }
     
-    fn detect_flight_state(&mut self, packet: &TelemetryPacket) -> FlightState {
+    fn detect_flight_state(detection: &FlightDetectionConfig, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> FlightState {
         let is_on_ground = 
             packet.altitude_gps <= Self::ALTITUDE_THRESHOLD &&
             packet.ground_speed <= Self::SPEED_THRESHOLD &&
-            self.is_gps_stable(packet);
+            Self::is_gps_stable(tracker, packet);
         
-        match self.flight_state {
+        match tracker.flight_state {
             FlightState::OnGround => {
-                if !is_on_ground {
+                if is_on_ground {
+                    tracker.takeoff_check_start = None;
+                    return FlightState::OnGround;
+                }
+                let takeoff_start = *tracker.takeoff_check_start.get_or_insert(packet.timestamp);
+                let airborne_duration = packet.timestamp.saturating_sub(takeoff_start);
+                
+                if airborne_duration >= detection.takeoff_confirm_ms {
+                    tracker.takeoff_check_start = None;
                     FlightState::InFlight
                 } else {
                     FlightState::OnGround
@@ -109,7 +545,7 @@ impl TelemetryStorage {
             
             FlightState::InFlight => {
                 if is_on_ground {
-                    self.landing_check_start = Some(packet.timestamp);
+                    tracker.landing_check_start = Some(packet.timestamp);
                     FlightState::Landing
                 } else {
                     FlightState::InFlight
@@ -118,14 +554,14 @@ impl TelemetryStorage {
             
             FlightState::Landing => {
                 if !is_on_ground {
-                    self.landing_check_start = None;
+                    tracker.landing_check_start = None;
                     FlightState::InFlight
                 } else {
                     let stable_duration = packet.timestamp.saturating_sub(
-                        self.landing_check_start.unwrap_or(packet.timestamp)
+                        tracker.landing_check_start.unwrap_or(packet.timestamp)
                     );
                     
-                    if stable_duration >= Self::LANDING_CONFIRM_MS {
+                    if stable_duration >= detection.landing_confirm_ms {
                         FlightState::OnGround
                     } else {
                         FlightState::Landing
@@ -135,8 +571,99 @@ impl TelemetryStorage {
         }
     }
     
-    fn is_gps_stable(&self, packet: &TelemetryPacket) -> bool {
-        if let Some((last_lat, last_lon)) = self.last_position {
+    /// Alert once per crossing below the threshold, re-armed when voltage recovers
+    fn check_low_battery(&self, tracker: &mut FlightTracker, packet: &TelemetryPacket) {
+        if packet.battery_voltage >= self.low_battery_threshold {
+            tracker.low_battery_latched = false;
+            return;
+        }
+        if tracker.low_battery_latched {
+            return;
+        }
+        tracker.low_battery_latched = true;
+        
+        if let Some(flight_id) = &tracker.current_flight_id {
+            self.emit_alert(Alert {
+                flight_id: flight_id.clone(),
+                kind: AlertKind::LowBattery,
+                value: packet.battery_voltage as f64,
+                timestamp: packet.timestamp,
+            });
+        }
+    }
+    
+    /// One alert on leaving the fence and one on coming back, not one per packet
+    fn check_geofence(&self, tracker: &mut FlightTracker, packet: &TelemetryPacket) {
+        let Some(fence) = &self.geofence else {
+            return;
+        };
+        let Some(flight_id) = &tracker.current_flight_id else {
+            return;
+        };
+        
+        let distance = Self::haversine_distance(
+            fence.center_lat, fence.center_lon,
+            packet.latitude, packet.longitude
+        );
+        let outside = distance > fence.radius_km;
+        if outside == tracker.outside_geofence {
+            return;
+        }
+        tracker.outside_geofence = outside;
+        
+        let (kind, value) = if outside {
+            (AlertKind::GeofenceBreach, distance - fence.radius_km)
+        } else {
+            (AlertKind::GeofenceReturn, distance)
+        };
+        self.emit_alert(Alert {
+            flight_id: flight_id.clone(),
+            kind,
+            value,
+            timestamp: packet.timestamp,
+        });
+    }
+    
+    /// Latch a sensor warning for the rest of the flight once GPS and baro
+    /// altitude stay far apart, alerting when it latches
+    fn check_altitude_sensors(&self, tracker: &mut FlightTracker, packet: &TelemetryPacket) {
+        let difference = (packet.altitude_gps - packet.altitude_baro).abs();
+        if difference <= Self::ALTITUDE_DISAGREEMENT_M {
+            tracker.altitude_disagreement_run = 0;
+            return;
+        }
+        tracker.altitude_disagreement_run += 1;
+        if tracker.sensor_warning || tracker.altitude_disagreement_run < Self::ALTITUDE_DISAGREEMENT_PACKETS {
+            return;
+        }
+        tracker.sensor_warning = true;
+        
+        if let Some(flight_id) = &tracker.current_flight_id {
+            self.emit_alert(Alert {
+                flight_id: flight_id.clone(),
+                kind: AlertKind::SensorDisagreement,
+                value: difference as f64,
+                timestamp: packet.timestamp,
+            });
+        }
+    }
+    
+    fn emit_alert(&self, alert: Alert) {
+        println!("[Alert] {} {:?} ({:.2})", alert.flight_id, alert.kind, alert.value);
+        if let Some(tx) = &self.alert_tx {
+            // No subscribers is fine
+            let _ = tx.send(alert);
+        }
+    }
+    
+    fn emit_event(&self, event: FlightEvent) {
+        if let Some(tx) = &self.event_tx {
+            let _ = tx.send(event);
+        }
+    }
+    
+    fn is_gps_stable(tracker: &FlightTracker, packet: &TelemetryPacket) -> bool {
+        if let Some((last_lat, last_lon)) = tracker.last_position {
             let lat_diff = (packet.latitude - last_lat).abs();
             let lon_diff = (packet.longitude - last_lon).abs();
             
@@ -164,14 +691,21 @@ impl TelemetryStorage {
         R * c
     }
     
-    fn start_new_flight(&mut self, packet: &TelemetryPacket) -> Result<()> {
-        let flight_id = format!("flight_{:03}", self.get_next_flight_number());
+    fn start_new_flight(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> Result<()> {
+        self.make_room_for_flight()?;
+        let number = self.take_next_flight_number(tracker.source_id.as_deref());
+        let flight_name = self.flight_id_format.format(number);
+        let flight_id = match &tracker.source_id {
+            Some(source) => format!("{}:{}", source, flight_name),
+            None => flight_name,
+        };
         
         println!("[Flight] {} started at altitude {:.1}m", 
                  flight_id, packet.altitude_gps);
         
         let metadata = FlightMetadata {
             flight_id: flight_id.clone(),
+            source_id: tracker.source_id.clone(),
             start_time: packet.timestamp,
             end_time: packet.timestamp,
             duration_secs: 0,
@@ -185,108 +719,181 @@ impl TelemetryStorage {
             min_battery: packet.battery_voltage,
             ended_normally: true,
             current_status: packet.get_flight_phase().to_string(),
+            gap_count: 0,
+            total_gap_ms: 0,
+            sensor_warning: false,
+            phase_timeline: Vec::new(),
+            phase_entry_voltages: Vec::new(),
+            label: None,
+            energy_wh: 0.0,
+            phase_durations: HashMap::new(),
+            integrity_hash: None,
+            // An imported log's timestamps have nothing to do with now
+            start_epoch_ms: (tracker.source_id.as_deref() != Some(Self::IMPORT_SOURCE))
+                .then(now_epoch_ms),
         };
         
         let key = format!("flight:{}", flight_id);
         let value = serde_json::to_string(&metadata)?;
         self.store.put(Key::String(key), Value::String(value));
         
-        self.current_flight_id = Some(flight_id);
-        self.total_distance_km = 0.0;
+        self.emit_event(FlightEvent::Started { id: flight_id.clone() });
+        tracker.current_flight_id = Some(flight_id);
+        tracker.total_distance_km = 0.0;
+        tracker.gap_count = 0;
+        tracker.total_gap_ms = 0;
+        tracker.low_battery_latched = false;
+        tracker.outside_geofence = false;
+        tracker.altitude_disagreement_run = 0;
+        tracker.sensor_warning = false;
+        tracker.recent_packets.clear();
+        tracker.chain_end = None;
         Ok(())
     }
     
-    fn update_flight_metadata(&mut self, packet: &TelemetryPacket) -> Result<()> {
-        if let Some(flight_id) = &self.current_flight_id {
+    fn update_flight_metadata(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket, extends_chain: bool) -> Result<()> {
+        if let Some(flight_id) = &tracker.current_flight_id {
             let key = format!("flight:{}", flight_id);
-            if let Ok(BorrowedEntry::Text(json)) = self.store.get(&Key::String(key.clone())) {
-                if let Ok(mut metadata) = serde_json::from_str::<FlightMetadata>(json) {
-                    metadata.end_time = packet.timestamp;
-                    metadata.duration_secs = (packet.timestamp - metadata.start_time) / 1000;
-                    metadata.packet_count += 1;
-                    metadata.distance_km = self.total_distance_km;
-                    metadata.last_lat = packet.latitude;
-                    metadata.last_lon = packet.longitude;
-                    metadata.max_altitude = metadata.max_altitude.max(packet.altitude_gps);
-                    metadata.min_battery = metadata.min_battery.min(packet.battery_voltage);
-                    
-                    let current_phase = packet.get_flight_phase().to_string();
-                    metadata.current_status = current_phase.clone();
-                    
-                    // Log phase transitions
-                    if self.last_phase.as_ref() != Some(&current_phase) {
-                        println!("[Flight] {} -> {}", flight_id, current_phase);
-                        self.last_phase = Some(current_phase);
+            if let Some(mut metadata) = self.get_flight(flight_id) {
+                // A packet arriving out of order doesn't move the end back
+                metadata.end_time = metadata.end_time.max(packet.timestamp);
+                metadata.duration_secs = metadata.end_time.saturating_sub(metadata.start_time) / 1000;
+                metadata.packet_count += 1;
+                metadata.distance_km = tracker.total_distance_km;
+                metadata.gap_count = tracker.gap_count;
+                metadata.total_gap_ms = tracker.total_gap_ms;
+                metadata.sensor_warning = tracker.sensor_warning;
+                metadata.last_lat = packet.latitude;
+                metadata.last_lon = packet.longitude;
+                metadata.max_altitude = metadata.max_altitude.max(packet.altitude_gps);
+                metadata.min_battery = metadata.min_battery.min(packet.battery_voltage);
+                metadata.integrity_hash = Some(if extends_chain {
+                    let prev = metadata.integrity_hash.as_deref().and_then(parse_hash).unwrap_or(0);
+                    format_hash(chain_packet(prev, packet))
+                } else {
+                    integrity_hash(&self.get_flight_data(flight_id))
+                });
+                
+                let current_phase = packet.get_flight_phase().to_string();
+                metadata.current_status = current_phase.clone();
+                
+                // Log and record phase transitions
+                if tracker.last_phase.as_ref() != Some(&current_phase) {
+                    println!("[Flight] {} -> {}", flight_id, current_phase);
+                    Self::record_phase(&mut metadata, packet.timestamp, &current_phase);
+                    if metadata.phase_entry_voltages.len() < Self::MAX_PHASE_TRANSITIONS {
+                        metadata.phase_entry_voltages.push((current_phase.clone(), packet.battery_voltage));
                     }
-                    
-                    let value = serde_json::to_string(&metadata)?;
-                    self.store.put(Key::String(key), Value::String(value));
+                    tracker.last_phase = Some(current_phase);
                 }
+                
+                let value = serde_json::to_string(&metadata)?;
+                self.store.put(Key::String(key), Value::String(value));
             }
         }
         Ok(())
     }
     
-    fn end_current_flight(&mut self, packet: &TelemetryPacket, normal: bool) -> Result<()> {
-        if let Some(flight_id) = &self.current_flight_id {
-            println!("[Flight] {} ended {}", flight_id, 
-                     if normal { "normally" } else { "abnormally" });
+    /// Append to the phase timeline, which stops growing at its cap so a
+    /// flight bouncing between phases can't bloat its metadata. The last
+    /// slot is kept for "Landed".
+    fn record_phase(metadata: &mut FlightMetadata, timestamp: u64, phase: &str) {
+        let cap = if phase == "Landed" { Self::MAX_PHASE_TRANSITIONS } else { Self::MAX_PHASE_TRANSITIONS - 1 };
+        if metadata.phase_timeline.len() < cap {
+            metadata.phase_timeline.push((timestamp, phase.to_string()));
+        }
+    }
+    
+    /// End the flight as landed, `packet` being the one that confirmed it
+    fn end_current_flight(&mut self, tracker: &mut FlightTracker, packet: &TelemetryPacket) -> Result<()> {
+        if let Some(flight_id) = &tracker.current_flight_id {
+            println!("[Flight] {} ended normally", flight_id);
             
-            // Update metadata one last time and set status to "Landed" if normal
-            if normal {
-                let key = format!("flight:{}", flight_id);
-                if let Ok(BorrowedEntry::Text(json)) = self.store.get(&Key::String(key.clone())) {
-                    if let Ok(mut metadata) = serde_json::from_str::<FlightMetadata>(json) {
-                        metadata.current_status = "Landed".to_string();
-                        let value = serde_json::to_string(&metadata)?;
-                        self.store.put(Key::String(key), Value::String(value));
-                    }
-                }
-            } else {
-                self.update_flight_metadata(packet)?;
+            let flight_id = flight_id.clone();
+            let key = format!("flight:{}", flight_id);
+            if let Some(mut metadata) = self.get_flight(&flight_id) {
+                metadata.current_status = "Landed".to_string();
+                Self::record_phase(&mut metadata, packet.timestamp, "Landed");
+                let packets = self.get_flight_data(&flight_id);
+                check_live_integrity(&metadata, &packets);
+                metadata.energy_wh = energy_wh(&packets);
+                metadata.phase_durations = phase_durations(&metadata);
+                let value = serde_json::to_string(&metadata)?;
+                self.store.put(Key::String(key), Value::String(value));
             }
+            self.emit_event(FlightEvent::Ended { id: flight_id, normally: true });
             
-            self.current_flight_id = None;
-            self.landing_check_start = None;
-            self.total_distance_km = 0.0;
-            self.last_phase = None;
+            tracker.current_flight_id = None;
+            tracker.landing_check_start = None;
+            tracker.total_distance_km = 0.0;
+            tracker.last_phase = None;
+            tracker.chain_end = None;
         }
         Ok(())
     }
     
-    fn end_current_flight_catastrophic(&mut self) -> Result<()> {
-        if let Some(flight_id) = &self.current_flight_id {
+    fn end_current_flight_catastrophic(&mut self, tracker: &mut FlightTracker) -> Result<()> {
+        if let Some(flight_id) = &tracker.current_flight_id {
             println!("[Flight] {} ended catastrophically (stream lost)", flight_id);
             
             let key = format!("flight:{}", flight_id);
-            if let Ok(BorrowedEntry::Text(json)) = self.store.get(&Key::String(key.clone())) {
-                if let Ok(mut metadata) = serde_json::from_str::<FlightMetadata>(json) {
-                    metadata.ended_normally = false;
-                    metadata.distance_km = self.total_distance_km;
-                    let value = serde_json::to_string(&metadata)?;
-                    self.store.put(Key::String(key), Value::String(value));
-                }
+            if let Some(mut metadata) = self.get_flight(flight_id) {
+                metadata.ended_normally = false;
+                metadata.distance_km = tracker.total_distance_km;
+                let packets = self.get_flight_data(flight_id);
+                check_live_integrity(&metadata, &packets);
+                metadata.energy_wh = energy_wh(&packets);
+                metadata.phase_durations = phase_durations(&metadata);
+                let value = serde_json::to_string(&metadata)?;
+                self.store.put(Key::String(key), Value::String(value));
             }
+            self.emit_event(FlightEvent::Catastrophic { id: flight_id.clone() });
             
-            self.current_flight_id = None;
-            self.landing_check_start = None;
-            self.total_distance_km = 0.0;
-            self.flight_state = FlightState::OnGround;
-            self.last_phase = None;
+            tracker.current_flight_id = None;
+            tracker.landing_check_start = None;
+            tracker.total_distance_km = 0.0;
+            tracker.flight_state = FlightState::OnGround;
+            tracker.last_phase = None;
+            tracker.chain_end = None;
         }
         Ok(())
     }
     
-    fn get_next_flight_number(&self) -> usize {
+    fn flight_counter_key(source_id: Option<&str>) -> Key {
+        match source_id {
+            Some(source) => Key::String(format!("meta:next_flight_num:{}", source)),
+            None => Key::String("meta:next_flight_num".to_string()),
+        }
+    }
+    
+    /// Next flight number for a source, from its persisted counter
+    fn get_next_flight_number(&self, source_id: Option<&str>) -> usize {
+        match self.store.get_int(&Self::flight_counter_key(source_id)) {
+            Ok(next) => next as usize,
+            Err(_) => self.scan_next_flight_number(source_id),
+        }
+    }
+    
+    /// Claim the next flight number and advance the counter
+    fn take_next_flight_number(&mut self, source_id: Option<&str>) -> usize {
+        let next = self.get_next_flight_number(source_id);
+        self.store.put(Self::flight_counter_key(source_id), Value::Int(next as i64 + 1));
+        next
+    }
+    
+    /// Derive the next flight number from existing flight keys, used when
+    /// there is no counter yet (fresh store or one written before counters)
+    fn scan_next_flight_number(&self, source_id: Option<&str>) -> usize {
+        let prefix = match source_id {
+            Some(source) => format!("flight:{}:", source),
+            None => "flight:".to_string(),
+        };
+        
         let mut max_num = 0;
         for key in self.store.keys() {
             if let Key::String(k) = key {
-                if k.starts_with("flight:flight_") {
-                    if let Some(num_str) = k.strip_prefix("flight:flight_") {
-                        if let Ok(num) = num_str.parse::<usize>() {
-                            max_num = max_num.max(num);
-                        }
-                    }
+                if let Some(num) = k.strip_prefix(&prefix).and_then(|name| self.flight_id_format.parse(name)) {
+                    max_num = max_num.max(num);
                 }
             }
         }
@@ -299,10 +906,8 @@ impl TelemetryStorage {
         for key in self.store.keys() {
             if let Key::String(k) = key {
                 if k.starts_with("flight:") {
-                    if let Ok(BorrowedEntry::Text(json)) = self.store.get(key) {
-                        if let Ok(flight) = serde_json::from_str(json) {
-                            flights.push(flight);
-                        }
+                    if let Some(flight) = self.read_json(key) {
+                        flights.push(flight);
                     }
                 }
             }
@@ -313,59 +918,2053 @@ impl TelemetryStorage {
     }
     
     pub fn get_flight(&self, flight_id: &str) -> Option<FlightMetadata> {
+        self.read_json(&Key::String(format!("flight:{}", flight_id)))
+    }
+    
+    /// Parse a JSON record. Anything but a missing key is logged, so records
+    /// that quietly drop out of results can be traced.
+    fn read_json<T: DeserializeOwned>(&self, key: &Key) -> Option<T> {
+        let result = match self.store.get(key) {
+            Ok(BorrowedEntry::Text(json)) => serde_json::from_str(json).map_err(|e| e.to_string()),
+            Ok(_) => Err("not a text value".to_string()),
+            Err(StoreError::KeyNotFound(_)) => return None,
+            Err(e) => Err(e.to_string()),
+        };
+        result.map_err(|e| eprintln!("[Storage] Warning: skipping {:?}: {}", key, e)).ok()
+    }
+    
+    /// Set or clear a flight's user label. Returns `None` for unknown flights.
+    pub fn set_flight_label(&mut self, flight_id: &str, label: Option<String>) -> Result<Option<FlightMetadata>> {
+        let Some(mut metadata) = self.get_flight(flight_id) else {
+            return Ok(None);
+        };
+        
+        metadata.label = label;
         let key = format!("flight:{}", flight_id);
-        if let Ok(BorrowedEntry::Text(json)) = self.store.get(&Key::String(key)) {
-            serde_json::from_str(json).ok()
-        } else {
-            None
-        }
+        let value = serde_json::to_string(&metadata)?;
+        self.store.put(Key::String(key), Value::String(value));
+        Ok(Some(metadata))
     }
     
-    pub fn get_flight_data(&self, flight_id: &str) -> Vec<TelemetryPacket> {
-        let prefix = format!("telem:{}:", flight_id);
-        let mut packets: Vec<TelemetryPacket> = Vec::new();
+    /// Rebuild a flight's derived metadata from its stored packets and save
+    /// it. Status, label and alerts are kept. Returns `None` for unknown flights.
+    pub fn recompute_flight(&mut self, flight_id: &str) -> Result<Option<FlightMetadata>> {
+        let Some(mut metadata) = self.get_flight(flight_id) else {
+            return Ok(None);
+        };
+        let packets = self.get_flight_data(flight_id);
+        let (Some(first), Some(last)) = (packets.first(), packets.last()) else {
+            return Ok(Some(metadata));
+        };
         
-        for key in self.store.keys() {
-            if let Key::String(k) = key {
-                if k.starts_with(&prefix) {
-                    if let Ok(BorrowedEntry::Text(json)) = self.store.get(key) {
-                        if let Ok(packet) = serde_json::from_str(json) {
-                            packets.push(packet);
-                        }
-                    }
+        // The epoch stays pinned to the start time
+        metadata.start_epoch_ms = metadata.start_epoch_ms
+            .map(|epoch| (epoch + first.timestamp).saturating_sub(metadata.start_time));
+        metadata.start_time = first.timestamp;
+        metadata.end_time = last.timestamp;
+        metadata.duration_secs = (last.timestamp - first.timestamp) / 1000;
+        metadata.packet_count = packets.len();
+        metadata.first_lat = first.latitude;
+        metadata.first_lon = first.longitude;
+        metadata.last_lat = last.latitude;
+        metadata.last_lon = last.longitude;
+        metadata.max_altitude = packets.iter().map(|p| p.altitude_gps).fold(f32::MIN, f32::max);
+        metadata.min_battery = packets.iter().map(|p| p.battery_voltage).fold(f32::MAX, f32::min);
+        metadata.energy_wh = energy_wh(&packets);
+        
+        // Same rules as live tracking: no distance across a gap
+        metadata.distance_km = 0.0;
+        metadata.gap_count = 0;
+        metadata.total_gap_ms = 0;
+        for pair in packets.windows(2) {
+            let gap = pair[1].timestamp - pair[0].timestamp;
+            if gap > Self::GAP_MS {
+                metadata.gap_count += 1;
+                metadata.total_gap_ms += gap;
+            } else {
+                metadata.distance_km += Self::haversine_distance(
+                    pair[0].latitude, pair[0].longitude,
+                    pair[1].latitude, pair[1].longitude
+                );
+            }
+        }
+        
+        // "Landed" is confirmed by a packet that isn't stored, keep the old entry
+        let landed = metadata.phase_timeline.last().filter(|(_, phase)| phase == "Landed").cloned();
+        metadata.phase_timeline.clear();
+        metadata.phase_entry_voltages.clear();
+        let mut last_phase = None;
+        for packet in &packets {
+            let phase = packet.get_flight_phase().to_string();
+            if last_phase.as_ref() != Some(&phase) {
+                Self::record_phase(&mut metadata, packet.timestamp, &phase);
+                if metadata.phase_entry_voltages.len() < Self::MAX_PHASE_TRANSITIONS {
+                    metadata.phase_entry_voltages.push((phase.clone(), packet.battery_voltage));
                 }
+                last_phase = Some(phase);
             }
         }
+        if let Some((timestamp, phase)) = landed {
+            Self::record_phase(&mut metadata, timestamp, &phase);
+        }
+        metadata.phase_durations = phase_durations(&metadata);
         
-        packets.sort_by_key(|p| p.timestamp);
-        packets
+        // An active flight keeps accumulating from the corrected totals
+        if let Some(tracker) = self.trackers.values_mut().find(|t| t.current_flight_id.as_deref() == Some(flight_id)) {
+            tracker.total_distance_km = metadata.distance_km;
+            tracker.gap_count = metadata.gap_count;
+            tracker.total_gap_ms = metadata.total_gap_ms;
+        }
+        
+        let key = format!("flight:{}", flight_id);
+        let value = serde_json::to_string(&metadata)?;
+        self.store.put(Key::String(key), Value::String(value));
+        Ok(Some(metadata))
+    }
+    
+    /// Recompute a flight's integrity chain from its stored packets and
+    /// compare it with the one in its metadata.
+    pub fn verify_integrity(&self, flight_id: &str) -> Option<IntegrityReport> {
+        let metadata = self.get_flight(flight_id)?;
+        let computed_hash = integrity_hash(&self.get_flight_data(flight_id));
+        Some(IntegrityReport {
+            flight_id: metadata.flight_id,
+            valid: metadata.integrity_hash.as_deref() == Some(computed_hash.as_str()),
+            stored_hash: metadata.integrity_hash,
+            computed_hash,
+        })
     }
     
-    pub fn delete_flight(&mut self, flight_id: &str) -> Result<()> {
-        let meta_key = format!("flight:{}", flight_id);
-        self.store.delete(&Key::String(meta_key))?;
+    /// Move the packets of `from` into `into` and drop `from`, for a flight
+    /// that detection split in two. Both must be finished, from the same
+    /// source and not overlap in time. Fails with `MergeRejected` otherwise.
+    pub fn merge_flights(&mut self, into: &str, from: &str) -> Result<FlightMetadata> {
+        let mut merged = self.get_flight(into)
+            .ok_or_else(|| MergeRejected::UnknownFlight(into.to_string()))?;
+        let absorbed = self.get_flight(from)
+            .ok_or_else(|| MergeRejected::UnknownFlight(from.to_string()))?;
+        if into == from {
+            return Err(MergeRejected::SameFlight.into());
+        }
+        if merged.source_id != absorbed.source_id {
+            return Err(MergeRejected::DifferentSources.into());
+        }
+        for id in [into, from] {
+            if self.trackers.values().any(|t| t.current_flight_id.as_deref() == Some(id)) {
+                return Err(MergeRejected::InProgress(id.to_string()).into());
+            }
+        }
+        if merged.start_time <= absorbed.end_time && absorbed.start_time <= merged.end_time {
+            return Err(MergeRejected::Overlapping.into());
+        }
         
-        let prefix = format!("telem:{}:", flight_id);
-        let keys_to_delete: Vec<Key> = self.store.keys()
-            .filter(|k| {
-                if let Key::String(s) = k {
-                    s.starts_with(&prefix)
-                } else {
-                    false
-                }
+        // Reads only fall back to the archive when nothing is in memory
+        self.restore_archived(into)?;
+        self.restore_archived(from)?;
+        // Re-chaining must not make a tampered flight look intact
+        let intact = [into, from].iter()
+            .all(|id| self.verify_integrity(id).is_some_and(|report| report.valid));
+        let from_prefix = format!("telem:{}:", from);
+        let from_keys: Vec<String> = self.store.keys()
+            .filter_map(|k| match k {
+                Key::String(s) if s.starts_with(&from_prefix) => Some(s.clone()),
+                _ => None,
             })
-            .cloned()
             .collect();
+        // All or nothing, so a failure can't leave packets split between the two
+        self.store.transaction(|tx| {
+            for old in from_keys {
+                let new = format!("telem:{}:{}", into, &old[from_prefix.len()..]);
+                tx.rename_key(&Key::String(old), &Key::String(new))?;
+            }
+            tx.delete(&Key::String(format!("flight:{}", from)))
+        })?;
+        self.page_keys.remove(into);
+        self.page_keys.remove(from);
         
-        for key in keys_to_delete {
-            self.store.delete(&key)?;
+        // The later flight decides how the merged one ended, the earlier
+        // when it started
+        if absorbed.start_time > merged.start_time {
+            merged.current_status = absorbed.current_status;
+            merged.ended_normally = absorbed.ended_normally;
+            merged.phase_timeline = absorbed.phase_timeline;
+        } else {
+            merged.start_time = absorbed.start_time;
+            merged.start_epoch_ms = absorbed.start_epoch_ms;
         }
+        merged.sensor_warning |= absorbed.sensor_warning;
+        merged.label = merged.label.or(absorbed.label);
+        merged.integrity_hash = intact.then(|| integrity_hash(&self.get_flight_data(into)));
+        let key = format!("flight:{}", into);
+        self.store.put(Key::String(key), Value::String(serde_json::to_string(&merged)?));
         
-        self.store.compact()?;
+        println!("[Flight] Merged {} into {}", from, into);
+        let merged = self.recompute_flight(into)?
+            .ok_or_else(|| MergeRejected::UnknownFlight(into.to_string()))?;
+        Ok(merged)
+    }
+    
+    /// Move an archived flight's packets back into the main store
+    fn restore_archived(&mut self, flight_id: &str) -> Result<()> {
+        let Some(Ok(archive)) = self.archive_path(flight_id).map(Store::load) else {
+            return Ok(());
+        };
+        let mut restored = 0;
+        for (key, value) in archive.iter() {
+            if let Ok(entry) = value {
+                self.store.put(key.clone(), owned_to_value(&borrowed_to_owned(&entry)));
+                restored += 1;
+            }
+        }
+        drop(archive);
+        self.remove_archive(flight_id);
+        self.packets_in_memory += restored;
         Ok(())
     }
     
-    pub fn get_current_flight_id(&self) -> Option<String> {
-        self.current_flight_id.clone()
+    pub fn get_flight_data(&self, flight_id: &str) -> Vec<TelemetryPacket> {
+        let (packets, skipped) = self.read_flight_data(flight_id);
+        if skipped > 0 {
+            eprintln!("[Storage] Warning: skipped {} unreadable packets of {}", skipped, flight_id);
+        }
+        packets
+    }
+    
+    /// One page of a flight's packets in timestamp order, with the flight's
+    /// packet count. Only the requested packets are parsed.
+    pub fn get_flight_data_page(&mut self, flight_id: &str, offset: usize, limit: usize) -> (Vec<TelemetryPacket>, usize) {
+        if !self.index_page_keys(flight_id) {
+            // Archived or unknown, there is nothing in memory to index
+            let packets = self.get_flight_data(flight_id);
+            let total = packets.len();
+            return (packets.into_iter().skip(offset).take(limit).collect(), total);
+        }
+        
+        let keys = &self.page_keys[flight_id];
+        let page: Vec<Key> = keys.iter().skip(offset).take(limit).cloned().collect();
+        let (records, unreadable) = collect_records(page.iter().zip(self.store.get_batch(&page)));
+        let (packets, unparsed) = parse_packets(&records);
+        if unreadable + unparsed > 0 {
+            eprintln!("[Storage] Warning: skipped {} unreadable packets of {}", unreadable + unparsed, flight_id);
+        }
+        (packets, keys.len())
+    }
+    
+    /// Like `get_flight_data_page`, but starting after the packet at `after`,
+    /// a `(timestamp, packet_sequence)` cursor. Also returns the offset the
+    /// page starts at. Only a packet stored without a sequence in its key and
+    /// sharing the cursor's timestamp is parsed to find the spot.
+    pub fn get_flight_data_after(&mut self, flight_id: &str, after: (u64, u32), limit: usize) -> (Vec<TelemetryPacket>, usize, usize) {
+        if !self.index_page_keys(flight_id) {
+            let packets = self.get_flight_data(flight_id);
+            let total = packets.len();
+            let offset = seek_after(&packets, after, |p| p.timestamp, |p| Some(p.packet_sequence));
+            return (packets.into_iter().skip(offset).take(limit).collect(), total, offset);
+        }
+        
+        let keys = &self.page_keys[flight_id];
+        let offset = seek_after(keys, after,
+            |key| telem_key_order(key).map_or(0, |(timestamp, _)| timestamp),
+            |key| match telem_key_order(key) {
+                Some((_, Some(sequence))) => Some(sequence),
+                _ => self.read_packet(key).map(|p| p.packet_sequence),
+            });
+        let (packets, total) = self.get_flight_data_page(flight_id, offset, limit);
+        (packets, total, offset)
+    }
+    
+    /// Build the page order of a flight's in-memory packets unless it is
+    /// cached. Returns false if none of its packets are in memory.
+    fn index_page_keys(&mut self, flight_id: &str) -> bool {
+        if self.page_keys.contains_key(flight_id) {
+            return true;
+        }
+        let prefix = format!("telem:{}:", flight_id);
+        let mut keys: Vec<(u64, Option<u32>, Key)> = self.store.keys()
+            .filter_map(|k| match k {
+                Key::String(s) => s.strip_prefix(&prefix)
+                    .and_then(telem_sort_key)
+                    .map(|(timestamp, sequence)| (timestamp, sequence, k.clone())),
+                Key::Int(_) | Key::Bytes(_) => None,
+            })
+            .collect();
+        if keys.is_empty() {
+            return false;
+        }
+        keys.sort_by_key(|(timestamp, sequence, _)| (*timestamp, *sequence));
+        let keys = keys.into_iter().map(|(_, _, key)| key).collect();
+        self.page_keys.insert(flight_id.to_string(), keys);
+        true
+    }
+    
+    fn read_packet(&self, key: &Key) -> Option<TelemetryPacket> {
+        let (records, _) = collect_records(std::iter::once((key, self.store.get(key))));
+        records.first()?.1.parse().ok()
+    }
+    
+    /// Packets of a flight in key order, the same order the pages use, and
+    /// how many records were unreadable
+    fn read_flight_data(&self, flight_id: &str) -> (Vec<TelemetryPacket>, usize) {
+        let prefix = format!("telem:{}:", flight_id);
+        let keys: Vec<Key> = self.store.keys()
+            .filter(|k| matches!(k, Key::String(s) if s.starts_with(&prefix)))
+            .cloned()
+            .collect();
+        
+        // Copy the records out first so the parsing below doesn't borrow the store
+        let (mut records, unreadable) = collect_records(keys.iter().zip(self.store.get_batch(&keys)));
+        records.sort_by_key(|(key, _)| telem_key_order(key));
+        let (mut packets, unparsed) = parse_packets(&records);
+        let mut skipped = unreadable + unparsed;
+        
+        if packets.is_empty() {
+            (packets, skipped) = self.load_archived_flight(flight_id);
+        }
+        
+        (packets, skipped)
+    }
+    
+    /// Move the telemetry of every flight that is no longer in progress out of
+    /// memory into its own store file, then save the slimmed-down main store.
+    /// Values that can't be read are logged and dropped. Returns the number
+    /// of packets moved.
+    pub fn evict_completed_flights(&mut self) -> Result<usize> {
+        let Some(archive_dir) = self.archive_dir.clone() else {
+            return Ok(0);
+        };
+        let active: HashSet<&str> = self.trackers.values()
+            .filter_map(|t| t.current_flight_id.as_deref())
+            .collect();
+        
+        // telem:<flight_id>:<timestamp>[-<sequence>], where the flight id may itself contain ':'
+        let mut by_flight: HashMap<String, Vec<Key>> = HashMap::new();
+        for key in self.store.keys() {
+            if let Key::String(s) = key {
+                let Some((prefix, _)) = s.strip_prefix("telem:").and_then(|rest| rest.rsplit_once(':')) else {
+                    continue;
+                };
+                if !active.contains(prefix) {
+                    by_flight.entry(prefix.to_string()).or_default().push(key.clone());
+                }
+            }
+        }
+        if by_flight.is_empty() {
+            return Ok(0);
+        }
+        
+        std::fs::create_dir_all(&archive_dir)?;
+        let mut evicted = 0;
+        let mut unreadable = 0;
+        for (flight_id, keys) in by_flight {
+            let mut archive = Store::with_path(archive_dir.join(&flight_id))?;
+            for key in &keys {
+                match self.store.get(key) {
+                    Ok(entry) => {
+                        archive.put(key.clone(), owned_to_value(&borrowed_to_owned(&entry)));
+                        evicted += 1;
+                    }
+                    Err(e) => {
+                        // Dropped with the rest, there is nothing to save
+                        eprintln!("[Storage] Warning: not archiving {:?}: {}", key, e);
+                        unreadable += 1;
+                    }
+                }
+            }
+            archive.close()?;
+            
+            for key in &keys {
+                self.store.delete(key)?;
+            }
+            self.page_keys.remove(&flight_id);
+            // Kept per flight so an error on a later one leaves the count right
+            self.packets_in_memory -= keys.len();
+        }
+        
+        self.store.compact()?;
+        self.store.save()?;
+        println!("[Storage] Archived {} packets of completed flights, dropped {} unreadable", evicted, unreadable);
+        Ok(evicted)
+    }
+    
+    fn archive_path(&self, flight_id: &str) -> Option<PathBuf> {
+        self.archive_dir.as_ref().map(|dir| dir.join(flight_id))
+    }
+    
+    fn load_archived_flight(&self, flight_id: &str) -> (Vec<TelemetryPacket>, usize) {
+        let Some(Ok(archive)) = self.archive_path(flight_id).map(Store::load) else {
+            return (Vec::new(), 0);
+        };
+        let (mut records, unreadable) = collect_records(archive.iter());
+        records.sort_by_key(|(key, _)| telem_key_order(key));
+        let (packets, unparsed) = parse_packets(&records);
+        (packets, unreadable + unparsed)
+    }
+    
+    /// Returns whether there was an archive to remove
+    fn remove_archive(&self, flight_id: &str) -> bool {
+        let Some(archive_path) = self.archive_path(flight_id) else {
+            return false;
+        };
+        let mut removed = false;
+        for path in Store::file_paths(archive_path) {
+            removed |= std::fs::remove_file(path).is_ok();
+        }
+        removed
+    }
+    
+    /// Remove a flight's metadata, telemetry and archive, whichever of them
+    /// are still there, so a delete interrupted halfway can be repeated.
+    /// Returns false if there was nothing left to delete.
+    pub fn delete_flight(&mut self, flight_id: &str) -> Result<bool> {
+        let meta_key = Key::String(format!("flight:{}", flight_id));
+        let had_metadata = self.store.delete(&meta_key).is_ok();
+        
+        let deleted = self.store.clear_prefix(&format!("telem:{}:", flight_id));
+        self.packets_in_memory = self.packets_in_memory.saturating_sub(deleted);
+        self.page_keys.remove(flight_id);
+        let had_archive = self.remove_archive(flight_id);
+        
+        // Only the first slice runs here, incoming packets finish the rest
+        self.store.compact_incremental(Self::COMPACTION_STEP_BYTES)?;
+        Ok(had_metadata || deleted > 0 || had_archive)
+    }
+    
+    /// Delete the oldest completed flights until one more fits under
+    /// `max_flights`. Flights in progress are never deleted, even if that
+    /// leaves the store over the cap.
+    fn make_room_for_flight(&mut self) -> Result<()> {
+        if self.max_flights == 0 {
+            return Ok(());
+        }
+        let mut flights = self.list_flights();
+        let total = flights.len();
+        if total < self.max_flights {
+            return Ok(());
+        }
+        let active: HashSet<&str> = self.trackers.values()
+            .filter_map(|t| t.current_flight_id.as_deref())
+            .collect();
+        flights.retain(|f| !active.contains(f.flight_id.as_str()));
+        // Source clocks restart, so go by when the server saw each flight start.
+        // Flights without that (older or imported ones) go first, by number.
+        flights.sort_by_key(|f| {
+            let name = match &f.source_id {
+                Some(source) => f.flight_id.strip_prefix(source.as_str()).and_then(|n| n.strip_prefix(':')),
+                None => Some(f.flight_id.as_str()),
+            };
+            (f.start_epoch_ms, name.and_then(|n| self.flight_id_format.parse(n)))
+        });
+        
+        for flight in flights.iter().take(total + 1 - self.max_flights) {
+            println!("[Flight] Deleting {} to stay within {} flights", flight.flight_id, self.max_flights);
+            self.delete_flight(&flight.flight_id)?;
+        }
+        Ok(())
+    }
+    
+    /// Remove every flight and its telemetry, leaving other keys untouched.
+    /// Returns the number of flights removed.
+    pub fn delete_all_flights(&mut self) -> Result<usize> {
+        let flights_deleted = self.store.clear_prefix("flight:");
+        self.store.clear_prefix("telem:");
+        
+        // In-progress flights are gone too, detection starts over
+        self.trackers.clear();
+        self.packets_in_memory = 0;
+        self.page_keys.clear();
+        if let Some(archive_dir) = &self.archive_dir {
+            std::fs::remove_dir_all(archive_dir).ok();
+        }
+        
+        self.store.compact()?;
+        Ok(flights_deleted)
+    }
+    
+    /// Active flight of the source the server heard from most recently
+    pub fn get_current_flight_id(&self) -> Option<String> {
+        self.trackers.values()
+            .filter(|t| t.current_flight_id.is_some())
+            .max_by_key(|t| t.last_received)
+            .and_then(|t| t.current_flight_id.clone())
+    }
+    
+    /// Estimated seconds of flight left in the pack of `source_id`'s current
+    /// flight, None on the ground or early in a flight
+    pub fn time_to_empty_secs(&self, source_id: Option<&str>) -> Option<u64> {
+        self.trackers.get(&source_id.map(str::to_string))
+            .filter(|t| t.current_flight_id.is_some())
+            .and_then(|t| time_to_empty_secs(&t.recent_packets, self.battery_capacity_mah))
+            .map(|secs| secs as u64)
+    }
+    
+    /// Read-only access to the underlying store for custom analysis.
+    /// Writes stay behind the methods above so flight tracking stays consistent.
+    ///
+    /// ```ignore
+    /// let telem_keys = storage.store().keys()
+    ///     .filter(|k| matches!(k, Key::String(s) if s.starts_with("telem:")))
+    ///     .count();
+    /// ```
+    #[allow(dead_code)] // Nothing in this binary needs it yet
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+    
+    /// Rewrite the store without dead values. Blocks ingest until done.
+    pub fn compact(&mut self) -> Result<CompactionReport> {
+        Ok(self.store.compact()?)
+    }
+    
+    pub fn fragmentation_ratio(&self) -> f64 {
+        self.store.fragmentation_ratio()
+    }
+    
+    pub fn type_histogram(&self) -> TypeHistogram {
+        self.store.type_histogram()
+    }
+    
+    pub fn data_size(&self) -> usize {
+        self.store.data_size()
+    }
+    
+    pub fn live_size(&self) -> usize {
+        self.store.live_size()
+    }
+    
+    /// Packets received since startup, stored or not
+    pub fn packets_ingested(&self) -> u64 {
+        self.packets_ingested
+    }
+    
+    pub fn active_flights(&self) -> usize {
+        self.trackers.values().filter(|t| t.current_flight_id.is_some()).count()
+    }
+    
+    pub fn last_save_error(&self) -> Option<&str> {
+        self.store.last_save_error()
+    }
+    
+    /// Flush the store to disk. Later writes only go to memory.
+    pub fn close(&mut self) -> Result<()> {
+        if self.archive_dir.is_none() {
+            return Ok(()); // In memory, nothing to save
+        }
+        std::mem::replace(&mut self.store, Store::new()).close()?;
+        Ok(())
+    }
+}
+
+/// One link of the integrity chain: xxHash64 of the previous hash followed
+/// by the packet's wire bytes
+fn chain_packet(prev: u64, packet: &TelemetryPacket) -> u64 {
+    let mut bytes = prev.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&packet.to_bytes());
+    Checksum::Xxhash64.compute(&bytes)
+}
+
+/// The chain over `packets`, which must be in stored key order
+fn integrity_hash(packets: &[TelemetryPacket]) -> String {
+    format_hash(packets.iter().fold(0, chain_packet))
+}
+
+/// Final check as a flight ends: warn when its packets no longer match the
+/// chain recorded live. The chain isn't touched after this.
+fn check_live_integrity(metadata: &FlightMetadata, packets: &[TelemetryPacket]) {
+    let computed = integrity_hash(packets);
+    if metadata.integrity_hash.as_deref() != Some(computed.as_str()) {
+        eprintln!("[Integrity] {} packets changed since they were recorded ({:?} recorded, {} stored)",
+                  metadata.flight_id, metadata.integrity_hash, computed);
+    }
+}
+
+/// Trapezoidal integral of `battery_power` over the packets' own timestamps,
+/// so uneven sampling and dropouts are weighted by the time they cover
+fn energy_wh(packets: &[TelemetryPacket]) -> f64 {
+    let joules: f64 = packets.windows(2)
+        .map(|pair| {
+            let seconds = pair[1].timestamp.saturating_sub(pair[0].timestamp) as f64 / 1000.0;
+            (pair[0].battery_power as f64 + pair[1].battery_power as f64) / 2.0 * seconds
+        })
+        .sum();
+    joules / 3600.0
+}
+
+/// Seconds until the pack runs out at the average `battery_power` of
+/// `recent`, with the charge left after the newest packet's `battery_mah_used`
+/// at its voltage. None with fewer than `MIN_SAMPLES` packets or while
+/// nothing is drawn.
+fn time_to_empty_secs(recent: &VecDeque<TelemetryPacket>, capacity_mah: f32) -> Option<f64> {
+    const MIN_SAMPLES: usize = 10;
+    let latest = recent.back()?;
+    if recent.len() < MIN_SAMPLES {
+        return None;
+    }
+    let avg_power = recent.iter().map(|p| p.battery_power as f64).sum::<f64>() / recent.len() as f64;
+    if avg_power <= 0.0 {
+        return None;
+    }
+    let remaining_mah = (capacity_mah - latest.battery_mah_used).max(0.0) as f64;
+    let remaining_wh = remaining_mah / 1000.0 * latest.battery_voltage as f64;
+    Some(remaining_wh / avg_power * 3600.0)
+}
+
+/// Milliseconds per phase from the timeline, each phase running until the
+/// next one starts and the last until the flight's end. Clamped to the
+/// flight, so the durations add up to `end_time - start_time`.
+fn phase_durations(metadata: &FlightMetadata) -> HashMap<String, u64> {
+    let mut durations = HashMap::new();
+    let timeline = &metadata.phase_timeline;
+    for (i, (timestamp, phase)) in timeline.iter().enumerate() {
+        let next = timeline.get(i + 1).map_or(metadata.end_time, |(t, _)| *t);
+        let start = (*timestamp).max(metadata.start_time);
+        let end = next.min(metadata.end_time);
+        if end > start {
+            *durations.entry(phase.clone()).or_insert(0) += end - start;
+        }
+    }
+    durations
+}
+
+fn format_hash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+fn parse_hash(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// Order of a telem key from its `<timestamp>[-<sequence>]` suffix
+fn telem_sort_key(suffix: &str) -> Option<(u64, Option<u32>)> {
+    match suffix.split_once('-') {
+        Some((timestamp, sequence)) => Some((timestamp.parse().ok()?, Some(sequence.parse().ok()?))),
+        None => Some((suffix.parse().ok()?, None)),
+    }
+}
+
+/// Order of a full telem key. Packets sharing a timestamp keep their
+/// arrival order, since only the later ones get a sequence suffix.
+fn telem_key_order(key: &Key) -> Option<(u64, Option<u32>)> {
+    match key {
+        Key::String(s) => s.rsplit_once(':').and_then(|(_, suffix)| telem_sort_key(suffix)),
+        Key::Int(_) | Key::Bytes(_) => None,
+    }
+}
+
+/// Index just past the item at cursor `after` in a flight's page order. A
+/// cursor that matches no item skips its whole millisecond.
+fn seek_after<T>(
+    items: &[T],
+    after: (u64, u32),
+    timestamp: impl Fn(&T) -> u64,
+    sequence: impl Fn(&T) -> Option<u32>,
+) -> usize {
+    let start = items.partition_point(|item| timestamp(item) < after.0);
+    let end = items.partition_point(|item| timestamp(item) <= after.0);
+    items[start..end].iter()
+        .position(|item| sequence(item) == Some(after.1))
+        .map_or(end, |i| start + i + 1)
+}
+
+/// A stored packet, JSON text or the binary wire format
+enum PacketRecord {
+    Json(String),
+    Binary(Vec<u8>),
+}
+
+impl PacketRecord {
+    fn parse(&self) -> Result<TelemetryPacket, String> {
+        match self {
+            PacketRecord::Json(json) => serde_json::from_str(json).map_err(|e| e.to_string()),
+            PacketRecord::Binary(bytes) => TelemetryPacket::from_bytes(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Copy out each packet record, logging and counting the ones that can't be read
+fn collect_records<'k, 'v>(
+    entries: impl Iterator<Item = (&'k Key, Result<BorrowedEntry<'v>, StoreError>)>,
+) -> (Vec<(Key, PacketRecord)>, usize) {
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    for (key, entry) in entries {
+        match entry {
+            Ok(BorrowedEntry::Text(json)) => records.push((key.clone(), PacketRecord::Json(json.to_string()))),
+            Ok(BorrowedEntry::Bytes(bytes)) => records.push((key.clone(), PacketRecord::Binary(bytes.to_vec()))),
+            Ok(_) => {
+                eprintln!("[Storage] Warning: skipping {:?}: not a packet value", key);
+                skipped += 1;
+            }
+            Err(e) => {
+                eprintln!("[Storage] Warning: skipping {:?}: {}", key, e);
+                skipped += 1;
+            }
+        }
+    }
+    (records, skipped)
+}
+
+/// Parse packets across the rayon pool, logging and counting records that
+/// don't parse. Packets come out in the order of `records`.
+fn parse_packets(records: &[(Key, PacketRecord)]) -> (Vec<TelemetryPacket>, usize) {
+    let packets: Vec<TelemetryPacket> = records.par_iter()
+        .filter_map(|(key, record)| match record.parse() {
+            Ok(packet) => Some(packet),
+            Err(e) => {
+                eprintln!("[Storage] Warning: skipping {:?}: {}", key, e);
+                None
+            }
+        })
+        .collect();
+    let skipped = records.len() - packets.len();
+    (packets, skipped)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+
+    pub(crate) fn remove_store_files(path: &str) {
+        fs::remove_file(format!("{}.keys", path)).ok();
+        fs::remove_file(format!("{}.data", path)).ok();
+        fs::remove_file(format!("{}.meta", path)).ok();
+    }
+
+    fn battery_packet(timestamp: u64, battery_voltage: f32) -> TelemetryPacket {
+        TelemetryPacket {
+            battery_voltage,
+            ..airborne_packet(timestamp)
+        }
+    }
+
+    /// Adjust one field of a test packet at a time
+    trait PacketBuilder {
+        fn with_timestamp(self, timestamp: u64) -> Self;
+        fn with_altitude(self, altitude: f32) -> Self;
+        fn with_speed(self, ground_speed: f32) -> Self;
+    }
+
+    impl PacketBuilder for TelemetryPacket {
+        fn with_timestamp(self, timestamp: u64) -> Self {
+            TelemetryPacket { timestamp, ..self }
+        }
+
+        /// GPS and baro both
+        fn with_altitude(self, altitude: f32) -> Self {
+            TelemetryPacket { altitude_gps: altitude, altitude_baro: altitude, ..self }
+        }
+
+        fn with_speed(self, ground_speed: f32) -> Self {
+            TelemetryPacket { ground_speed, ..self }
+        }
+    }
+
+    // Flights start on the first airborne packet, for tests that don't exercise takeoff
+    const INSTANT_TAKEOFF: FlightDetectionConfig = FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 5000 };
+
+    fn flight_state(storage: &TelemetryStorage) -> FlightState {
+        storage.trackers.get(&None).map_or(FlightState::OnGround, |t| t.flight_state)
+    }
+
+    #[test]
+    fn test_flight_state_transitions() -> Result<()> {
+        let detection = FlightDetectionConfig { takeoff_confirm_ms: 1000, landing_confirm_ms: 3000 };
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(detection);
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+        let flying = airborne_packet(0);
+
+        storage.save_packet(&parked.with_timestamp(1000))?;
+        assert_eq!(flight_state(&storage), FlightState::OnGround);
+
+        // Airborne, but not for long enough yet
+        storage.save_packet(&flying.with_timestamp(2000))?;
+        storage.save_packet(&flying.with_timestamp(2999))?;
+        assert_eq!(flight_state(&storage), FlightState::OnGround);
+        storage.save_packet(&flying.with_timestamp(3000))?;
+        assert_eq!(flight_state(&storage), FlightState::InFlight);
+        assert_eq!(storage.get_current_flight_id().as_deref(), Some("flight_001"));
+
+        // Touches down, lifts off again before it's confirmed
+        storage.save_packet(&parked.with_timestamp(4000))?;
+        assert_eq!(flight_state(&storage), FlightState::Landing);
+        storage.save_packet(&flying.with_timestamp(5000))?;
+        assert_eq!(flight_state(&storage), FlightState::InFlight);
+
+        storage.save_packet(&parked.with_timestamp(6000))?;
+        storage.save_packet(&parked.with_timestamp(8999))?;
+        assert_eq!(flight_state(&storage), FlightState::Landing);
+        storage.save_packet(&parked.with_timestamp(9000))?;
+        assert_eq!(flight_state(&storage), FlightState::OnGround);
+        assert_eq!(storage.get_current_flight_id(), None);
+
+        let flight = storage.get_flight("flight_001").unwrap();
+        assert_eq!(flight.current_status, "Landed");
+        assert!(flight.ended_normally);
+        assert_eq!((flight.start_time, flight.end_time), (2000, 8999));
+        // Packets from before the takeoff was confirmed are part of the flight
+        let timestamps: Vec<u64> = storage.get_flight_data("flight_001").iter().map(|p| p.timestamp).collect();
+        assert_eq!(timestamps[..3], [2000, 2999, 3000]);
+        assert_eq!(storage.list_flights().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_flights_deletes_oldest() -> Result<()> {
+        let detection = FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 };
+        let mut storage = TelemetryStorage::new_in_memory()
+            .with_flight_detection(detection)
+            .with_max_flights(2);
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+        for start in [10_000, 20_000, 30_000] {
+            storage.save_packet(&airborne_packet(start))?;
+            storage.save_packet(&airborne_packet(start + 1000))?;
+            storage.save_packet(&parked.with_timestamp(start + 2000))?;
+            storage.save_packet(&parked.with_timestamp(start + 3000))?;
+        }
+
+        let mut ids: Vec<String> = storage.list_flights().into_iter().map(|f| f.flight_id).collect();
+        ids.sort();
+        assert_eq!(ids, ["flight_002", "flight_003"]);
+        assert!(storage.get_flight_data("flight_001").is_empty());
+        assert_eq!(storage.get_flight_data("flight_003").len(), 3);
+
+        // A flight still in the air is kept even over the cap
+        let mut storage = storage.with_max_flights(1);
+        storage.save_packet(&airborne_packet(40_000))?;
+        storage.save_packet_from(Some("drone2"), &airborne_packet(40_000))?;
+        let mut ids: Vec<String> = storage.list_flights().into_iter().map(|f| f.flight_id).collect();
+        ids.sort();
+        assert_eq!(ids, ["drone2:flight_001", "flight_004"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_flights_survives_source_clock_reset() -> Result<()> {
+        let detection = FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 };
+        let mut storage = TelemetryStorage::new_in_memory()
+            .with_flight_detection(detection)
+            .with_max_flights(2);
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+        // The source restarts before the second flight, its clock starting over
+        for start in [500_000, 10_000, 20_000] {
+            storage.save_packet(&parked.with_timestamp(start - 1000))?;
+            storage.save_packet(&airborne_packet(start))?;
+            storage.save_packet(&airborne_packet(start + 1000))?;
+            storage.save_packet(&parked.with_timestamp(start + 2000))?;
+            storage.save_packet(&parked.with_timestamp(start + 3000))?;
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let mut ids: Vec<String> = storage.list_flights().into_iter().map(|f| f.flight_id).collect();
+        ids.sort();
+        assert_eq!(ids, ["flight_002", "flight_003"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_capped_phase_timeline_still_ends_landed() -> Result<()> {
+        let detection = FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 };
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(detection);
+        let cruise = airborne_packet(0).with_altitude(150.0);
+        let hover = cruise.with_speed(0.0);
+        for i in 0..TelemetryStorage::MAX_PHASE_TRANSITIONS as u64 + 50 {
+            let packet = if i.is_multiple_of(2) { cruise } else { hover };
+            storage.save_packet(&packet.with_timestamp(1000 + i * 100))?;
+        }
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+        storage.save_packet(&parked.with_timestamp(60_000))?;
+        storage.save_packet(&parked.with_timestamp(61_000))?;
+
+        let timeline = storage.get_flight("flight_001").unwrap().phase_timeline;
+        assert_eq!(timeline.len(), TelemetryStorage::MAX_PHASE_TRANSITIONS);
+        assert_eq!(timeline.last().unwrap(), &(61_000, "Landed".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_to_empty_estimate() {
+        // 150 W from 16 V with 1000 of 5000 mAh used: 64 Wh left, ~25.6 min
+        let packet = |i: u64| TelemetryPacket {
+            battery_power: if i.is_multiple_of(2) { 140.0 } else { 160.0 },
+            battery_voltage: 16.0,
+            battery_mah_used: 1000.0,
+            ..airborne_packet(i * 200)
+        };
+        let early: VecDeque<_> = (0..5).map(packet).collect();
+        assert_eq!(time_to_empty_secs(&early, 5000.0), None);
+
+        let recent: VecDeque<_> = (0..20).map(packet).collect();
+        let secs = time_to_empty_secs(&recent, 5000.0).unwrap();
+        assert!((1530.0..1545.0).contains(&secs), "{secs}");
+
+        // Past capacity is empty, not negative
+        assert_eq!(time_to_empty_secs(&recent, 800.0), Some(0.0));
+
+        let mut storage = TelemetryStorage::new_in_memory();
+        assert_eq!(storage.time_to_empty_secs(None), None);
+        for i in 0..20 {
+            storage.save_packet(&packet(i + 1)).unwrap();
+        }
+        assert_eq!(storage.time_to_empty_secs(None), Some(secs as u64));
+        assert_eq!(storage.time_to_empty_secs(Some("drone_2")), None);
+    }
+
+    #[test]
+    fn test_flight_events_in_order() -> Result<()> {
+        let (event_tx, mut event_rx) = broadcast::channel(16);
+        let mut storage = TelemetryStorage::new_in_memory()
+            .with_flight_detection(FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 })
+            .with_events(event_tx);
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+
+        storage.save_packet(&airborne_packet(1000))?;
+        storage.save_packet(&airborne_packet(2000))?;
+        storage.save_packet(&parked.with_timestamp(3000))?;
+        storage.save_packet(&parked.with_timestamp(4000))?;
+
+        let id = "flight_001".to_string();
+        assert_eq!(event_rx.try_recv()?, FlightEvent::Started { id: id.clone() });
+        assert_eq!(event_rx.try_recv()?, FlightEvent::Ended { id, normally: true });
+        assert!(event_rx.try_recv().is_err());
+        Ok(())
+    }
+
+    pub(crate) fn airborne_packet(timestamp: u64) -> TelemetryPacket {
+        TelemetryPacket {
+            latitude: 49.8728,
+            longitude: 8.6512,
+            altitude_gps: 50.0,
+            altitude_baro: 50.0,
+            ground_speed: 20.0,
+            battery_voltage: 16.8,
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_in_memory_storage_writes_nothing() -> Result<()> {
+        let files = |dir: &str| -> Vec<PathBuf> {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(dir).unwrap()
+                .map(|e| e.unwrap().path())
+                .collect();
+            entries.sort();
+            entries
+        };
+        let before = files(".");
+
+        let mut storage = TelemetryStorage::new_in_memory().with_max_in_memory_packets(1).with_flight_detection(INSTANT_TAKEOFF);
+        storage.save_packet_from(Some("drone_1"), &airborne_packet(1000))?;
+        storage.save_packet_from(Some("drone_1"), &airborne_packet(1200))?;
+        storage.save_packet_from(Some("drone_2"), &airborne_packet(1000))?;
+
+        let flights = storage.list_flights();
+        assert_eq!(flights.len(), 2);
+        assert_eq!(storage.get_flight_data("drone_1:flight_001").len(), 2);
+        storage.delete_flight("drone_2:flight_001")?;
+        assert_eq!(storage.list_flights().len(), 1);
+        storage.close()?;
+        drop(storage);
+
+        assert_eq!(files("."), before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_numbering_per_source() -> Result<()> {
+        let path = "/tmp/test_telemetry_sources";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet_from(Some("drone_1"), &airborne_packet(1000))?;
+            storage.save_packet_from(Some("drone_2"), &airborne_packet(1000))?;
+
+            let flights = storage.list_flights();
+            let ids: Vec<&str> = flights.iter().map(|f| f.flight_id.as_str()).collect();
+            assert!(ids.contains(&"drone_1:flight_001"));
+            assert!(ids.contains(&"drone_2:flight_001"));
+
+            let flight = storage.get_flight("drone_2:flight_001").unwrap();
+            assert_eq!(flight.source_id.as_deref(), Some("drone_2"));
+            assert_eq!(storage.get_flight_data("drone_1:flight_001").len(), 1);
+
+            assert_eq!(storage.get_next_flight_number(Some("drone_1")), 2);
+            assert_eq!(storage.get_next_flight_number(Some("drone_3")), 1);
+            assert_eq!(storage.get_next_flight_number(None), 1);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_current_flight_is_from_last_heard_source() -> Result<()> {
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(INSTANT_TAKEOFF);
+        // drone_1 has been up far longer, so its clock reads much later
+        storage.save_packet_from(Some("drone_1"), &airborne_packet(5_000_000))?;
+        storage.save_packet_from(Some("drone_2"), &airborne_packet(1000))?;
+        assert_eq!(storage.get_current_flight_id().as_deref(), Some("drone_2:flight_001"));
+
+        storage.save_packet_from(Some("drone_1"), &airborne_packet(5_000_200))?;
+        assert_eq!(storage.get_current_flight_id().as_deref(), Some("drone_1:flight_001"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_low_battery_alert_latches_until_recovery() -> Result<()> {
+        let path = "/tmp/test_telemetry_low_battery";
+        remove_store_files(path);
+
+        {
+            let (alert_tx, mut alert_rx) = broadcast::channel(16);
+            let mut storage = TelemetryStorage::new(path)?.with_alerts(alert_tx);
+
+            storage.save_packet(&battery_packet(1000, 15.0))?;
+            storage.save_packet(&battery_packet(1500, 13.9))?;
+            storage.save_packet(&battery_packet(2000, 13.8))?;
+
+            let alert = alert_rx.try_recv()?;
+            assert_eq!(alert.kind, AlertKind::LowBattery);
+            assert_eq!(alert.flight_id, "flight_001");
+            assert_eq!(alert.timestamp, 1500);
+            assert!(alert_rx.try_recv().is_err());
+
+            // Recovering above the threshold re-arms the alert
+            storage.save_packet(&battery_packet(2500, 14.2))?;
+            storage.save_packet(&battery_packet(3000, 13.7))?;
+            assert_eq!(alert_rx.try_recv()?.timestamp, 3000);
+            assert!(alert_rx.try_recv().is_err());
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_geofence_breach_alerts_once() -> Result<()> {
+        let path = "/tmp/test_telemetry_geofence";
+        remove_store_files(path);
+
+        {
+            let (alert_tx, mut alert_rx) = broadcast::channel(16);
+            let fence = Geofence { center_lat: 49.8728, center_lon: 8.6512, radius_km: 1.0 };
+            let mut storage = TelemetryStorage::new(path)?
+                .with_alerts(alert_tx)
+                .with_geofence(fence);
+
+            storage.save_packet(&airborne_packet(1000))?;
+            for (i, lat) in [49.90, 49.901, 49.902].into_iter().enumerate() {
+                let packet = TelemetryPacket {
+                    latitude: lat,
+                    ..airborne_packet(1500 + i as u64 * 500)
+                };
+                storage.save_packet(&packet)?;
+            }
+
+            let breach = alert_rx.try_recv()?;
+            assert_eq!(breach.kind, AlertKind::GeofenceBreach);
+            assert_eq!(breach.timestamp, 1500);
+            assert!(breach.value > 1.0 && breach.value < 3.0);
+            assert!(alert_rx.try_recv().is_err());
+
+            storage.save_packet(&airborne_packet(3500))?;
+            assert_eq!(alert_rx.try_recv()?.kind, AlertKind::GeofenceReturn);
+            assert!(alert_rx.try_recv().is_err());
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_label_persists() -> Result<()> {
+        let path = "/tmp/test_telemetry_label";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            assert_eq!(storage.get_flight("flight_001").unwrap().label, None);
+
+            let updated = storage.set_flight_label("flight_001", Some("Field test A".into()))?;
+            assert_eq!(updated.unwrap().label.as_deref(), Some("Field test A"));
+            assert!(storage.set_flight_label("flight_999", Some("nope".into()))?.is_none());
+
+            // Later packets keep the label
+            storage.save_packet(&airborne_packet(1500))?;
+            assert_eq!(storage.list_flights()[0].label.as_deref(), Some("Field test A"));
+        }
+
+        let storage = TelemetryStorage::new(path)?;
+        let flight = storage.get_flight("flight_001").unwrap();
+        assert_eq!(flight.label.as_deref(), Some("Field test A"));
+        drop(storage);
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_flight_twice() -> Result<()> {
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(INSTANT_TAKEOFF);
+        storage.save_packet(&airborne_packet(1000))?;
+        storage.save_packet(&airborne_packet(1200))?;
+        storage.save_packet(&airborne_packet(1400))?;
+
+        assert!(storage.delete_flight("flight_001")?);
+        assert!(!storage.delete_flight("flight_001")?);
+        assert!(!storage.delete_flight("flight_404")?);
+
+        // Metadata already gone, telemetry left behind
+        storage.save_packet(&airborne_packet(70_000))?;
+        storage.save_packet(&airborne_packet(70_200))?;
+        let current = storage.get_current_flight_id().unwrap();
+        storage.store.delete(&Key::String(format!("flight:{}", current)))?;
+        assert!(storage.delete_flight(&current)?);
+        assert!(storage.get_flight_data(&current).is_empty());
+        assert!(!storage.delete_flight(&current)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_all_flights() -> Result<()> {
+        let path = "/tmp/test_telemetry_delete_all";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet_from(Some("drone_1"), &airborne_packet(1000))?;
+            storage.save_packet_from(Some("drone_2"), &airborne_packet(1000))?;
+            assert_eq!(storage.list_flights().len(), 2);
+
+            assert_eq!(storage.delete_all_flights()?, 2);
+            assert!(storage.list_flights().is_empty());
+            assert!(storage.get_flight_data("drone_1:flight_001").is_empty());
+            assert_eq!(storage.get_current_flight_id(), None);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_counter_survives_reload() -> Result<()> {
+        let path = "/tmp/test_telemetry_counter";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            // Each long gap ends the flight, the next airborne packet starts another
+            for timestamp in [1000, 100_000, 200_000] {
+                storage.save_packet(&airborne_packet(timestamp))?;
+            }
+
+            let ids: Vec<String> = storage.list_flights().into_iter().map(|f| f.flight_id).collect();
+            assert_eq!(ids, vec!["flight_001", "flight_002", "flight_003"]);
+            assert_eq!(storage.store.get_int(&TelemetryStorage::flight_counter_key(None))?, 4);
+        }
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            assert_eq!(storage.get_next_flight_number(None), 4);
+            storage.save_packet(&airborne_packet(300_000))?;
+            assert_eq!(storage.get_current_flight_id().as_deref(), Some("flight_004"));
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_counter_derived_from_existing_flights() -> Result<()> {
+        let path = "/tmp/test_telemetry_counter_derive";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            storage.store.delete(&TelemetryStorage::flight_counter_key(None))?;
+
+            assert_eq!(storage.get_next_flight_number(None), 2);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_flight_id_format_past_999() -> Result<()> {
+        let path = "/tmp/test_telemetry_id_format";
+        remove_store_files(path);
+
+        {
+            let format = FlightIdFormat { prefix: "2024-06-01_".to_string(), width: 4 };
+            let mut storage = TelemetryStorage::new(path)?.with_flight_id_format(format).with_flight_detection(INSTANT_TAKEOFF);
+            storage.store.put(TelemetryStorage::flight_counter_key(None), Value::Int(999));
+            for timestamp in [1000, 100_000] {
+                storage.save_packet(&airborne_packet(timestamp))?;
+            }
+
+            let ids: Vec<String> = storage.list_flights().into_iter().map(|f| f.flight_id).collect();
+            assert_eq!(ids, vec!["2024-06-01_0999", "2024-06-01_1000"]);
+
+            storage.store.delete(&TelemetryStorage::flight_counter_key(None))?;
+            assert_eq!(storage.get_next_flight_number(None), 1001);
+        }
+
+        // The default keeps three digits but still parses wider numbers
+        let format = FlightIdFormat::default();
+        assert_eq!(format.format(7), "flight_007");
+        assert_eq!(format.format(1000), "flight_1000");
+        assert_eq!(format.parse("flight_1000"), Some(1000));
+        assert_eq!(format.parse("flight_+5"), None);
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_altitude_disagreement_latches_warning() -> Result<()> {
+        let path = "/tmp/test_telemetry_sensor_warning";
+        remove_store_files(path);
+
+        {
+            let (alert_tx, mut alert_rx) = broadcast::channel(16);
+            let mut storage = TelemetryStorage::new(path)?.with_alerts(alert_tx);
+            let disagreeing = |timestamp| TelemetryPacket { altitude_baro: 120.0, ..airborne_packet(timestamp) };
+            
+            // A short glitch doesn't count
+            storage.save_packet(&airborne_packet(1000))?;
+            for i in 0..5 {
+                storage.save_packet(&disagreeing(1200 + i * 200))?;
+            }
+            storage.save_packet(&airborne_packet(2200))?;
+            assert!(!storage.get_flight("flight_001").unwrap().sensor_warning);
+            
+            for i in 0..12 {
+                storage.save_packet(&disagreeing(2400 + i * 200))?;
+            }
+            let alert = alert_rx.try_recv()?;
+            assert_eq!(alert.kind, AlertKind::SensorDisagreement);
+            assert_eq!(alert.value, 70.0);
+            assert!(alert_rx.try_recv().is_err());
+            
+            // Stays set after the sensors agree again
+            storage.save_packet(&airborne_packet(5000))?;
+            assert!(storage.get_flight("flight_001").unwrap().sensor_warning);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_takeoff_debounce_ignores_glitch() -> Result<()> {
+        let path = "/tmp/test_telemetry_takeoff_debounce";
+        remove_store_files(path);
+
+        {
+            let detection = FlightDetectionConfig { takeoff_confirm_ms: 1000, ..Default::default() };
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(detection);
+            let ground = |timestamp| TelemetryPacket {
+                latitude: 49.8728,
+                longitude: 8.6512,
+                battery_voltage: 16.8,
+                timestamp,
+                ..Default::default()
+            };
+            
+            // One GPS glitch between ground packets
+            for timestamp in [1000, 1200, 1400] {
+                storage.save_packet(&ground(timestamp))?;
+            }
+            storage.save_packet(&TelemetryPacket { altitude_gps: 50.0, ..ground(1600) })?;
+            for timestamp in [1800, 2000, 2200] {
+                storage.save_packet(&ground(timestamp))?;
+            }
+            assert!(storage.list_flights().is_empty());
+            
+            // A real takeoff starts the flight once it has lasted long enough
+            for timestamp in (3000..=4000).step_by(200) {
+                storage.save_packet(&airborne_packet(timestamp))?;
+            }
+            let flights = storage.list_flights();
+            assert_eq!(flights.len(), 1);
+            assert_eq!(flights[0].start_time, 3000);
+            assert_eq!(storage.get_flight_data(&flights[0].flight_id).len(), 6);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_closed_ends_flight() -> Result<()> {
+        let path = "/tmp/test_telemetry_stream_lost";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet_from(Some("drone_1"), &airborne_packet(1000))?;
+            storage.save_packet_from(Some("drone_2"), &airborne_packet(1000))?;
+            
+            // A failure leaves the flight to the gap timeout
+            storage.on_stream_lost(Some("drone_1"), StreamEnd::Failed)?;
+            assert_eq!(storage.active_flights(), 2);
+            
+            storage.on_stream_lost(Some("drone_1"), StreamEnd::Closed)?;
+            assert_eq!(storage.active_flights(), 1);
+            assert!(!storage.get_flight("drone_1:flight_001").unwrap().ended_normally);
+            assert!(storage.get_flight("drone_2:flight_001").unwrap().ended_normally);
+            
+            // The next packet after reconnecting starts a new flight
+            storage.save_packet_from(Some("drone_1"), &airborne_packet(3000))?;
+            assert!(storage.get_flight("drone_1:flight_002").is_some());
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_phase_durations_cover_flight() {
+        let mut flight = FlightMetadata {
+            start_time: 1000,
+            end_time: 61_000,
+            phase_timeline: vec![
+                (1000, "Taking Off".to_string()),
+                (4000, "Ascent".to_string()),
+                (20_000, "Cruise".to_string()),
+                (40_000, "Ascent".to_string()),
+                (45_000, "Descent".to_string()),
+                (63_000, "Landed".to_string()),
+            ],
+            ..Default::default()
+        };
+        let durations = phase_durations(&flight);
+        assert_eq!(durations["Taking Off"], 3000);
+        assert_eq!(durations["Ascent"], 16_000 + 5000);
+        assert_eq!(durations["Cruise"], 20_000);
+        // Open until the end of the flight, and "Landed" comes after it
+        assert_eq!(durations["Descent"], 16_000);
+        assert!(!durations.contains_key("Landed"));
+        assert_eq!(durations.values().sum::<u64>(), flight.end_time - flight.start_time);
+
+        flight.phase_timeline = vec![(1000, "Cruise".to_string())];
+        assert_eq!(phase_durations(&flight), HashMap::from([("Cruise".to_string(), 60_000)]));
+        flight.phase_timeline.clear();
+        assert!(phase_durations(&flight).is_empty());
+    }
+
+    #[test]
+    fn test_clock_regression_keeps_duration_sane() -> Result<()> {
+        let mut storage = TelemetryStorage::new_in_memory();
+        for i in 0..10 {
+            storage.save_packet(&airborne_packet(100_000 + i * 200))?;
+        }
+        // Reordered by a second, from before the flight started
+        storage.save_packet(&airborne_packet(99_000))?;
+        let flight = storage.get_flight("flight_001").unwrap();
+        assert_eq!(flight.start_time, 100_000);
+        assert_eq!(flight.end_time, 101_800);
+        assert_eq!(flight.duration_secs, 1);
+
+        // The source restarted its clock
+        for i in 0..10 {
+            storage.save_packet(&airborne_packet(1000 + i * 200))?;
+        }
+        let old = storage.get_flight("flight_001").unwrap();
+        assert!(!old.ended_normally);
+        assert_eq!(old.duration_secs, 1);
+        let new = storage.get_flight("flight_002").unwrap();
+        assert_eq!(storage.get_current_flight_id().as_deref(), Some("flight_002"));
+        assert!(new.start_time < 3000 && new.duration_secs < 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_energy_of_constant_power_flight() -> Result<()> {
+        // 150 W for ten minutes, sampled unevenly
+        let mut timestamps = vec![0];
+        let mut t = 0;
+        for i in 0.. {
+            t += [100, 250, 1000, 40][i % 4];
+            if t >= 600_000 {
+                break;
+            }
+            timestamps.push(t);
+        }
+        timestamps.push(600_000);
+        let packets: Vec<TelemetryPacket> = timestamps.iter()
+            .map(|&timestamp| TelemetryPacket { battery_power: 150.0, ..airborne_packet(timestamp) })
+            .collect();
+        assert!((energy_wh(&packets) - 25.0).abs() < 1e-6);
+        assert_eq!(energy_wh(&packets[..1]), 0.0);
+
+        let mut storage = TelemetryStorage::new_in_memory();
+        for packet in &packets {
+            storage.save_packet(&TelemetryPacket { timestamp: packet.timestamp + 1000, ..*packet })?;
+        }
+        for timestamp in [601_200, 606_200] {
+            storage.save_packet(&TelemetryPacket { latitude: 49.8728, longitude: 8.6512, timestamp, ..Default::default() })?;
+        }
+        let flight = storage.get_flight("flight_001").unwrap();
+        assert_eq!(flight.current_status, "Landed");
+        assert!(flight.energy_wh > 24.0);
+        assert!((flight.energy_wh - energy_wh(&storage.get_flight_data("flight_001"))).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompute_restores_metadata() -> Result<()> {
+        let path = "/tmp/test_telemetry_recompute";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            for i in 0..20 {
+                storage.save_packet(&TelemetryPacket {
+                    latitude: 49.8728 + i as f64 * 0.001,
+                    altitude_gps: 50.0 + i as f32,
+                    battery_voltage: 16.8 - i as f32 * 0.01,
+                    ..airborne_packet(1000 + i * 200)
+                })?;
+            }
+            // Touch down and confirm the landing
+            for timestamp in [5000, 10000] {
+                storage.save_packet(&TelemetryPacket {
+                    latitude: 49.8918,
+                    longitude: 8.6512,
+                    battery_voltage: 16.5,
+                    timestamp,
+                    ..Default::default()
+                })?;
+            }
+            let original = storage.get_flight("flight_001").unwrap();
+            assert_eq!(original.current_status, "Landed");
+
+            let mut corrupted = original.clone();
+            corrupted.distance_km = 999.0;
+            corrupted.duration_secs = 0;
+            corrupted.max_altitude = 0.0;
+            corrupted.packet_count = 3;
+            corrupted.phase_timeline.clear();
+            storage.store.put(Key::String("flight:flight_001".into()), Value::String(serde_json::to_string(&corrupted)?));
+
+            let recomputed = storage.recompute_flight("flight_001")?.unwrap();
+            assert_eq!(recomputed.packet_count, original.packet_count);
+            assert_eq!(recomputed.duration_secs, original.duration_secs);
+            assert_eq!(recomputed.max_altitude, 69.0);
+            assert!((recomputed.distance_km - original.distance_km).abs() < 1e-9);
+            assert_eq!(recomputed.phase_timeline[0], original.phase_timeline[0]);
+            assert_eq!(storage.get_flight("flight_001").unwrap().distance_km, recomputed.distance_km);
+            assert!(storage.recompute_flight("flight_999")?.is_none());
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_check_detects_altered_packet() -> Result<()> {
+        let path = "/tmp/test_telemetry_integrity";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            for i in 0..10 {
+                storage.save_packet(&airborne_packet(1000 + i * 200))?;
+            }
+            // Chained live while flying
+            assert!(storage.verify_integrity("flight_001").unwrap().valid);
+            for timestamp in [5000, 10000] {
+                storage.save_packet(&TelemetryPacket { timestamp, ..Default::default() })?;
+            }
+            let report = storage.verify_integrity("flight_001").unwrap();
+            assert!(report.valid);
+            assert_eq!(report.stored_hash.as_deref(), Some(report.computed_hash.as_str()));
+
+            let key = Key::String("telem:flight_001:1400".into());
+            let mut packet = storage.get_flight_data("flight_001")[2];
+            packet.altitude_gps += 1.0;
+            storage.store.put(key, Value::String(serde_json::to_string(&packet)?));
+            let report = storage.verify_integrity("flight_001").unwrap();
+            assert!(!report.valid);
+            assert_ne!(report.stored_hash.as_deref(), Some(report.computed_hash.as_str()));
+            assert!(storage.verify_integrity("flight_999").is_none());
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_survives_flight_end_and_merge() -> Result<()> {
+        let detection = FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 };
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(detection);
+        let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+        for start in [10_000, 20_000] {
+            for i in 0..5 {
+                storage.save_packet(&airborne_packet(start + i * 200))?;
+            }
+            // Arrives late, behind a newer packet
+            storage.save_packet(&airborne_packet(start + 1100))?;
+            storage.save_packet(&airborne_packet(start + 1000))?;
+            storage.save_packet(&parked.with_timestamp(start + 3000))?;
+            storage.save_packet(&parked.with_timestamp(start + 4000))?;
+        }
+        assert!(storage.verify_integrity("flight_001").unwrap().valid);
+
+        // Altered mid-flight, the end of the flight mustn't accept it
+        storage.save_packet(&airborne_packet(30_000))?;
+        storage.save_packet(&airborne_packet(30_200))?;
+        let key = Key::String("telem:flight_003:30000".into());
+        let packet = TelemetryPacket { altitude_gps: 500.0, ..airborne_packet(30_000) };
+        storage.store.put(key, Value::String(serde_json::to_string(&packet)?));
+        storage.save_packet(&parked.with_timestamp(33_000))?;
+        storage.save_packet(&parked.with_timestamp(34_000))?;
+        assert_eq!(storage.get_current_flight_id(), None);
+        assert!(!storage.verify_integrity("flight_003").unwrap().valid);
+
+        storage.merge_flights("flight_001", "flight_002")?;
+        assert!(storage.verify_integrity("flight_001").unwrap().valid);
+        storage.merge_flights("flight_001", "flight_003")?;
+        assert!(!storage.verify_integrity("flight_001").unwrap().valid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_chain_catches_swapped_packets() -> Result<()> {
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(INSTANT_TAKEOFF);
+        for i in 0..5 {
+            storage.save_packet(&TelemetryPacket { packet_sequence: i as u32, ..airborne_packet(1000 + i * 200) })?;
+        }
+        assert!(storage.verify_integrity("flight_001").unwrap().valid);
+
+        // Same packets, different order: a sum of packet hashes wouldn't notice
+        let first = Key::String("telem:flight_001:1200".into());
+        let second = Key::String("telem:flight_001:1400".into());
+        let a = storage.store.get_str(&first)?.to_string();
+        let b = storage.store.get_str(&second)?.to_string();
+        storage.store.put(first, Value::String(b));
+        storage.store.put(second, Value::String(a));
+        assert!(!storage.verify_integrity("flight_001").unwrap().valid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_integrity_chain_with_repeated_packet() -> Result<()> {
+        let mut storage = TelemetryStorage::new_in_memory().with_flight_detection(INSTANT_TAKEOFF);
+        storage.save_packet(&airborne_packet(1000))?;
+        // Delivered three times: the copy under the sequence key is overwritten
+        for _ in 0..3 {
+            storage.save_packet(&TelemetryPacket { packet_sequence: 5, ..airborne_packet(1200) })?;
+        }
+        storage.save_packet(&airborne_packet(1400))?;
+        assert_eq!(storage.get_flight_data("flight_001").len(), 4);
+        let report = storage.verify_integrity("flight_001").unwrap();
+        assert!(report.valid, "{:?}", report);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_gps_not_stored() -> Result<()> {
+        let path = "/tmp/test_telemetry_invalid_gps";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_invalid_gps_skipped().with_flight_detection(INSTANT_TAKEOFF);
+            let fixed = |timestamp| TelemetryPacket { gps_fix_type: 3, ..airborne_packet(timestamp) };
+            storage.save_packet(&fixed(1000))?;
+            storage.save_packet(&fixed(1200))?;
+            storage.save_packet(&TelemetryPacket { latitude: 0.0, longitude: 0.0, gps_fix_type: 0, ..airborne_packet(1400) })?;
+            storage.save_packet(&fixed(1600))?;
+            
+            let timestamps: Vec<u64> = storage.get_flight_data("flight_001").iter().map(|p| p.timestamp).collect();
+            assert_eq!(timestamps, vec![1000, 1200, 1600]);
+            let flights = storage.list_flights();
+            assert_eq!(flights.len(), 1);
+            assert_eq!(flights[0].packet_count, 3);
+            // No detour to 0,0 and back
+            assert_eq!(flights[0].distance_km, 0.0);
+            assert_eq!(storage.get_current_flight_id().as_deref(), Some("flight_001"));
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_flights() -> Result<()> {
+        let path = "/tmp/test_telemetry_merge";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            // A long dropout splits one flight in two
+            for i in 0..10 {
+                storage.save_packet(&airborne_packet(1000 + i * 200))?;
+            }
+            for i in 0..15 {
+                storage.save_packet(&airborne_packet(100_000 + i * 200))?;
+            }
+            
+            let rejected = storage.merge_flights("flight_001", "flight_002").unwrap_err();
+            assert_eq!(rejected.downcast_ref(), Some(&MergeRejected::InProgress("flight_002".into())));
+            storage.on_stream_lost(None, StreamEnd::Closed)?;
+            let rejected = storage.merge_flights("flight_001", "flight_001").unwrap_err();
+            assert_eq!(rejected.downcast_ref(), Some(&MergeRejected::SameFlight));
+            
+            let merged = storage.merge_flights("flight_001", "flight_002")?;
+            assert_eq!(merged.packet_count, 25);
+            assert_eq!(merged.start_time, 1000);
+            assert_eq!(merged.end_time, 102_800);
+            assert!(!merged.ended_normally);
+            assert_eq!(storage.get_flight_data("flight_001").len(), 25);
+            assert!(storage.get_flight("flight_002").is_none());
+            assert!(storage.get_flight_data("flight_002").is_empty());
+            assert_eq!(storage.list_flights().len(), 1);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_packets_read_back_identical() -> Result<()> {
+        let path = "/tmp/test_telemetry_binary";
+        remove_store_files(path);
+        let sent: Vec<TelemetryPacket> = (0..20)
+            .map(|i| TelemetryPacket {
+                altitude_gps: 50.0 + i as f32 * 0.37,
+                battery_power: 148.25 + i as f32,
+                packet_sequence: i as u32,
+                ..airborne_packet(1000 + i * 200)
+            })
+            .collect();
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_binary_packets();
+            for packet in &sent {
+                storage.save_packet(packet)?;
+            }
+            assert_eq!(storage.type_histogram().bytes.count, storage.get_flight("flight_001").unwrap().packet_count);
+            storage.close()?;
+        }
+
+        // Read without the option, next to a packet stored as JSON
+        let mut storage = TelemetryStorage::new(path)?;
+        let late = airborne_packet(9000);
+        storage.store.put(Key::String("telem:flight_001:9000".into()), Value::String(serde_json::to_string(&late)?));
+        let stored = storage.get_flight_data("flight_001");
+        assert_eq!(stored.len(), sent.len() + 1);
+        for (stored, sent) in stored.iter().zip(sent.iter().chain([&late])) {
+            assert_eq!(stored.to_bytes(), sent.to_bytes());
+        }
+        assert_eq!(storage.get_flight_data_page("flight_001", 5, 3).0[0].to_bytes(), sent[5].to_bytes());
+        drop(storage);
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_derived_vertical_speed() -> Result<()> {
+        let path = "/tmp/test_telemetry_vspeed";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_derived_vertical_speed();
+            storage.save_packet(&TelemetryPacket { vertical_speed: 9.0, ..airborne_packet(1000) })?;
+            storage.save_packet(&TelemetryPacket { altitude_baro: 60.0, ..airborne_packet(3000) })?;
+            // Same timestamp again must not divide by zero
+            storage.save_packet(&TelemetryPacket { altitude_baro: 70.0, packet_sequence: 1, ..airborne_packet(3000) })?;
+
+            let data = storage.get_flight_data("flight_001");
+            let speeds: Vec<f32> = data.iter().map(|p| p.vertical_speed).collect();
+            assert_eq!(speeds.len(), 3);
+            assert_eq!(speeds[0], 9.0);
+            assert!((speeds[1] - 5.0).abs() < 1e-6);
+            assert!((speeds[2] - 5.0).abs() < 1e-6);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_gap_continues_flight() -> Result<()> {
+        let path = "/tmp/test_telemetry_gap";
+        remove_store_files(path);
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            storage.save_packet(&airborne_packet(1500))?;
+            // 10s dropout, reappearing ~1.1 km away
+            storage.save_packet(&TelemetryPacket { latitude: 49.8828, ..airborne_packet(11500) })?;
+            storage.save_packet(&TelemetryPacket { latitude: 49.8828, ..airborne_packet(12000) })?;
+
+            let flights = storage.list_flights();
+            assert_eq!(flights.len(), 1);
+
+            let flight = &flights[0];
+            assert_eq!(flight.packet_count, 4);
+            assert_eq!(flight.gap_count, 1);
+            assert_eq!(flight.total_gap_ms, 10000);
+            assert!(flight.distance_km < 0.001);
+        }
+
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_completed_flights_leave_memory() -> Result<()> {
+        let path = "/tmp/test_telemetry_retention";
+        remove_store_files(path);
+        fs::remove_dir_all(format!("{}_archive", path)).ok();
+
+        let ground = |timestamp| TelemetryPacket {
+            altitude_gps: 0.0,
+            altitude_baro: 0.0,
+            ground_speed: 0.0,
+            ..airborne_packet(timestamp)
+        };
+        let packet_size = serde_json::to_string(&airborne_packet(0))?.len() + 64;
+
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_max_in_memory_packets(50);
+            let mut t = 1000;
+            let mut peak = 0;
+            for _ in 0..8 {
+                for _ in 0..30 {
+                    storage.save_packet(&airborne_packet(t))?;
+                    t += 500;
+                }
+                for _ in 0..12 {
+                    storage.save_packet(&ground(t))?;
+                    t += 500;
+                }
+                peak = peak.max(storage.store.data_size());
+            }
+
+            assert_eq!(storage.list_flights().len(), 8);
+            // 8 flights of ~41 packets never sit in memory together
+            assert!(peak < 150 * packet_size, "peak {} bytes", peak);
+
+            let first = storage.get_flight("flight_001").unwrap();
+            assert_eq!(storage.get_flight_data("flight_001").len(), first.packet_count);
+
+            storage.delete_flight("flight_001")?;
+            assert!(storage.get_flight_data("flight_001").is_empty());
+        }
+
+        remove_store_files(path);
+        fs::remove_dir_all(format!("{}_archive", path)).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_eviction_does_not_fail_ingest() -> Result<()> {
+        let path = "/tmp/test_telemetry_eviction_fails";
+        remove_store_files(path);
+        // A file where the archive directory should go
+        let archive = format!("{}_archive", path);
+        fs::remove_dir_all(&archive).ok();
+        fs::write(&archive, b"in the way")?;
+
+        {
+            let mut storage = TelemetryStorage::new(path)?
+                .with_max_in_memory_packets(5)
+                .with_flight_detection(FlightDetectionConfig { takeoff_confirm_ms: 0, landing_confirm_ms: 1000 });
+            let parked = airborne_packet(0).with_altitude(0.0).with_speed(0.0);
+            for i in 0..5 {
+                storage.save_packet(&airborne_packet(1000 + i * 200))?;
+            }
+            storage.save_packet(&parked.with_timestamp(3000))?;
+            storage.save_packet(&parked.with_timestamp(4000))?;
+            // Passing the threshold tries to archive flight_001 and fails
+            for i in 0..8 {
+                storage.save_packet(&airborne_packet(10_000 + i * 200))?;
+            }
+
+            // Nothing was archived, and the next attempt waits for more packets
+            assert_eq!(storage.get_flight_data("flight_001").len(), 6);
+            assert_eq!(storage.packets_in_memory, 14);
+            assert!(storage.eviction_threshold > storage.packets_in_memory);
+        }
+
+        remove_store_files(path);
+        fs::remove_file(&archive).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_parse_matches_sequential() -> Result<()> {
+        let mut records: Vec<(Key, PacketRecord)> = (0..2000u64).rev()
+            .map(|i| Ok((Key::Int(i as i64), PacketRecord::Json(serde_json::to_string(&airborne_packet(1000 + i * 100))?))))
+            .collect::<Result<_>>()?;
+        records.push((Key::Int(-1), PacketRecord::Json("not json".to_string())));
+
+        let mut sequential: Vec<u64> = records.iter()
+            .filter_map(|(_, record)| record.parse().ok())
+            .map(|p| p.timestamp)
+            .collect();
+        let (packets, skipped) = parse_packets(&records);
+        assert_eq!(skipped, 1);
+        let mut parallel: Vec<u64> = packets.iter().map(|p| p.timestamp).collect();
+        sequential.sort();
+        parallel.sort();
+        assert_eq!(parallel, sequential);
+
+        let path = "/tmp/test_telemetry_parallel";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            for i in 0..500 {
+                storage.save_packet(&airborne_packet(1000 + i * 100))?;
+            }
+            let flight_id = storage.get_current_flight_id().unwrap();
+            let packets = storage.get_flight_data(&flight_id);
+            assert_eq!(packets.len(), 500);
+            assert!(packets.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_exposes_telem_keys() -> Result<()> {
+        let path = "/tmp/test_telemetry_store_ref";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            for i in 0..5 {
+                storage.save_packet(&airborne_packet(1000 + i * 100))?;
+            }
+            let telem_keys = storage.store().keys()
+                .filter(|k| matches!(k, Key::String(s) if s.starts_with("telem:")))
+                .count();
+            assert_eq!(telem_keys, 5);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_same_timestamp_packets_are_kept() -> Result<()> {
+        let path = "/tmp/test_telemetry_same_timestamp";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 7, ..airborne_packet(1100) })?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 8, ..airborne_packet(1100) })?;
+            storage.save_packet(&airborne_packet(1200))?;
+
+            let flight_id = storage.get_current_flight_id().unwrap();
+            let packets = storage.get_flight_data(&flight_id);
+            let order: Vec<(u64, u32)> = packets.iter().map(|p| (p.timestamp, p.packet_sequence)).collect();
+            assert_eq!(order, vec![(1000, 0), (1100, 7), (1100, 8), (1200, 0)]);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pages_and_full_read_agree_on_out_of_order_sequence() -> Result<()> {
+        let path = "/tmp/test_telemetry_page_order";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            // The later arrival at 1100 carries the lower sequence number
+            storage.save_packet(&TelemetryPacket { packet_sequence: 8, ..airborne_packet(1100) })?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 7, ..airborne_packet(1100) })?;
+
+            let flight_id = storage.get_current_flight_id().unwrap();
+            let full: Vec<u32> = storage.get_flight_data(&flight_id).iter().map(|p| p.packet_sequence).collect();
+            let mut paged = Vec::new();
+            for offset in 0..3 {
+                let (packets, _) = storage.get_flight_data_page(&flight_id, offset, 1);
+                paged.extend(packets.iter().map(|p| p.packet_sequence));
+            }
+            assert_eq!(paged, full);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_data_after_cursor() -> Result<()> {
+        let path = "/tmp/test_telemetry_after_cursor";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            storage.save_packet(&airborne_packet(1000))?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 8, ..airborne_packet(1100) })?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 7, ..airborne_packet(1100) })?;
+            storage.save_packet(&TelemetryPacket { packet_sequence: 9, ..airborne_packet(1200) })?;
+
+            let flight_id = storage.get_current_flight_id().unwrap();
+            let sequences = |packets: Vec<TelemetryPacket>| packets.iter().map(|p| p.packet_sequence).collect::<Vec<_>>();
+            let (packets, total, offset) = storage.get_flight_data_after(&flight_id, (1100, 8), 10);
+            assert_eq!((sequences(packets), total, offset), (vec![7, 9], 4, 2));
+            let (packets, _, offset) = storage.get_flight_data_after(&flight_id, (1100, 7), 10);
+            assert_eq!((sequences(packets), offset), (vec![9], 3));
+            let (packets, _, offset) = storage.get_flight_data_after(&flight_id, (1100, u32::MAX), 10);
+            assert_eq!((sequences(packets), offset), (vec![9], 3));
+            let (packets, _, offset) = storage.get_flight_data_after(&flight_id, (1200, 9), 10);
+            assert_eq!((sequences(packets), offset), (vec![], 4));
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_batch_matches_single_saves() -> Result<()> {
+        let single_path = "/tmp/test_telemetry_single";
+        let batch_path = "/tmp/test_telemetry_batch";
+        remove_store_files(single_path);
+        remove_store_files(batch_path);
+        {
+            let packets = [airborne_packet(1000), airborne_packet(1100), battery_packet(1200, 15.0)];
+
+            let mut single = TelemetryStorage::new(single_path)?.with_flight_detection(INSTANT_TAKEOFF);
+            for packet in &packets {
+                single.save_packet(packet)?;
+            }
+            let mut batch = TelemetryStorage::new(batch_path)?.with_flight_detection(INSTANT_TAKEOFF);
+            assert!(batch.ingest_batch(None, &packets).is_empty());
+
+            // Everything but the wall-clock start has to match
+            let flights = |storage: &TelemetryStorage| -> Result<serde_json::Value> {
+                let flights: Vec<FlightMetadata> = storage.list_flights().into_iter()
+                    .map(|f| FlightMetadata { start_epoch_ms: None, ..f })
+                    .collect();
+                Ok(serde_json::to_value(flights)?)
+            };
+            assert_eq!(flights(&batch)?, flights(&single)?);
+            assert_eq!(
+                serde_json::to_value(batch.get_flight_data("flight_001"))?,
+                serde_json::to_value(single.get_flight_data("flight_001"))?,
+            );
+            assert_eq!(batch.get_flight_data("flight_001").len(), 3);
+        }
+        remove_store_files(single_path);
+        remove_store_files(batch_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_packets_are_counted() -> Result<()> {
+        let path = "/tmp/test_telemetry_unreadable";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?.with_flight_detection(INSTANT_TAKEOFF);
+            for i in 0..5 {
+                storage.save_packet(&airborne_packet(1000 + i * 100))?;
+            }
+            storage.store.put(Key::String("telem:flight_001:1100".into()), Value::String("{ truncated".into()));
+            storage.store.put(Key::String("telem:flight_001:1200".into()), Value::Int(7));
+
+            let (packets, skipped) = storage.read_flight_data("flight_001");
+            assert_eq!(skipped, 2);
+            let timestamps: Vec<u64> = packets.iter().map(|p| p.timestamp).collect();
+            assert_eq!(timestamps, vec![1000, 1300, 1400]);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_phase_timeline() -> Result<()> {
+        let path = "/tmp/test_telemetry_phase_timeline";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            let at = |timestamp, altitude_gps, altitude_baro, ground_speed, vertical_speed| TelemetryPacket {
+                altitude_gps,
+                altitude_baro,
+                ground_speed,
+                vertical_speed,
+                ..airborne_packet(timestamp)
+            };
+            storage.save_packet(&at(1000, 6.0, 1.0, 5.0, 0.0))?;      // Taking Off
+            storage.save_packet(&at(2000, 150.0, 150.0, 20.0, 0.0))?; // Cruise
+            storage.save_packet(&at(2500, 150.0, 150.0, 20.0, 0.0))?;
+            storage.save_packet(&at(3000, 10.0, 10.0, 10.0, -2.0))?;  // Landing
+            storage.save_packet(&at(4000, 0.0, 0.0, 0.0, 0.0))?;      // On Ground
+            storage.save_packet(&at(10000, 0.0, 0.0, 0.0, 0.0))?;     // Landed
+
+            let timeline = storage.get_flight("flight_001").unwrap().phase_timeline;
+            let expected = [(1000, "Taking Off"), (2000, "Cruise"), (3000, "Landing"), (4000, "On Ground"), (10000, "Landed")];
+            let expected: Vec<(u64, String)> = expected.iter().map(|(t, p)| (*t, p.to_string())).collect();
+            assert_eq!(timeline, expected);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_phase_entry_voltages() -> Result<()> {
+        let path = "/tmp/test_telemetry_phase_voltages";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            let at = |timestamp, altitude_gps, altitude_baro, ground_speed, battery_voltage| TelemetryPacket {
+                altitude_gps,
+                altitude_baro,
+                ground_speed,
+                battery_voltage,
+                ..airborne_packet(timestamp)
+            };
+            storage.save_packet(&at(1000, 6.0, 1.0, 5.0, 16.8))?;      // Taking Off
+            storage.save_packet(&at(1500, 6.0, 1.0, 5.0, 16.7))?;
+            storage.save_packet(&at(2000, 150.0, 150.0, 20.0, 16.4))?; // Cruise
+            storage.save_packet(&at(2500, 150.0, 150.0, 20.0, 16.1))?;
+            storage.save_packet(&at(3000, 0.0, 0.0, 0.0, 15.9))?;      // On Ground
+
+            let voltages = storage.get_flight("flight_001").unwrap().phase_entry_voltages;
+            let expected = [("Taking Off", 16.8), ("Cruise", 16.4), ("On Ground", 15.9)];
+            let expected: Vec<(String, f32)> = expected.iter().map(|(p, v)| (p.to_string(), *v)).collect();
+            assert_eq!(voltages, expected);
+        }
+        remove_store_files(path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_flight_data_pages() -> Result<()> {
+        let path = "/tmp/test_telemetry_pages";
+        remove_store_files(path);
+        {
+            let mut storage = TelemetryStorage::new(path)?;
+            // Every timestamp is shared by two packets
+            for i in 0..100 {
+                storage.save_packet(&TelemetryPacket { packet_sequence: i as u32, ..airborne_packet(1000 + (i / 2) * 100) })?;
+            }
+
+            let mut seen = Vec::new();
+            for page in 0..10 {
+                let (packets, total) = storage.get_flight_data_page("flight_001", page * 10, 10);
+                assert_eq!(total, 100);
+                assert_eq!(packets.len(), 10);
+                seen.extend(packets.iter().map(|p| p.packet_sequence));
+            }
+            let expected: Vec<u32> = storage.get_flight_data("flight_001").iter().map(|p| p.packet_sequence).collect();
+            assert_eq!(seen, expected);
+            assert_eq!(seen, (0..100).collect::<Vec<u32>>());
+
+            // A new packet drops the cached order
+            storage.save_packet(&TelemetryPacket { packet_sequence: 100, ..airborne_packet(20000) })?;
+            let (last, total) = storage.get_flight_data_page("flight_001", 100, 10);
+            assert_eq!(total, 101);
+            assert_eq!(last[0].packet_sequence, 100);
+        }
+        remove_store_files(path);
+        Ok(())
     }
 }
@@ -1,148 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-/// Telemetry packet matching ESP32 LoRa hardware format
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[repr(C)]
-pub struct TelemetryPacket {
-    // GPS
-    pub latitude: f64,
-    pub longitude: f64,
-    pub altitude_gps: f32,
-    pub ground_speed: f32,
-    pub heading: f32,
-    pub num_satellites: u8,
-    pub gps_fix_type: u8,
-    
-    // Barometer
-    pub altitude_baro: f32,
-    pub vertical_speed: f32,
-    pub temperature: f32,
-    
-    // IMU
-    pub roll: f32,
-    pub pitch: f32,
-    pub yaw: f32,
-    pub gyro_x: f32,
-    pub gyro_y: f32,
-    pub gyro_z: f32,
-    pub accel_x: f32,
-    pub accel_y: f32,
-    pub accel_z: f32,
-    
-    // Power
-    pub battery_voltage: f32,
-    pub battery_current: f32,
-    pub battery_power: f32,
-    pub battery_mah_used: f32,
-    
-    // Communication
-    pub rssi: i16,
-    pub snr: f32,
-    
-    // System
-    pub timestamp: u64,
-    pub packet_sequence: u32,
-    pub system_status: u8,
-}
+pub use telemetry_proto::TelemetryPacket;
 
-impl TelemetryPacket {
-    /// Deserialize from binary format (little-endian)
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
-        if bytes.len() < 113 {
-            return Err("Insufficient bytes for telemetry packet");
-        }
-        
-        let mut offset = 0;
-        
-        macro_rules! read_f64 {
-            () => {{
-                let val = f64::from_le_bytes(bytes[offset..offset+8].try_into().unwrap());
-                offset += 8;
-                val
-            }};
-        }
-        
-        macro_rules! read_f32 {
-            () => {{
-                let val = f32::from_le_bytes(bytes[offset..offset+4].try_into().unwrap());
-                offset += 4;
-                val
-            }};
-        }
-        
-        macro_rules! read_u8 {
-            () => {{
-                let val = bytes[offset];
-                offset += 1;
-                val
-            }};
-        }
-        
-        macro_rules! read_i16 {
-            () => {{
-                let val = i16::from_le_bytes(bytes[offset..offset+2].try_into().unwrap());
-                offset += 2;
-                val
-            }};
-        }
-        
-        macro_rules! read_u64 {
-            () => {{
-                let val = u64::from_le_bytes(bytes[offset..offset+8].try_into().unwrap());
-                offset += 8;
-                val
-            }};
-        }
-        
-        macro_rules! read_u32 {
-            () => {{
-                let val = u32::from_le_bytes(bytes[offset..offset+4].try_into().unwrap());
-                offset += 4;
-                val
-            }};
-        }
-        
-        Ok(TelemetryPacket {
-            latitude: read_f64!(),
-            longitude: read_f64!(),
-            altitude_gps: read_f32!(),
-            ground_speed: read_f32!(),
-            heading: read_f32!(),
-            num_satellites: read_u8!(),
-            gps_fix_type: read_u8!(),
-            
-            altitude_baro: read_f32!(),
-            vertical_speed: read_f32!(),
-            temperature: read_f32!(),
-            
-            roll: read_f32!(),
-            pitch: read_f32!(),
-            yaw: read_f32!(),
-            gyro_x: read_f32!(),
-            gyro_y: read_f32!(),
-            gyro_z: read_f32!(),
-            accel_x: read_f32!(),
-            accel_y: read_f32!(),
-            accel_z: read_f32!(),
-            
-            battery_voltage: read_f32!(),
-            battery_current: read_f32!(),
-            battery_power: read_f32!(),
-            battery_mah_used: read_f32!(),
-            
-            rssi: read_i16!(),
-            snr: read_f32!(),
-            
-            timestamp: read_u64!(),
-            packet_sequence: read_u32!(),
-            system_status: read_u8!(),
-        })
-    }
+/// Telemetry packet tagged with the source it was received from
+#[derive(Debug, Clone, Serialize)]
+pub struct SourcedPacket {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    #[serde(flatten)]
+    pub packet: TelemetryPacket,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FlightMetadata {
     pub flight_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
     pub start_time: u64,
     pub end_time: u64,
     pub duration_secs: u64,
@@ -156,63 +30,126 @@ pub struct FlightMetadata {
     pub min_battery: f32,
     pub ended_normally: bool,
     pub current_status: String,
+    /// Link dropouts survived without ending the flight
+    #[serde(default)]
+    pub gap_count: u32,
+    #[serde(default)]
+    pub total_gap_ms: u64,
+    /// GPS and baro altitude disagreed for a sustained stretch
+    #[serde(default)]
+    pub sensor_warning: bool,
+    /// (timestamp, phase) at each phase change, ending with "Landed"
+    #[serde(default)]
+    pub phase_timeline: Vec<(u64, String)>,
+    /// (phase, battery voltage) on entering each phase, in flight order
+    #[serde(default)]
+    pub phase_entry_voltages: Vec<(String, f32)>,
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Battery energy drawn, integrated from `battery_power` once the flight ends
+    #[serde(default)]
+    pub energy_wh: f64,
+    /// Milliseconds spent in each phase, from the timeline once the flight ends
+    #[serde(default)]
+    pub phase_durations: HashMap<String, u64>,
+    /// Hex xxHash64 chain over the stored packets, see `verify_integrity`
+    #[serde(default)]
+    pub integrity_hash: Option<String>,
+    /// Wall-clock ms since the Unix epoch at `start_time`. None for imports
+    /// and flights recorded before it was kept.
+    #[serde(default)]
+    pub start_epoch_ms: Option<u64>,
 }
 
-impl TelemetryPacket {
-    /// Determine flight phase from telemetry data
-    pub fn get_flight_phase(&self) -> &'static str {
-        const GROUND_ALTITUDE: f32 = 2.0;      // Consider on ground if below 2m
-        const CRUISE_ALTITUDE: f32 = 140.0;     // Cruise altitude threshold
-        const TAKEOFF_SPEED: f32 = 3.0;         // Speed threshold for takeoff roll
-        const CLIMB_RATE: f32 = 0.8;            // Minimum climb rate for ascent
-        const DESCENT_RATE: f32 = -0.8;         // Descent rate threshold
-        
-        let is_on_ground = self.altitude_baro < GROUND_ALTITUDE;
-        let is_moving = self.ground_speed >= TAKEOFF_SPEED;
-        let is_climbing = self.vertical_speed > CLIMB_RATE;
-        let is_descending = self.vertical_speed < DESCENT_RATE;
-        
-        // On Ground: stationary or slow movement
-        if is_on_ground && !is_moving {
-            return "On Ground";
-        }
-        
-        // Taking Off: on ground, accelerating
-        if is_on_ground && is_moving {
-            return "Taking Off";
-        }
-        
-        // Landing: low altitude, descending
-        if self.altitude_baro < 20.0 && is_descending {
-            return "Landing";
-        }
-        
-        // Ascent: airborne and climbing
-        if !is_on_ground && is_climbing && self.altitude_baro < CRUISE_ALTITUDE {
-            return "Ascent";
-        }
-        
-        // Cruise: at or near cruise altitude, level flight
-        if self.altitude_baro >= CRUISE_ALTITUDE && !is_climbing && !is_descending {
-            return "Cruise";
-        }
-        
-        // Descent: descending from altitude
-        if is_descending && self.altitude_baro > 20.0 {
-            return "Descent";
-        }
-        
-        // Still climbing to cruise
-        if is_climbing {
-            return "Ascent";
-        }
-        
-        // Default for airborne but not clearly in a phase
-        if !is_on_ground {
-            return "Cruise";
-        }
-        
-        "On Ground"
+/// Stored integrity hash of a flight against one recomputed from its packets
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    pub flight_id: String,
+    /// None for flights recorded before hashing was added
+    pub stored_hash: Option<String>,
+    pub computed_hash: String,
+    pub valid: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    LowBattery,
+    GeofenceBreach,
+    GeofenceReturn,
+    SensorDisagreement,
+}
+
+/// Operator alert raised while processing a flight
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub flight_id: String,
+    pub kind: AlertKind,
+    pub value: f64,
+    pub timestamp: u64,
+}
+
+/// Flight lifecycle change, for subscribers that don't want to poll
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum FlightEvent {
+    Started { id: String },
+    /// Landed. `normally` is always true, a flight cut short ends with
+    /// `Catastrophic` instead.
+    Ended { id: String, normally: bool },
+    /// Stream lost or timed out mid-flight, or the source's clock reset
+    Catastrophic { id: String },
+}
+
+/// Circular area flights are expected to stay within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geofence {
+    pub center_lat: f64,
+    pub center_lon: f64,
+    pub radius_km: f64,
+}
+
+/// How long takeoff and landing conditions must hold before they count
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlightDetectionConfig {
+    /// Airborne this long before a flight starts, 0 starts on the first packet.
+    /// The flight still begins at the first airborne packet.
+    pub takeoff_confirm_ms: u64,
+    /// On the ground this long before a flight ends
+    pub landing_confirm_ms: u64,
+}
+
+impl Default for FlightDetectionConfig {
+    fn default() -> Self {
+        Self { takeoff_confirm_ms: 1000, landing_confirm_ms: 5000 }
+    }
+}
+
+/// How flight numbers become flight ids, `flight_001` by default
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightIdFormat {
+    pub prefix: String,
+    /// Zero-pad numbers to this many digits; wider numbers are kept whole
+    pub width: usize,
+}
+
+impl Default for FlightIdFormat {
+    fn default() -> Self {
+        Self { prefix: "flight_".to_string(), width: 3 }
     }
 }
 
+impl FlightIdFormat {
+    pub fn format(&self, number: usize) -> String {
+        format!("{}{:0width$}", self.prefix, number, width = self.width)
+    }
+    
+    /// Flight number of an id written by `format`
+    pub fn parse(&self, flight_name: &str) -> Option<usize> {
+        let digits = flight_name.strip_prefix(&self.prefix)?;
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+}
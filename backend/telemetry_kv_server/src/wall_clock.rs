@@ -0,0 +1,67 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::types::{FlightMetadata, TelemetryPacket};
+
+/// Milliseconds since the Unix epoch, 0 if the clock is set before it
+pub fn now_epoch_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Wall-clock time of a packet, from the flight's start epoch plus how far
+/// into the flight the packet is. None for flights without a start epoch.
+pub fn absolute_timestamp(packet: &TelemetryPacket, flight: &FlightMetadata) -> Option<String> {
+    flight.start_epoch_ms
+        .map(|base| format_iso8601((base + packet.timestamp).saturating_sub(flight.start_time)))
+}
+
+/// UTC with millisecond precision, e.g. `2024-06-01T12:34:56.789Z`
+pub fn format_iso8601(epoch_ms: u64) -> String {
+    let secs = epoch_ms / 1000;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60,
+        epoch_ms % 1000,
+    )
+}
+
+/// (year, month, day) of a day count since 1970-01-01, Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_absolute_timestamp() {
+        let flight = FlightMetadata {
+            start_time: 10_000,
+            // 2024-02-29T23:59:59.500Z
+            start_epoch_ms: Some(1_709_251_199_500),
+            ..Default::default()
+        };
+        let packet = TelemetryPacket { timestamp: 10_750, ..Default::default() };
+        assert_eq!(absolute_timestamp(&packet, &flight).as_deref(), Some("2024-03-01T00:00:00.250Z"));
+
+        let legacy = FlightMetadata { start_epoch_ms: None, ..flight };
+        assert_eq!(absolute_timestamp(&packet, &legacy), None);
+
+        assert_eq!(format_iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+    }
+}
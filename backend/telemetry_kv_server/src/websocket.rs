@@ -6,14 +6,24 @@ use axum::{
     response::IntoResponse,
 };
 use tokio::sync::{Mutex, broadcast};
+use tokio::time::{interval_at, Duration, Instant};
 use std::sync::Arc;
 use crate::storage::TelemetryStorage;
-use crate::types::TelemetryPacket;
+use crate::types::{SourcedPacket, Alert, FlightEvent};
+use crate::health::LinkStatus;
+
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const PONG_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Arc<Mutex<TelemetryStorage>>,
-    pub broadcast_tx: broadcast::Sender<TelemetryPacket>,
+    pub broadcast_tx: broadcast::Sender<SourcedPacket>,
+    pub alert_tx: broadcast::Sender<Alert>,
+    pub event_tx: broadcast::Sender<FlightEvent>,
+    pub link: Arc<LinkStatus>,
+    // Last packet received from any source, for clients that only want a snapshot
+    pub latest_packet: Arc<Mutex<Option<SourcedPacket>>>,
 }
 
 pub async fn websocket_handler(
@@ -43,13 +53,91 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) {
         }
     }
     
-    // Stream real-time data
+    // Stream real-time data, pinging so wedged clients don't hold a subscription
     let mut rx = state.broadcast_tx.subscribe();
-    while let Ok(packet) = rx.recv().await {
-        let json = serde_json::to_string(&packet).unwrap();
-        if socket.send(Message::Text(json)).await.is_err() {
-            println!("✗ Client disconnected");
-            break;
+    let mut ping = interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL);
+    let mut last_pong = Instant::now();
+    loop {
+        tokio::select! {
+            received = recv_skipping_lag(&mut rx) => {
+                let Some(packet) = received else {
+                    break;
+                };
+                let json = serde_json::to_string(&packet).unwrap();
+                if socket.send(Message::Text(json)).await.is_err() {
+                    println!("✗ Client disconnected");
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                if pong_overdue(last_pong, Instant::now()) {
+                    println!("✗ Client stopped answering pings, closing");
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    println!("✗ Client disconnected");
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                match incoming {
+                    Some(Ok(Message::Pong(_))) => last_pong = Instant::now(),
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                        println!("✗ Client disconnected");
+                        break;
+                    }
+                    Some(Ok(_)) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Next message, resyncing past anything a slow client missed. None once the channel closes.
+async fn recv_skipping_lag<T: Clone>(rx: &mut broadcast::Receiver<T>) -> Option<T> {
+    loop {
+        match rx.recv().await {
+            Ok(item) => return Some(item),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                println!("⚠️  WebSocket client lagging, skipped {} packets", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
         }
     }
 }
+
+/// No pong since before the previous ping went out, plus some slack
+fn pong_overdue(last_pong: Instant, now: Instant) -> bool {
+    now.duration_since(last_pong) > PING_INTERVAL + PONG_TIMEOUT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pong_overdue() {
+        let start = Instant::now();
+        // Pong answering the previous ping arrived in time
+        assert!(!pong_overdue(start, start + PING_INTERVAL));
+        assert!(!pong_overdue(start, start + PING_INTERVAL + PONG_TIMEOUT));
+        // Previous ping went unanswered
+        assert!(pong_overdue(start, start + PING_INTERVAL * 2));
+    }
+
+    #[tokio::test]
+    async fn test_recv_continues_after_lag() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+
+        // Oldest three were overwritten, receiving picks up at the next one kept
+        assert_eq!(recv_skipping_lag(&mut rx).await, Some(3));
+        assert_eq!(recv_skipping_lag(&mut rx).await, Some(4));
+
+        drop(tx);
+        assert_eq!(recv_skipping_lag(&mut rx).await, None);
+    }
+}
@@ -0,0 +1,417 @@
+//! The binary telemetry packet shared by the simulator and the KV server,
+//! so both sides agree on its layout.
+
+use serde::{Deserialize, Serialize};
+use flight_phase::FlightPhase;
+
+/// Binary frames start with these two bytes, then the version
+pub const FRAME_MAGIC: [u8; 2] = *b"XT";
+/// Frame version written by `to_bytes`. Version 0 is the headerless frame of
+/// older firmware.
+pub const FRAME_VERSION: u8 = 1;
+const HEADER_SIZE: usize = FRAME_MAGIC.len() + 1;
+// The fields in order, little-endian, unpadded
+const FIELDS_SIZE: usize = 30 + 12 + 36 + 16 + 6 + 13;
+/// Bytes in a frame written by `to_bytes`
+pub const PACKET_SIZE: usize = HEADER_SIZE + FIELDS_SIZE;
+
+/// Field bytes a frame of `version` carries, None for versions this build
+/// doesn't know
+fn fields_size(version: u8) -> Option<usize> {
+    match version {
+        0 | 1 => Some(FIELDS_SIZE),
+        _ => None,
+    }
+}
+
+/// Telemetry packet matching ESP32 LoRa hardware format
+/// 
+/// Architecture:
+/// ESP32 (binary) → KV Store Backend → WebSocket (JSON) → Frontend
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TelemetryPacket {
+    // GPS
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude_gps: f32,
+    pub ground_speed: f32,
+    pub heading: f32,
+    pub num_satellites: u8,
+    pub gps_fix_type: u8,
+    
+    // Barometer
+    pub altitude_baro: f32,
+    pub vertical_speed: f32,
+    pub temperature: f32,
+    
+    // IMU
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub gyro_x: f32,
+    pub gyro_y: f32,
+    pub gyro_z: f32,
+    pub accel_x: f32,
+    pub accel_y: f32,
+    pub accel_z: f32,
+    
+    // Power
+    pub battery_voltage: f32,
+    pub battery_current: f32,
+    pub battery_power: f32,
+    pub battery_mah_used: f32,
+    
+    // Communication
+    pub rssi: i16,
+    pub snr: f32,
+    
+    // System
+    pub timestamp: u64,
+    pub packet_sequence: u32,
+    pub system_status: u8,
+}
+
+impl TelemetryPacket {
+    /// Phase as the KV server classifies this packet
+    pub fn flight_phase(&self) -> FlightPhase {
+        flight_phase::classify(self.altitude_baro, self.ground_speed, self.vertical_speed)
+    }
+    
+    pub fn get_flight_phase(&self) -> &'static str {
+        self.flight_phase().as_str()
+    }
+    
+    /// Whether the GPS fields describe a real position
+    pub fn check_gps(&self) -> Result<(), InvalidGps> {
+        if self.gps_fix_type == 0 {
+            Err(InvalidGps::NoFix)
+        } else if self.latitude == 0.0 && self.longitude == 0.0 {
+            Err(InvalidGps::NullIsland)
+        } else {
+            Ok(())
+        }
+    }
+    
+    /// Serialize to binary format (little-endian) matching ESP32 output
+    /// This is what the ESP32 LoRa will transmit
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PACKET_SIZE);
+        bytes.extend_from_slice(&FRAME_MAGIC);
+        bytes.push(FRAME_VERSION);
+        
+        // GPS (8 + 8 + 4 + 4 + 4 + 1 + 1 = 30 bytes)
+        bytes.extend_from_slice(&self.latitude.to_le_bytes());
+        bytes.extend_from_slice(&self.longitude.to_le_bytes());
+        bytes.extend_from_slice(&self.altitude_gps.to_le_bytes());
+        bytes.extend_from_slice(&self.ground_speed.to_le_bytes());
+        bytes.extend_from_slice(&self.heading.to_le_bytes());
+        bytes.push(self.num_satellites);
+        bytes.push(self.gps_fix_type);
+        
+        // Barometer (4 + 4 + 4 = 12 bytes)
+        bytes.extend_from_slice(&self.altitude_baro.to_le_bytes());
+        bytes.extend_from_slice(&self.vertical_speed.to_le_bytes());
+        bytes.extend_from_slice(&self.temperature.to_le_bytes());
+        
+        // IMU (4 * 9 = 36 bytes)
+        bytes.extend_from_slice(&self.roll.to_le_bytes());
+        bytes.extend_from_slice(&self.pitch.to_le_bytes());
+        bytes.extend_from_slice(&self.yaw.to_le_bytes());
+        bytes.extend_from_slice(&self.gyro_x.to_le_bytes());
+        bytes.extend_from_slice(&self.gyro_y.to_le_bytes());
+        bytes.extend_from_slice(&self.gyro_z.to_le_bytes());
+        bytes.extend_from_slice(&self.accel_x.to_le_bytes());
+        bytes.extend_from_slice(&self.accel_y.to_le_bytes());
+        bytes.extend_from_slice(&self.accel_z.to_le_bytes());
+        
+        // Power (4 * 4 = 16 bytes)
+        bytes.extend_from_slice(&self.battery_voltage.to_le_bytes());
+        bytes.extend_from_slice(&self.battery_current.to_le_bytes());
+        bytes.extend_from_slice(&self.battery_power.to_le_bytes());
+        bytes.extend_from_slice(&self.battery_mah_used.to_le_bytes());
+        
+        // Communication (2 + 4 = 6 bytes)
+        bytes.extend_from_slice(&self.rssi.to_le_bytes());
+        bytes.extend_from_slice(&self.snr.to_le_bytes());
+        
+        // System (8 + 4 + 1 = 13 bytes)
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+        bytes.extend_from_slice(&self.packet_sequence.to_le_bytes());
+        bytes.push(self.system_status);
+        
+        bytes
+    }
+    
+    /// Deserialize from binary format (little-endian)
+    /// Used for testing and receiving from ESP32
+    pub fn from_bytes(frame: &[u8]) -> Result<Self, FrameError> {
+        let (version, bytes) = match frame {
+            // Older firmware sends the bare fields, whose first bytes may
+            // happen to read as the magic
+            _ if frame.len() == FIELDS_SIZE => (0, frame),
+            [m0, m1, version, fields @ ..] if [*m0, *m1] == FRAME_MAGIC => (*version, fields),
+            _ => return Err(FrameError::BadMagic),
+        };
+        let expected = fields_size(version).ok_or(FrameError::UnsupportedVersion(version))?;
+        if bytes.len() != expected {
+            return Err(FrameError::WrongLength { expected, actual: bytes.len() });
+        }
+        
+        let mut offset = 0;
+        
+        // Helper macro to read values
+        macro_rules! read_f64 {
+            () => {{
+                let val = f64::from_le_bytes(bytes[offset..offset+8].try_into().unwrap());
+                offset += 8;
+                val
+            }};
+        }
+        
+        macro_rules! read_f32 {
+            () => {{
+                let val = f32::from_le_bytes(bytes[offset..offset+4].try_into().unwrap());
+                offset += 4;
+                val
+            }};
+        }
+        
+        macro_rules! read_u8 {
+            () => {{
+                let val = bytes[offset];
+                offset += 1;
+                val
+            }};
+        }
+        
+        macro_rules! read_i16 {
+            () => {{
+                let val = i16::from_le_bytes(bytes[offset..offset+2].try_into().unwrap());
+                offset += 2;
+                val
+            }};
+        }
+        
+        macro_rules! read_u64 {
+            () => {{
+                let val = u64::from_le_bytes(bytes[offset..offset+8].try_into().unwrap());
+                offset += 8;
+                val
+            }};
+        }
+        
+        macro_rules! read_u32 {
+            () => {{
+                let val = u32::from_le_bytes(bytes[offset..offset+4].try_into().unwrap());
+                offset += 4;
+                val
+            }};
+        }
+        
+        let packet = TelemetryPacket {
+            // GPS
+            latitude: read_f64!(),
+            longitude: read_f64!(),
+            altitude_gps: read_f32!(),
+            ground_speed: read_f32!(),
+            heading: read_f32!(),
+            num_satellites: read_u8!(),
+            gps_fix_type: read_u8!(),
+            
+            // Barometer
+            altitude_baro: read_f32!(),
+            vertical_speed: read_f32!(),
+            temperature: read_f32!(),
+            
+            // IMU
+            roll: read_f32!(),
+            pitch: read_f32!(),
+            yaw: read_f32!(),
+            gyro_x: read_f32!(),
+            gyro_y: read_f32!(),
+            gyro_z: read_f32!(),
+            accel_x: read_f32!(),
+            accel_y: read_f32!(),
+            accel_z: read_f32!(),
+            
+            // Power
+            battery_voltage: read_f32!(),
+            battery_current: read_f32!(),
+            battery_power: read_f32!(),
+            battery_mah_used: read_f32!(),
+            
+            // Communication
+            rssi: read_i16!(),
+            snr: read_f32!(),
+            
+            // System
+            timestamp: read_u64!(),
+            packet_sequence: read_u32!(),
+            system_status: read_u8!(),
+        };
+        debug_assert_eq!(offset, FIELDS_SIZE);
+        Ok(packet)
+    }
+}
+
+/// Why a binary frame couldn't be read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+    /// Neither a versioned frame nor a headerless one
+    BadMagic,
+    /// Written by firmware newer than this build
+    UnsupportedVersion(u8),
+    /// Field bytes don't match what the frame's version carries
+    WrongLength { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::BadMagic => write!(f, "not a telemetry frame"),
+            FrameError::UnsupportedVersion(version) => {
+                write!(f, "unsupported telemetry frame version {}", version)
+            }
+            FrameError::WrongLength { expected, actual } => {
+                write!(f, "telemetry frame has {} field bytes, expected {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// Why a packet's position can't be trusted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidGps {
+    NoFix,
+    /// 0,0 is what receivers report while they have nothing
+    NullIsland,
+}
+
+impl std::fmt::Display for InvalidGps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidGps::NoFix => write!(f, "no GPS fix"),
+            InvalidGps::NullIsland => write!(f, "GPS position is 0,0"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidGps {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn sample_packet() -> TelemetryPacket {
+        TelemetryPacket {
+            latitude: 49.8728,
+            longitude: 8.6512,
+            altitude_gps: 151.2,
+            ground_speed: 25.0,
+            heading: 270.5,
+            num_satellites: 10,
+            gps_fix_type: 3,
+            altitude_baro: 150.0,
+            vertical_speed: -0.4,
+            temperature: 18.5,
+            roll: -3.0,
+            pitch: 1.5,
+            yaw: 270.5,
+            gyro_x: 0.1,
+            gyro_y: -0.2,
+            gyro_z: 0.3,
+            accel_x: 0.05,
+            accel_y: -0.05,
+            accel_z: 9.81,
+            battery_voltage: 15.2,
+            battery_current: 10.0,
+            battery_power: 152.0,
+            battery_mah_used: 1234.5,
+            rssi: -65,
+            snr: 8.5,
+            timestamp: 123_456,
+            packet_sequence: 42,
+            system_status: 0x08,
+        }
+    }
+    
+    #[test]
+    fn test_bytes_roundtrip() {
+        let packet = sample_packet();
+        let bytes = packet.to_bytes();
+        assert_eq!(bytes.len(), PACKET_SIZE);
+        
+        let decoded = TelemetryPacket::from_bytes(&bytes).unwrap();
+        // Every field survives
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.latitude, packet.latitude);
+        assert_eq!(decoded.rssi, packet.rssi);
+        assert_eq!(decoded.timestamp, packet.timestamp);
+        assert_eq!(decoded.system_status, packet.system_status);
+    }
+    
+    #[test]
+    fn test_from_bytes_requires_packet_size() {
+        let bytes = sample_packet().to_bytes();
+        assert_eq!(
+            TelemetryPacket::from_bytes(&bytes[..PACKET_SIZE - 1]).unwrap_err(),
+            FrameError::WrongLength { expected: FIELDS_SIZE, actual: FIELDS_SIZE - 1 }
+        );
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(TelemetryPacket::from_bytes(&longer).is_err());
+        assert_eq!(TelemetryPacket::from_bytes(&[0; 10]).unwrap_err(), FrameError::BadMagic);
+    }
+    
+    #[test]
+    fn test_current_frame_has_header() {
+        let bytes = sample_packet().to_bytes();
+        assert_eq!(bytes[..2], FRAME_MAGIC);
+        assert_eq!(bytes[2], FRAME_VERSION);
+        assert_eq!(TelemetryPacket::from_bytes(&bytes).unwrap().packet_sequence, 42);
+    }
+    
+    #[test]
+    fn test_headerless_frame_is_version_0() {
+        let bytes = sample_packet().to_bytes();
+        let decoded = TelemetryPacket::from_bytes(&bytes[HEADER_SIZE..]).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        
+        // A latitude whose low bytes spell the magic
+        let mut fields = bytes[HEADER_SIZE..].to_vec();
+        fields[..2].copy_from_slice(&FRAME_MAGIC);
+        let decoded = TelemetryPacket::from_bytes(&fields).unwrap();
+        assert_eq!(decoded.to_bytes()[HEADER_SIZE..], fields[..]);
+    }
+    
+    #[test]
+    fn test_future_version_is_rejected() {
+        // A newer firmware that appended a field
+        let mut bytes = sample_packet().to_bytes();
+        bytes[2] = FRAME_VERSION + 1;
+        bytes.extend_from_slice(&7.5f32.to_le_bytes());
+        assert_eq!(
+            TelemetryPacket::from_bytes(&bytes).unwrap_err(),
+            FrameError::UnsupportedVersion(FRAME_VERSION + 1)
+        );
+    }
+    
+    #[test]
+    fn test_check_gps() {
+        let fixed = sample_packet();
+        assert_eq!(fixed.check_gps(), Ok(()));
+        assert_eq!(TelemetryPacket { gps_fix_type: 0, ..fixed }.check_gps(), Err(InvalidGps::NoFix));
+        assert_eq!(TelemetryPacket { latitude: 0.0, longitude: 0.0, ..fixed }.check_gps(), Err(InvalidGps::NullIsland));
+        // Crossing the equator or the prime meridian is fine
+        assert_eq!(TelemetryPacket { latitude: 0.0, ..fixed }.check_gps(), Ok(()));
+    }
+    
+    #[test]
+    fn test_cruise_packet_phase() {
+        assert_eq!(sample_packet().flight_phase(), FlightPhase::Cruise);
+        assert_eq!(sample_packet().get_flight_phase(), "Cruise");
+    }
+}
@@ -1,31 +1,151 @@
-use crate::telemetry::TelemetryPacket;
-use crate::trajectory::{get_flight_state_at_time, FlightPhase};
+use telemetry_proto::TelemetryPacket;
+use crate::trajectory::{
+    get_flight_state_at_time, forced_landing_state, apply_wind, turbulence, wind_factor,
+    TrajectoryPhase, FlightState, Route, Wind,
+};
 use rand::Rng;
 use std::time::Instant;
 
+/// LiPo pack with a per-cell discharge curve and internal resistance sag
+#[derive(Debug, Clone, Copy)]
+pub struct Battery {
+    pub capacity_mah: f32,
+    pub cells: u8,
+    pub mah_used: f32,
+}
+
+// Resting cell voltage by state of charge (1.0 = full)
+const CELL_DISCHARGE_CURVE: [(f32, f32); 11] = [
+    (0.0, 3.30), (0.1, 3.55), (0.2, 3.66), (0.3, 3.71), (0.4, 3.75), (0.5, 3.79),
+    (0.6, 3.84), (0.7, 3.90), (0.8, 3.98), (0.9, 4.08), (1.0, 4.20),
+];
+const CELL_CUTOFF_VOLTAGE: f32 = 3.3;
+const CELL_RESISTANCE_OHM: f32 = 0.015;
+
+impl Battery {
+    pub fn new(capacity_mah: f32, cells: u8) -> Self {
+        Self { capacity_mah, cells, mah_used: 0.0 }
+    }
+    
+    pub fn state_of_charge(&self) -> f32 {
+        (1.0 - self.mah_used / self.capacity_mah).clamp(0.0, 1.0)
+    }
+    
+    pub fn cutoff_voltage(&self) -> f32 {
+        CELL_CUTOFF_VOLTAGE * self.cells as f32
+    }
+    
+    /// Draw `current_a` for `dt_secs`
+    pub fn drain(&mut self, current_a: f32, dt_secs: f32) {
+        self.mah_used += current_a * dt_secs / 3.6;
+    }
+    
+    /// Terminal voltage under load, never below cutoff
+    pub fn voltage(&self, current_a: f32) -> f32 {
+        let soc = self.state_of_charge();
+        let cell_ocv = CELL_DISCHARGE_CURVE.windows(2)
+            .find(|w| soc <= w[1].0)
+            .map(|w| {
+                let (soc0, v0) = w[0];
+                let (soc1, v1) = w[1];
+                v0 + (v1 - v0) * (soc - soc0) / (soc1 - soc0)
+            })
+            .unwrap_or(CELL_DISCHARGE_CURVE[10].1);
+        
+        let cell_voltage = cell_ocv - current_a * CELL_RESISTANCE_OHM;
+        (cell_voltage * self.cells as f32).max(self.cutoff_voltage())
+    }
+}
+
 pub struct Generator {
     start_time: Instant,
     packet_seq: u32,
-    battery_start: f32,
     prev_heading: f32,
+    wind: Wind,
+    route: Route,
+    battery: Battery,
+    // Times in ms since the simulator started, exact however long it runs
+    last_elapsed_ms: u64,
+    cycle_start_ms: u64,                        // Trajectory time origin, reset after a pack swap
+    forced_landing: Option<(u64, FlightState)>, // Start time and state when cutoff hit
 }
 
+// Turbulence amplitude per m/s of felt wind
+const TURBULENCE_ROLL_DEG: f32 = 0.8;
+const TURBULENCE_PITCH_DEG: f32 = 0.4;
+const TURBULENCE_VSPEED: f32 = 0.15;
+
 impl Generator {
     pub fn new() -> Self {
         Self {
             start_time: Instant::now(),
             packet_seq: 0,
-            battery_start: 16.8,
             prev_heading: 90.0,
+            wind: Wind::default(),
+            route: Route::default(),
+            battery: Battery::new(5000.0, 4), // 4S, 16.8V full
+            last_elapsed_ms: 0,
+            cycle_start_ms: 0,
+            forced_landing: None,
         }
     }
     
+    /// Fly on a different pack (capacity in mAh, cells in series)
+    pub fn with_battery(mut self, capacity_mah: f32, cells: u8) -> Self {
+        self.battery = Battery::new(capacity_mah, cells);
+        self
+    }
+    
+    /// Fly a custom cruise route instead of the built-in rectangle
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.route = route;
+        self
+    }
+    
+    /// Fly in a steady wind (m/s, direction it blows from in degrees)
+    pub fn with_wind(mut self, speed: f32, direction_deg: f32) -> Self {
+        self.wind = Wind { speed, direction_deg };
+        self
+    }
+    
     pub fn generate_packet(&mut self) -> TelemetryPacket {
-        let elapsed = self.start_time.elapsed().as_secs_f32();
+        let elapsed_ms = self.start_time.elapsed().as_millis() as u64;
+        self.generate_packet_at(elapsed_ms)
+    }
+    
+    /// Swap in a fresh pack and restart the flight cycle from rest
+    fn swap_battery(&mut self, elapsed_ms: u64) {
+        self.battery.mah_used = 0.0;
+        self.cycle_start_ms = elapsed_ms;
+        self.forced_landing = None;
+    }
+    
+    fn trajectory_state(&mut self, elapsed_ms: u64) -> FlightState {
+        if let Some((start_ms, from)) = self.forced_landing {
+            if let Some(state) = forced_landing_state(&from, &self.route, secs_between(start_ms, elapsed_ms)) {
+                return state;
+            }
+            println!("🔋 Forced landing complete - swapping battery");
+            self.swap_battery(elapsed_ms);
+        }
+        get_flight_state_at_time(secs_between(self.cycle_start_ms, elapsed_ms), &self.route)
+    }
+    
+    /// Generate the packet for `elapsed_ms` milliseconds since the simulator started
+    pub fn generate_packet_at(&mut self, elapsed_ms: u64) -> TelemetryPacket {
         let mut rng = rand::thread_rng();
+        let dt = secs_between(self.last_elapsed_ms, elapsed_ms);
+        self.last_elapsed_ms = elapsed_ms;
+        // Only the turbulence noise runs on absolute time, where f32 rounding is harmless
+        let elapsed = elapsed_ms as f32 / 1000.0;
         
         // Get flight state from trajectory
-        let state = get_flight_state_at_time(elapsed);
+        let still_air = self.trajectory_state(elapsed_ms);
+        let mut state = apply_wind(still_air, &self.wind);
+        
+        // Turbulence scales with the wind actually felt at this altitude
+        let gust = self.wind.speed * wind_factor(&still_air);
+        state.vertical_speed += turbulence(elapsed, 3) * gust * TURBULENCE_VSPEED;
         
         // Add GPS noise
         let lat = state.lat + rng.gen_range(-0.000005..0.000005);
@@ -43,37 +163,45 @@ impl Generator {
         
         // Calculate roll angle (bank angle during turns)
         // Standard rate turn: ~15 degrees of bank per 3 deg/sec turn rate
-        let turn_rate = heading_change * 2.0; // We sample at 2 Hz
-        let roll = (turn_rate * 5.0).clamp(-30.0, 30.0); // Max 30 degrees bank
+        let turn_rate = if dt > 0.0 { heading_change / dt } else { 0.0 };
+        let roll = (turn_rate * 5.0 + turbulence(elapsed, 1) * gust * TURBULENCE_ROLL_DEG)
+            .clamp(-30.0, 30.0); // Max 30 degrees bank
         
         // Calculate pitch angle based on vertical speed and ground speed
         let pitch = if state.ground_speed > 1.0 {
-            (state.vertical_speed / state.ground_speed).atan().to_degrees().clamp(-15.0, 15.0)
+            (state.vertical_speed / state.ground_speed).atan().to_degrees()
+                + turbulence(elapsed, 2) * gust * TURBULENCE_PITCH_DEG
         } else {
             0.0
-        };
+        }.clamp(-15.0, 15.0);
         
         self.prev_heading = state.heading;
         
-        // Battery drain varies by phase
-        let battery_drain_rate = match state.phase {
-            FlightPhase::Rest => 0.0,
-            FlightPhase::Taxi => 0.3,
-            FlightPhase::Takeoff => 1.5,  // High power during climb
-            FlightPhase::Cruise => 0.8,
-            FlightPhase::Landing => 0.6,
-        };
-        let battery_voltage = self.battery_start - (elapsed / 600.0) * battery_drain_rate;
-        
         // Current varies by phase
         let battery_current = match state.phase {
-            FlightPhase::Rest => 0.5 + rng.gen_range(-0.1..0.1),
-            FlightPhase::Taxi => 5.0 + rng.gen_range(-1.0..1.0),
-            FlightPhase::Takeoff => 15.0 + rng.gen_range(-2.0..2.0),
-            FlightPhase::Cruise => 10.0 + rng.gen_range(-1.5..1.5),
-            FlightPhase::Landing => 8.0 + rng.gen_range(-1.0..1.0),
+            TrajectoryPhase::Rest => 0.5 + rng.gen_range(-0.1..0.1),
+            TrajectoryPhase::Taxi => 5.0 + rng.gen_range(-1.0..1.0),
+            TrajectoryPhase::Takeoff => 15.0 + rng.gen_range(-2.0..2.0),  // High power during climb
+            TrajectoryPhase::Cruise => 10.0 + rng.gen_range(-1.5..1.5),
+            TrajectoryPhase::Hover => 13.0 + rng.gen_range(-1.5..1.5),   // Holding position costs more than cruising
+            TrajectoryPhase::Landing => 8.0 + rng.gen_range(-1.0..1.0),
         };
         
+        self.battery.drain(battery_current, dt);
+        let battery_voltage = self.battery.voltage(battery_current);
+        
+        // Hitting cutoff ends the flight early
+        if battery_voltage <= self.battery.cutoff_voltage() && self.forced_landing.is_none() {
+            match still_air.phase {
+                TrajectoryPhase::Takeoff | TrajectoryPhase::Cruise | TrajectoryPhase::Hover => {
+                    println!("🔋 Battery at cutoff ({:.2}V) - forcing landing", battery_voltage);
+                    self.forced_landing = Some((elapsed_ms, still_air));
+                }
+                TrajectoryPhase::Rest | TrajectoryPhase::Taxi => self.swap_battery(elapsed_ms),
+                TrajectoryPhase::Landing => {}
+            }
+        }
+        
         // RSSI decreases with altitude
         let rssi = -50 - ((state.alt / 10.0) as i16);
         
@@ -94,7 +222,7 @@ impl Generator {
             altitude_gps: state.alt + rng.gen_range(-1.0..1.0),
             ground_speed: state.ground_speed + rng.gen_range(-0.5..0.5),
             heading: state.heading,
-            num_satellites: if state.phase == FlightPhase::Rest { 8 } else { 10 },
+            num_satellites: if state.phase == TrajectoryPhase::Rest { 8 } else { 10 },
             gps_fix_type: 3,
             
             // Barometer
@@ -117,21 +245,22 @@ impl Generator {
             battery_voltage,
             battery_current,
             battery_power: battery_voltage * battery_current,
-            battery_mah_used: battery_current * elapsed / 3.6,
+            battery_mah_used: self.battery.mah_used,
             
             // Communication
             rssi,
             snr: 8.0 + rng.gen_range(-2.0..2.0),
             
             // System
-            timestamp: self.start_time.elapsed().as_millis() as u64,
+            timestamp: elapsed_ms,
             packet_sequence: self.packet_seq,
             system_status: match state.phase {
-                FlightPhase::Rest => 0x01,      // Idle
-                FlightPhase::Taxi => 0x02,      // Taxiing
-                FlightPhase::Takeoff => 0x04,   // Takeoff
-                FlightPhase::Cruise => 0x08,    // Cruise
-                FlightPhase::Landing => 0x10,   // Landing
+                TrajectoryPhase::Rest => 0x01,      // Idle
+                TrajectoryPhase::Taxi => 0x02,      // Taxiing
+                TrajectoryPhase::Takeoff => 0x04,   // Takeoff
+                TrajectoryPhase::Cruise => 0x08,    // Cruise
+                TrajectoryPhase::Hover => 0x20,     // Hover
+                TrajectoryPhase::Landing => 0x10,   // Landing
             },
         };
         
@@ -139,3 +268,100 @@ impl Generator {
         packet
     }
 }
+
+/// Seconds from `from_ms` to `to_ms`, 0 if `to_ms` is earlier. Taking the
+/// difference before converting keeps it exact on long runs.
+fn secs_between(from_ms: u64, to_ms: u64) -> f32 {
+    to_ms.saturating_sub(from_ms) as f32 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiny_pack_depletes_and_forces_landing() {
+        let mut gen = Generator::new().with_battery(200.0, 4);
+        let cutoff = gen.battery.cutoff_voltage();
+
+        let mut hit_cutoff = false;
+        let mut forced = false;
+        for t in (0..240_000).step_by(500) {
+            let packet = gen.generate_packet_at(t);
+            assert!(packet.battery_voltage > 0.0);
+            assert!(packet.battery_voltage >= cutoff);
+            hit_cutoff |= packet.battery_voltage <= cutoff;
+            forced |= gen.forced_landing.is_some();
+        }
+
+        assert!(hit_cutoff);
+        assert!(forced);
+    }
+
+    #[test]
+    fn test_yaw_rate_follows_sample_interval() {
+        // Same 5 degree heading change, sampled at 2 Hz and 10 Hz
+        for dt_ms in [500, 100] {
+            let mut gen = Generator::new();
+            let t = 150_000;
+            gen.generate_packet_at(t - dt_ms);
+            gen.prev_heading = get_flight_state_at_time(150.0, &gen.route).heading - 5.0;
+            let packet = gen.generate_packet_at(t);
+            let expected = 5.0 / (dt_ms as f32 / 1000.0);
+            assert!((packet.gyro_z - expected).abs() <= 1.0, "dt {}ms: yaw rate {}", dt_ms, packet.gyro_z);
+        }
+    }
+
+    #[test]
+    fn test_timestamps_stay_exact_on_long_runs() {
+        // About 25 days in, where f32 seconds step by a quarter second
+        let start = 25 * 24 * 3600 * 1000 + 7;
+        let mut gen = Generator::new();
+        let timestamps: Vec<u64> = (0..5).map(|i| gen.generate_packet_at(start + i * 10).timestamp).collect();
+        assert_eq!(timestamps, (0..5).map(|i| start + i * 10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_voltage_sags_under_load() {
+        let battery = Battery::new(5000.0, 4);
+        assert!((battery.voltage(0.0) - 16.8).abs() < 0.01);
+        assert!(battery.voltage(15.0) < battery.voltage(0.5));
+    }
+
+    #[test]
+    fn test_cruise_classified_as_cruise() {
+        use flight_phase::FlightPhase;
+        
+        let route = Route::default();
+        let t = 150.0;
+        let state = get_flight_state_at_time(t, &route);
+        assert_eq!(state.phase, TrajectoryPhase::Cruise);
+        
+        // Trajectory state and the packet the server receives land in the same phase
+        let from_state = flight_phase::classify(state.alt, state.ground_speed, state.vertical_speed);
+        let packet = Generator::new().generate_packet_at(150_000);
+        assert_eq!(from_state, FlightPhase::Cruise);
+        assert_eq!(packet.flight_phase(), from_state);
+    }
+
+    #[test]
+    fn test_hover_holds_altitude_at_zero_speed() {
+        use flight_phase::FlightPhase;
+        
+        let route = Route::default();
+        let mut gen = Generator::new();
+        let mut hover_packets = 0;
+        for t in (0..300_000).step_by(500) {
+            let packet = gen.generate_packet_at(t);
+            if get_flight_state_at_time(t as f32 / 1000.0, &route).phase == TrajectoryPhase::Hover {
+                assert!(packet.ground_speed.abs() < 1.0);
+                assert!(packet.altitude_baro > 100.0);
+                assert_eq!(packet.pitch, 0.0);
+                assert_eq!(packet.system_status, 0x20);
+                assert_eq!(packet.flight_phase(), FlightPhase::Hover);
+                hover_packets += 1;
+            }
+        }
+        assert!(hover_packets > 0);
+    }
+}
@@ -1,17 +1,61 @@
-mod telemetry;
 mod trajectory;
 mod generator;
 mod server;
 
 use generator::Generator;
+use trajectory::Route;
 use server::{create_router, AppState};
 use tokio::time::{interval, Duration};
 use tokio::sync::broadcast;
+use std::net::SocketAddr;
+
+// Largest pack the simulator flies, 14S
+const MAX_CELLS: f32 = 14.0;
 
 #[tokio::main]
 async fn main() {
     println!("🚀 XtraChallenge Telemetry Simulator\n");
     
+    let addr = std::env::var("SIM_BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(_) => {
+            eprintln!("SIM_BIND_ADDR '{}' is not a valid address, expected host:port like 0.0.0.0:8080", addr);
+            std::process::exit(1);
+        }
+    };
+    let rate_hz = env_f32("SIM_RATE_HZ").unwrap_or(2.0);
+    if !(rate_hz > 0.0 && rate_hz <= 100.0) {
+        eprintln!("SIM_RATE_HZ must be between 0 and 100, got {}", rate_hz);
+        std::process::exit(1);
+    }
+    
+    // Optional pack: BATTERY_CAPACITY_MAH and BATTERY_CELLS (default 5000 mAh 4S)
+    let battery = env_f32("BATTERY_CAPACITY_MAH").map(|capacity| {
+        if !(capacity.is_finite() && capacity > 0.0) {
+            eprintln!("BATTERY_CAPACITY_MAH must be above 0, got {}", capacity);
+            std::process::exit(1);
+        }
+        let cells = env_f32("BATTERY_CELLS").unwrap_or(4.0);
+        if !(cells.fract() == 0.0 && (1.0..=MAX_CELLS).contains(&cells)) {
+            eprintln!("BATTERY_CELLS must be a whole number from 1 to {}, got {}", MAX_CELLS, cells);
+            std::process::exit(1);
+        }
+        (capacity, cells as u8)
+    });
+    
+    // Optional cruise route: --route route.json
+    let route = route_arg().map(|path| match Route::load(&path) {
+        Ok(route) => {
+            println!("🗺️  Loaded route from {}", path);
+            route
+        }
+        Err(e) => {
+            eprintln!("{} - using built-in route", e);
+            Route::default()
+        }
+    });
+    
     // Create broadcast channel
     let (tx, _rx) = broadcast::channel(100);
     
@@ -19,9 +63,25 @@ async fn main() {
     let gen_tx = tx.clone();
     tokio::spawn(async move {
         let mut gen = Generator::new();
-        let mut ticker = interval(Duration::from_millis(500));
+        if let Some(route) = route {
+            gen = gen.with_route(route);
+        }
         
-        println!("📡 Generator started (2 Hz)\n");
+        if let Some((capacity, cells)) = battery {
+            println!("🔋 Battery {:.0} mAh {}S", capacity, cells);
+            gen = gen.with_battery(capacity, cells);
+        }
+        
+        // Optional steady wind: WIND_SPEED (m/s) and WIND_DIRECTION (deg, blowing from)
+        let wind_speed = env_f32("WIND_SPEED").unwrap_or(0.0);
+        if wind_speed > 0.0 {
+            let direction = env_f32("WIND_DIRECTION").unwrap_or(0.0);
+            println!("💨 Wind {:.1} m/s from {:.0}°", wind_speed, direction);
+            gen = gen.with_wind(wind_speed, direction);
+        }
+        let mut ticker = interval(Duration::from_secs_f32(1.0 / rate_hz));
+        
+        println!("📡 Generator started ({} Hz)\n", rate_hz);
         
         loop {
             ticker.tick().await;
@@ -30,8 +90,9 @@ async fn main() {
             
             // Print to console
             println!(
-                "#{:04} | GPS: {:.6},{:.6} | Alt: {:6.1}m | Batt: {:4.2}V ({:5.1}W) | RSSI: {:4}dBm",
+                "#{:04} | {:10} | GPS: {:.6},{:.6} | Alt: {:6.1}m | Batt: {:4.2}V ({:5.1}W) | RSSI: {:4}dBm",
                 packet.packet_sequence,
+                packet.flight_phase().as_str(),
                 packet.latitude,
                 packet.longitude,
                 packet.altitude_baro,
@@ -49,11 +110,33 @@ async fn main() {
     let state = AppState { tx };
     let app = create_router(state);
     
-    let addr = "0.0.0.0:8080";
     println!("🌐 WebSocket endpoints:");
     println!("   Binary (ESP32 → KV):  ws://{}/ws/binary", addr);
     println!("   JSON (KV → Frontend): ws://{}/ws\n", addr);
     
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to bind {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
     axum::serve(listener, app).await.unwrap();
 }
+
+fn route_arg() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--route" {
+            return args.next();
+        }
+        if let Some(path) = arg.strip_prefix("--route=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+fn env_f32(name: &str) -> Option<f32> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
@@ -8,7 +8,7 @@ use axum::{
     Router,
 };
 use tokio::sync::broadcast;
-use crate::telemetry::TelemetryPacket;
+use telemetry_proto::TelemetryPacket;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -35,7 +35,7 @@ async fn handle_binary_socket(mut socket: WebSocket, state: AppState) {
     
     println!("[Simulator] Binary client connected");
     
-    while let Ok(packet) = rx.recv().await {
+    while let Some(packet) = recv_skipping_lag(&mut rx).await {
         let bytes = packet.to_bytes();
         
         if socket.send(Message::Binary(bytes)).await.is_err() {
@@ -44,3 +44,35 @@ async fn handle_binary_socket(mut socket: WebSocket, state: AppState) {
         }
     }
 }
+
+/// Next packet, resyncing past anything a slow client missed. None once the channel closes.
+async fn recv_skipping_lag<T: Clone>(rx: &mut broadcast::Receiver<T>) -> Option<T> {
+    loop {
+        match rx.recv().await {
+            Ok(item) => return Some(item),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                println!("[Simulator] Binary client lagging, skipped {} packets", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recv_continues_after_lag() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+
+        assert_eq!(recv_skipping_lag(&mut rx).await, Some(3));
+        assert_eq!(recv_skipping_lag(&mut rx).await, Some(4));
+
+        drop(tx);
+        assert_eq!(recv_skipping_lag(&mut rx).await, None);
+    }
+}
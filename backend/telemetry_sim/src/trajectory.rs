@@ -1,15 +1,17 @@
 use std::f64::consts::PI;
+use std::path::Path;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum FlightPhase {
+pub enum TrajectoryPhase {
     Rest,
     Taxi,
     Takeoff,
     Cruise,
+    Hover,
     Landing,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct FlightState {
     pub lat: f64,
     pub lon: f64,
@@ -17,7 +19,14 @@ pub struct FlightState {
     pub heading: f32,
     pub vertical_speed: f32,
     pub ground_speed: f32,
-    pub phase: FlightPhase,
+    pub phase: TrajectoryPhase,
+}
+
+/// Steady wind, direction is where the wind blows FROM (meteorological)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Wind {
+    pub speed: f32,         // m/s
+    pub direction_deg: f32,
 }
 
 // Airport location (Darmstadt area)
@@ -30,9 +39,96 @@ const REST_DURATION: f32 = 45.0;
 const TAXI_DURATION: f32 = 20.0;
 const TAKEOFF_DURATION: f32 = 25.0;
 const CRUISE_DURATION: f32 = 120.0;
+const HOVER_DURATION: f32 = 20.0;
 const LANDING_DURATION: f32 = 30.0;
 
-const TOTAL_FLIGHT_CYCLE: f32 = REST_DURATION + TAXI_DURATION + TAKEOFF_DURATION + CRUISE_DURATION + LANDING_DURATION;
+// Takeoff climbs out from the airport to the first route waypoint
+const TAKEOFF_OFFSET_LAT: f64 = 0.003;
+const TAKEOFF_OFFSET_LON: f64 = 0.005;
+
+const TOTAL_FLIGHT_CYCLE: f32 = REST_DURATION + TAXI_DURATION + TAKEOFF_DURATION + CRUISE_DURATION + HOVER_DURATION + LANDING_DURATION;
+
+// Wind model
+const WIND_FULL_ALTITUDE: f32 = 100.0; // Wind reaches full strength at this height
+const DRIFT_LAG_SECS: f32 = 4.0;       // Autopilot cross-track correction lag
+const METERS_PER_DEG_LAT: f64 = 111_320.0;
+
+/// Cruise route as a closed loop of (lat, lon) waypoints
+#[derive(Debug, Clone)]
+pub struct Route {
+    waypoints: Vec<(f64, f64)>,
+    leg_lengths: Vec<f32>,
+    total_length: f32,
+}
+
+impl Route {
+    /// Build a route, closing it back to the first waypoint if needed
+    pub fn new(mut waypoints: Vec<(f64, f64)>) -> Result<Self, String> {
+        if waypoints.len() < 2 {
+            return Err(format!("Route needs at least 2 waypoints, got {}", waypoints.len()));
+        }
+        if waypoints.first() != waypoints.last() {
+            waypoints.push(waypoints[0]);
+        }
+        
+        let leg_lengths: Vec<f32> = waypoints.windows(2)
+            .map(|w| calculate_distance(w[0].0, w[0].1, w[1].0, w[1].1))
+            .collect();
+        let total_length: f32 = leg_lengths.iter().sum();
+        if total_length <= 0.0 {
+            return Err("Route has zero length".to_string());
+        }
+        
+        Ok(Self { waypoints, leg_lengths, total_length })
+    }
+    
+    /// Load a route from a JSON array of [lat, lon] pairs
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let waypoints: Vec<(f64, f64)> = serde_json::from_str(&json)
+            .map_err(|e| format!("Invalid route {}: {}", path.display(), e))?;
+        Self::new(waypoints)
+    }
+    
+    pub fn start(&self) -> (f64, f64) {
+        self.waypoints[0]
+    }
+    
+    /// The airport sits at a fixed offset from where the route begins
+    pub fn airport(&self) -> (f64, f64) {
+        let (lat, lon) = self.start();
+        (lat - TAKEOFF_OFFSET_LAT, lon - TAKEOFF_OFFSET_LON)
+    }
+    
+    /// Leg endpoints and progress along that leg, legs timed by their length
+    fn leg_at(&self, progress: f32) -> ((f64, f64), (f64, f64), f32) {
+        let mut remaining = progress.clamp(0.0, 1.0) * self.total_length;
+        let last = self.leg_lengths.len() - 1;
+        
+        for (i, &length) in self.leg_lengths.iter().enumerate() {
+            if remaining < length || i == last {
+                let leg_progress = if length > 0.0 { (remaining / length).min(1.0) } else { 1.0 };
+                return (self.waypoints[i], self.waypoints[i + 1], leg_progress);
+            }
+            remaining -= length;
+        }
+        unreachable!("route always has at least one leg")
+    }
+}
+
+impl Default for Route {
+    /// Rectangular pattern north of the airport
+    fn default() -> Self {
+        Self::new(vec![
+            (AIRPORT_LAT + 0.003, AIRPORT_LON + 0.005), // Start (after takeoff)
+            (AIRPORT_LAT + 0.008, AIRPORT_LON + 0.005), // North
+            (AIRPORT_LAT + 0.008, AIRPORT_LON + 0.000), // West
+            (AIRPORT_LAT + 0.003, AIRPORT_LON + 0.000), // South
+        ]).expect("built-in route is valid")
+    }
+}
 
 /// Calculate heading between two GPS coordinates (in degrees)
 fn calculate_heading(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f32 {
@@ -68,22 +164,25 @@ fn interpolate_position(lat1: f64, lon1: f64, lat2: f64, lon2: f64, progress: f3
     (lat, lon)
 }
 
-pub fn get_flight_state_at_time(t: f32) -> FlightState {
+pub fn get_flight_state_at_time(t: f32, route: &Route) -> FlightState {
     // Loop the flight cycle
     let t = t % TOTAL_FLIGHT_CYCLE;
     
+    let (route_lat, route_lon) = route.start();
+    let (airport_lat, airport_lon) = route.airport();
+    
     let mut time_offset = 0.0;
     
     // REST PHASE
     if t < time_offset + REST_DURATION {
         return FlightState {
-            lat: AIRPORT_LAT,
-            lon: AIRPORT_LON,
+            lat: airport_lat,
+            lon: airport_lon,
             alt: 0.0,
             heading: 90.0, // Facing east
             vertical_speed: 0.0,
             ground_speed: 0.0,
-            phase: FlightPhase::Rest,
+            phase: TrajectoryPhase::Rest,
         };
     }
     time_offset += REST_DURATION;
@@ -95,11 +194,11 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
         
         // Taxi to runway (move east)
         let taxi_distance = 0.002; // ~200m in degrees
-        let end_lat = AIRPORT_LAT;
-        let end_lon = AIRPORT_LON + taxi_distance;
+        let end_lat = airport_lat;
+        let end_lon = airport_lon + taxi_distance;
         
-        let (lat, lon) = interpolate_position(AIRPORT_LAT, AIRPORT_LON, end_lat, end_lon, progress);
-        let heading = calculate_heading(AIRPORT_LAT, AIRPORT_LON, end_lat, end_lon);
+        let (lat, lon) = interpolate_position(airport_lat, airport_lon, end_lat, end_lon, progress);
+        let heading = calculate_heading(airport_lat, airport_lon, end_lat, end_lon);
         
         return FlightState {
             lat,
@@ -108,7 +207,7 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
             heading,
             vertical_speed: 0.0,
             ground_speed: 5.0 + progress * 5.0, // Accelerate from 5 to 10 m/s
-            phase: FlightPhase::Taxi,
+            phase: TrajectoryPhase::Taxi,
         };
     }
     time_offset += TAXI_DURATION;
@@ -118,10 +217,9 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
         let phase_time = t - time_offset;
         let progress = phase_time / TAKEOFF_DURATION;
         
-        let start_lat = AIRPORT_LAT;
-        let start_lon = AIRPORT_LON + 0.002;
-        let end_lat = AIRPORT_LAT + 0.003;
-        let end_lon = AIRPORT_LON + 0.005;
+        let start_lat = airport_lat;
+        let start_lon = airport_lon + 0.002;
+        let (end_lat, end_lon) = (route_lat, route_lon);
         
         let (lat, lon) = interpolate_position(start_lat, start_lon, end_lat, end_lon, progress);
         let heading = calculate_heading(start_lat, start_lon, end_lat, end_lon);
@@ -137,28 +235,15 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
             heading,
             vertical_speed,
             ground_speed: 10.0 + progress * 15.0, // Accelerate from 10 to 25 m/s
-            phase: FlightPhase::Takeoff,
+            phase: TrajectoryPhase::Takeoff,
         };
     }
     time_offset += TAKEOFF_DURATION;
     
-    // CRUISE PHASE - Fly a rectangular pattern
+    // CRUISE PHASE - Fly the route
     if t < time_offset + CRUISE_DURATION {
         let phase_time = t - time_offset;
-        let leg_duration = CRUISE_DURATION / 4.0;
-        let leg = (phase_time / leg_duration).floor() as i32;
-        let leg_progress = (phase_time % leg_duration) / leg_duration;
-        
-        let waypoints = [
-            (AIRPORT_LAT + 0.003, AIRPORT_LON + 0.005), // Start (after takeoff)
-            (AIRPORT_LAT + 0.008, AIRPORT_LON + 0.005), // North
-            (AIRPORT_LAT + 0.008, AIRPORT_LON + 0.000), // West
-            (AIRPORT_LAT + 0.003, AIRPORT_LON + 0.000), // South
-            (AIRPORT_LAT + 0.003, AIRPORT_LON + 0.005), // Back to start
-        ];
-        
-        let start_wp = waypoints[leg as usize];
-        let end_wp = waypoints[(leg + 1) as usize];
+        let (start_wp, end_wp, leg_progress) = route.leg_at(phase_time / CRUISE_DURATION);
         
         let (lat, lon) = interpolate_position(start_wp.0, start_wp.1, end_wp.0, end_wp.1, leg_progress);
         let heading = calculate_heading(start_wp.0, start_wp.1, end_wp.0, end_wp.1);
@@ -170,21 +255,34 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
             heading,
             vertical_speed: 0.0,
             ground_speed: 25.0,
-            phase: FlightPhase::Cruise,
+            phase: TrajectoryPhase::Cruise,
         };
     }
     time_offset += CRUISE_DURATION;
     
+    // HOVER PHASE - Hold over the first waypoint, nose toward the airport
+    if t < time_offset + HOVER_DURATION {
+        return FlightState {
+            lat: route_lat,
+            lon: route_lon,
+            alt: CRUISE_ALTITUDE,
+            heading: calculate_heading(route_lat, route_lon, airport_lat, airport_lon),
+            vertical_speed: 0.0,
+            ground_speed: 0.0,
+            phase: TrajectoryPhase::Hover,
+        };
+    }
+    time_offset += HOVER_DURATION;
+    
     // LANDING PHASE
     if t < time_offset + LANDING_DURATION {
         let phase_time = t - time_offset;
         let progress = phase_time / LANDING_DURATION;
         
-        let start_lat = AIRPORT_LAT + 0.003;
-        let start_lon = AIRPORT_LON + 0.005;
+        let (start_lat, start_lon) = (route_lat, route_lon);
         
-        let (lat, lon) = interpolate_position(start_lat, start_lon, AIRPORT_LAT, AIRPORT_LON, progress);
-        let heading = calculate_heading(start_lat, start_lon, AIRPORT_LAT, AIRPORT_LON);
+        let (lat, lon) = interpolate_position(start_lat, start_lon, airport_lat, airport_lon, progress);
+        let heading = calculate_heading(start_lat, start_lon, airport_lat, airport_lon);
         
         // Smooth descent
         let alt = CRUISE_ALTITUDE * (1.0 - progress * progress);
@@ -197,18 +295,215 @@ pub fn get_flight_state_at_time(t: f32) -> FlightState {
             heading,
             vertical_speed,
             ground_speed: 25.0 - progress * 15.0, // Decelerate from 25 to 10 m/s
-            phase: FlightPhase::Landing,
+            phase: TrajectoryPhase::Landing,
         };
     }
     
     // Fallback (should not reach here)
     FlightState {
-        lat: AIRPORT_LAT,
-        lon: AIRPORT_LON,
+        lat: airport_lat,
+        lon: airport_lon,
         alt: 0.0,
         heading: 90.0,
         vertical_speed: 0.0,
         ground_speed: 0.0,
-        phase: FlightPhase::Rest,
+        phase: TrajectoryPhase::Rest,
+    }
+}
+
+/// Emergency landing straight back to the airport from wherever the aircraft
+/// was when it started. Returns `None` once the aircraft is down.
+pub fn forced_landing_state(from: &FlightState, route: &Route, phase_time: f32) -> Option<FlightState> {
+    if phase_time >= LANDING_DURATION {
+        return None;
+    }
+    let progress = phase_time / LANDING_DURATION;
+    let (airport_lat, airport_lon) = route.airport();
+    
+    let (lat, lon) = interpolate_position(from.lat, from.lon, airport_lat, airport_lon, progress);
+    let heading = calculate_heading(from.lat, from.lon, airport_lat, airport_lon);
+    
+    let alt = from.alt * (1.0 - progress * progress);
+    let vertical_speed = -(from.alt / LANDING_DURATION) * (2.0 * progress);
+    let start_speed = from.ground_speed.max(10.0);
+    
+    Some(FlightState {
+        lat,
+        lon,
+        alt,
+        heading,
+        vertical_speed,
+        ground_speed: start_speed - progress * (start_speed - 10.0),
+        phase: TrajectoryPhase::Landing,
+    })
+}
+
+/// Fraction of the configured wind felt at this state (calm on the ground)
+pub fn wind_factor(state: &FlightState) -> f32 {
+    match state.phase {
+        TrajectoryPhase::Rest | TrajectoryPhase::Taxi => 0.0,
+        _ => (state.alt / WIND_FULL_ALTITUDE).clamp(0.0, 1.0),
+    }
+}
+
+/// Apply wind to a still-air flight state.
+///
+/// The trajectory's heading and ground speed are treated as the intended
+/// course and airspeed. Crosswind pushes the aircraft off track by a small
+/// steady offset and forces it to crab into the wind, tail/headwind changes
+/// the ground speed.
+pub fn apply_wind(state: FlightState, wind: &Wind) -> FlightState {
+    let speed = wind.speed * wind_factor(&state);
+    if speed <= 0.0 {
+        return state;
+    }
+    
+    // Wind velocity vector (where the air moves TO)
+    let wind_dir = wind.direction_deg.to_radians();
+    let wind_north = -speed * wind_dir.cos();
+    let wind_east = -speed * wind_dir.sin();
+    
+    let course = state.heading.to_radians();
+    let crosswind = wind_east * course.cos() - wind_north * course.sin(); // + pushes right
+    let tailwind = wind_north * course.cos() + wind_east * course.sin();
+    
+    let airspeed = state.ground_speed.max(1.0);
+    let crab = (crosswind / airspeed).clamp(-1.0, 1.0).asin();
+    let heading = (state.heading - crab.to_degrees() + 360.0) % 360.0;
+    let ground_speed = (airspeed * crab.cos() + tailwind).max(0.0);
+    
+    // Steady cross-track offset to the right of course
+    let offset = (crosswind * DRIFT_LAG_SECS) as f64;
+    let offset_north = -offset * (course as f64).sin();
+    let offset_east = offset * (course as f64).cos();
+    let lat = state.lat + offset_north / METERS_PER_DEG_LAT;
+    let lon = state.lon + offset_east / (METERS_PER_DEG_LAT * state.lat.to_radians().cos());
+    
+    FlightState {
+        lat,
+        lon,
+        heading,
+        ground_speed,
+        ..state
+    }
+}
+
+/// Smooth 1-D value noise in [-1, 1], a few octaves for a gusty feel
+pub fn turbulence(t: f32, seed: u32) -> f32 {
+    fn lattice(i: i32, seed: u32) -> f32 {
+        let mut h = (i as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x85eb_ca6b);
+        h ^= h >> 13;
+        (h as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+    
+    fn value_noise(t: f32, seed: u32) -> f32 {
+        let i = t.floor();
+        let f = t - i;
+        let smooth = f * f * (3.0 - 2.0 * f);
+        let a = lattice(i as i32, seed);
+        let b = lattice(i as i32 + 1, seed);
+        a + (b - a) * smooth
+    }
+    
+    (value_noise(t * 0.5, seed) * 0.6
+        + value_noise(t * 1.3, seed.wrapping_add(1)) * 0.3
+        + value_noise(t * 3.1, seed.wrapping_add(2)) * 0.1)
+        .clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_wind_matches_still_air_path() {
+        let calm = Wind::default();
+        let route = Route::default();
+        let mut t = 0.0;
+        while t < TOTAL_FLIGHT_CYCLE {
+            let state = get_flight_state_at_time(t, &route);
+            assert_eq!(apply_wind(state, &calm), state);
+            t += 0.5;
+        }
+    }
+
+    #[test]
+    fn test_crosswind_induces_crab_and_drift() {
+        let t = REST_DURATION + TAXI_DURATION + TAKEOFF_DURATION + 5.0;
+        let state = get_flight_state_at_time(t, &Route::default());
+        let wind = Wind { speed: 8.0, direction_deg: (state.heading + 90.0) % 360.0 };
+        let windy = apply_wind(state, &wind);
+
+        assert_ne!(windy.heading, state.heading);
+        assert_ne!((windy.lat, windy.lon), (state.lat, state.lon));
+        assert_eq!(windy.alt, state.alt);
+    }
+
+    const CRUISE_START: f32 = REST_DURATION + TAXI_DURATION + TAKEOFF_DURATION;
+
+    #[test]
+    fn test_out_and_back_route() {
+        let a = (49.90, 8.60);
+        let b = (49.91, 8.60);
+        let route = Route::new(vec![a, b]).unwrap();
+
+        let out = get_flight_state_at_time(CRUISE_START + CRUISE_DURATION * 0.25, &route);
+        assert!(out.lat > a.0 && out.lat < b.0);
+        assert!(out.heading < 1.0 || out.heading > 359.0); // Due north
+
+        let back = get_flight_state_at_time(CRUISE_START + CRUISE_DURATION * 0.75, &route);
+        assert!((back.heading - 180.0).abs() < 1.0);
+
+        // Landing heads home from the first waypoint after hovering there
+        let hover = get_flight_state_at_time(CRUISE_START + CRUISE_DURATION, &route);
+        assert_eq!(hover.phase, TrajectoryPhase::Hover);
+        assert!((hover.lat - a.0).abs() < 1e-9);
+        let landing = get_flight_state_at_time(CRUISE_START + CRUISE_DURATION + HOVER_DURATION, &route);
+        assert_eq!(landing.phase, TrajectoryPhase::Landing);
+        assert!((landing.lat - a.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_many_waypoint_route_visits_each_waypoint() {
+        let waypoints = vec![
+            (49.900, 8.600),
+            (49.905, 8.610),
+            (49.910, 8.600),
+            (49.905, 8.590),
+            (49.902, 8.595),
+        ];
+        let route = Route::new(waypoints.clone()).unwrap();
+
+        let mut visited = std::collections::HashSet::new();
+        let mut t = CRUISE_START;
+        while t < CRUISE_START + CRUISE_DURATION {
+            let state = get_flight_state_at_time(t, &route);
+            assert_eq!(state.phase, TrajectoryPhase::Cruise);
+            for (i, wp) in waypoints.iter().enumerate().skip(1) {
+                if (state.lat - wp.0).abs() < 2e-4 && (state.lon - wp.1).abs() < 2e-4 {
+                    visited.insert(i);
+                }
+            }
+            t += 0.5;
+        }
+        for i in 1..waypoints.len() {
+            assert!(visited.contains(&i), "waypoint {} never reached", i);
+        }
+    }
+
+    #[test]
+    fn test_route_needs_two_waypoints() {
+        assert!(Route::new(vec![(49.9, 8.6)]).is_err());
+        assert!(Route::new(vec![(49.9, 8.6), (49.9, 8.6)]).is_err());
+    }
+
+    #[test]
+    fn test_turbulence_is_bounded() {
+        for i in 0..1000 {
+            let v = turbulence(i as f32 * 0.37, 7);
+            assert!((-1.0..=1.0).contains(&v));
+        }
     }
 }